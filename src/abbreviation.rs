@@ -0,0 +1,165 @@
+use crate::validation::DAY_NAMES;
+
+// Page furniture that has turned up inside day blocks on some variants of the page
+pub const UI_LABELS: &[&str] = &["Expand All", "Collapse All", "Read More", "Show More", "Show Less"];
+
+// Tidies one scraped day block for the abbreviated forecast: a line that is only one of the `strip`
+// labels (ignoring case) is dropped, prose that merely contains one ("skies will show more sun") is
+// kept. Each of `elements`, the text of an excluded element inside the block, is removed once where it
+// makes up a line or ends or starts one, as it would for a button after the prose. With the block's
+// heading known, the heading leads the block as its "Tonight:" label. A block none of this applies to
+// is returned as scraped, None when nothing is left, e.g. a block that was only a button
+pub fn clean_block(text: &str, heading: Option<&str>, strip: &[String], elements: &[String]) -> Option<String> {
+    let heading = heading
+        .map(|heading| heading.trim().trim_end_matches(':').trim())
+        .filter(|heading| !heading.is_empty());
+    let is_label = |line: &str| {
+        strip
+            .iter()
+            .any(|label| !label.trim().is_empty() && line.trim().eq_ignore_ascii_case(label.trim()))
+    };
+    let mut elements = elements
+        .iter()
+        .map(|element| element.trim())
+        .filter(|element| !element.is_empty())
+        .collect::<Vec<_>>();
+
+    let mut cleaned = text.to_string();
+    if let Some(heading) = heading {
+        cleaned = remove_first(&cleaned, heading);
+        cleaned = cleaned.trim_start().trim_start_matches(':').to_string();
+    }
+
+    let mut changed = heading.is_some();
+    let mut lines = Vec::new();
+    for line in cleaned.lines() {
+        let mut line = line.to_string();
+        elements.retain(|element| match without_element(&line, element) {
+            Some(rest) => {
+                line = rest;
+                changed = true;
+                false
+            }
+            None => true,
+        });
+        if is_label(&line) {
+            changed = true;
+        } else if !line.trim().is_empty() {
+            lines.push(line);
+        }
+    }
+    if !changed {
+        return Some(text.to_string());
+    }
+    if lines.is_empty() {
+        return None;
+    }
+
+    match heading {
+        Some(heading) => Some(format!("{}:\n{}", heading, lines.join("\n"))),
+        None => Some(lines.join("\n")),
+    }
+}
+
+// The line without `element` where it's the whole line or a word of its own at either end, None
+// when it isn't there like that. ASCII lowercasing keeps byte offsets, so they hold in the line too
+fn without_element(line: &str, element: &str) -> Option<String> {
+    let trimmed = line.trim();
+    let lower = trimmed.to_ascii_lowercase();
+    let element = element.to_ascii_lowercase();
+    if lower == element {
+        return Some(String::new());
+    }
+    if lower.ends_with(&element) {
+        let rest = &trimmed[..trimmed.len() - element.len()];
+        if rest.ends_with(|c: char| !c.is_alphanumeric()) {
+            return Some(rest.trim_end().to_string());
+        }
+    }
+    if lower.starts_with(&element) {
+        let rest = &trimmed[element.len()..];
+        if rest.starts_with(|c: char| !c.is_alphanumeric()) {
+            return Some(rest.trim_start().to_string());
+        }
+    }
+    None
+}
+
+fn remove_first(text: &str, part: &str) -> String {
+    match position_ignoring_case(text, part) {
+        Some(i) => format!("{}{}", &text[..i], &text[i + part.len()..]),
+        None => text.to_string(),
+    }
+}
+
+// ASCII lowercasing keeps byte offsets, so the position is valid in the original text
+fn position_ignoring_case(text: &str, part: &str) -> Option<usize> {
+    text.to_ascii_lowercase().find(&part.to_ascii_lowercase())
+}
+
+// How many of the leading blocks, by their labels, make up the first `days` days. "Tonight" is part of
+// today and "Sunday Night" of Sunday, so a page leading with Today and Tonight still counts them as
+// one. None when a label isn't a day, the boundaries can't be trusted then
+pub fn first_days(labels: &[String], days: usize) -> Option<usize> {
+    let mut seen = 0;
+    let mut current = None;
+    for (i, label) in labels.iter().enumerate() {
+        let day = day_of(label)?;
+        if current.as_ref() != Some(&day) {
+            if seen == days {
+                return Some(i);
+            }
+            seen += 1;
+            current = Some(day);
+        }
+    }
+    Some(labels.len())
+}
+
+fn day_of(label: &str) -> Option<String> {
+    let label = label.trim().trim_end_matches(':').to_ascii_lowercase();
+    let day = label.strip_suffix(" night").unwrap_or(&label);
+    let day = if day == "tonight" { "today" } else { day };
+    DAY_NAMES.contains(&day).then(|| day.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip() -> Vec<String> {
+        UI_LABELS.iter().map(|label| label.to_string()).collect()
+    }
+
+    #[test]
+    fn drops_labels_only_as_whole_lines() {
+        let block = "Sunday:\nClearing skies will show more sun by midday.\nRead More";
+        assert_eq!(
+            clean_block(block, None, &strip(), &[]).as_deref(),
+            Some("Sunday:\nClearing skies will show more sun by midday.")
+        );
+        let prose = "Tonight:\nSkies will show more sun. Expand all gear checks.";
+        assert_eq!(clean_block(prose, None, &strip(), &[]).as_deref(), Some(prose));
+        assert_eq!(clean_block("Expand All", None, &strip(), &[]), None);
+    }
+
+    #[test]
+    fn removes_a_stray_button_after_the_prose() {
+        let block = "Today:\nHighs around 5 below zero. Read More";
+        assert_eq!(
+            clean_block(block, None, &strip(), &["Read More".to_string()]).as_deref(),
+            Some("Today:\nHighs around 5 below zero.")
+        );
+        // Part of a word isn't the button
+        let block = "Today:\nLoading docks will be unloadable";
+        assert_eq!(clean_block(block, None, &strip(), &["able".to_string()]).as_deref(), Some(block));
+    }
+
+    #[test]
+    fn the_heading_leads_the_block() {
+        assert_eq!(
+            clean_block("Tonight Clear. Lows around 20 below.", Some("Tonight"), &strip(), &[]).as_deref(),
+            Some("Tonight:\nClear. Lows around 20 below.")
+        );
+    }
+}
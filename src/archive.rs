@@ -0,0 +1,421 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::change::stable_hash;
+use crate::effects::Effects;
+use crate::fetch::FetchedForecast;
+use crate::forecast::Forecast;
+
+// One per changed revision, by default a file named by when it was archived to the millisecond, e.g.
+// archive/20240210T100712345Z.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedRevision {
+    pub archived: DateTime<Utc>,
+    // When the source captured it, differs from `archived` for fallback sources
+    pub captured: DateTime<Utc>,
+    pub source: String,
+    pub fallback: bool,
+    // The raw page text, so revisions can be re-parsed when the parser improves
+    pub full: String,
+    pub day_blocks: Vec<String>,
+    pub forecast: Forecast,
+}
+
+impl ArchivedRevision {
+    pub fn new(fetched: &FetchedForecast, forecast: &Forecast, archived: DateTime<Utc>) -> ArchivedRevision {
+        ArchivedRevision {
+            archived,
+            captured: fetched.captured,
+            source: fetched.source.clone(),
+            fallback: fetched.fallback,
+            full: fetched.full.clone(),
+            day_blocks: fetched.day_blocks.clone(),
+            forecast: forecast.clone(),
+        }
+    }
+
+    // The revision as the cycle originally saw it, for replays
+    pub fn fetched(&self) -> FetchedForecast {
+        FetchedForecast {
+            full: self.full.clone(),
+            day_blocks: self.day_blocks.clone(),
+            source: self.source.clone(),
+            captured: self.captured,
+            fallback: self.fallback,
+        }
+    }
+}
+
+const INDEX_FILE: &str = "index.json";
+const BUNDLE_EXTENSION: &str = "json.gz";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveFormat {
+    // One pretty-printed JSON file per revision
+    #[default]
+    Files,
+    // Each revision a gzip member appended to that month's bundle, e.g. archive/2024-02.json.gz,
+    // found through archive/index.json
+    Bundles,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ArchiveIndex {
+    pub entries: Vec<IndexEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub archived: DateTime<Utc>,
+    // File name within the archive dir
+    pub bundle: String,
+    pub offset: u64,
+    pub length: u64,
+    // Of the uncompressed JSON
+    pub hash: String,
+}
+
+pub fn archive_revision(
+    effects: &Effects,
+    dir: &Path,
+    format: ArchiveFormat,
+    revision: &ArchivedRevision,
+) -> Result<PathBuf, Box<dyn Error>> {
+    effects.create_dir_all(dir)?;
+    match format {
+        ArchiveFormat::Files => {
+            let path = dir.join(format!("{}.json", revision.archived.format("%Y%m%dT%H%M%S%3fZ")));
+            // Two revisions archived in the same millisecond, the first one stays
+            if effects.exists(&path) {
+                return Err(format!("{} already exists, not archiving over it", path.display()).into());
+            }
+            effects.write_atomic(&path, &serde_json::to_string_pretty(revision)?)?;
+            Ok(path)
+        }
+        ArchiveFormat::Bundles => append_to_bundle(effects, dir, revision),
+    }
+}
+
+// A revision only becomes part of the archive once the index points at it. A crash between the
+// append and the index write leaves unindexed bytes at the end of the bundle, which later
+// appends simply follow and `raven archive verify` reports
+fn append_to_bundle(effects: &Effects, dir: &Path, revision: &ArchivedRevision) -> Result<PathBuf, Box<dyn Error>> {
+    let index_path = dir.join(INDEX_FILE);
+    // Never start a fresh index over one that can't be read, that would orphan every bundle
+    let mut index = match effects.read(&index_path) {
+        Some(json) => serde_json::from_str::<ArchiveIndex>(&json)
+            .map_err(|e| format!("{} is unreadable ({}), not archiving", index_path.display(), e))?,
+        None => ArchiveIndex::default(),
+    };
+
+    let json = serde_json::to_string(revision)?;
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(json.as_bytes())?;
+    let member = encoder.finish()?;
+
+    let bundle = format!("{}.{}", revision.archived.format("%Y-%m"), BUNDLE_EXTENSION);
+    let path = dir.join(&bundle);
+    let offset = effects.append(&path, &member)?;
+
+    index.entries.push(IndexEntry {
+        archived: revision.archived,
+        bundle,
+        offset,
+        length: member.len() as u64,
+        hash: content_hash(&json),
+    });
+    effects.write_atomic(&index_path, &serde_json::to_string_pretty(&index)?)?;
+    Ok(path)
+}
+
+// Every revision archived at or after `since`, from loose files and bundles alike, oldest first.
+// Unreadable entries are logged and skipped, and only the bundled revisions in range are decompressed
+pub fn load_revisions(
+    effects: &Effects,
+    dir: &Path,
+    since: Option<DateTime<Utc>>,
+) -> Result<Vec<ArchivedRevision>, Box<dyn Error>> {
+    let mut revisions = Vec::new();
+    if !effects.exists(dir) {
+        return Ok(revisions);
+    }
+
+    for path in revision_files(effects, dir)? {
+        match read_revision_file(effects, &path) {
+            Ok(revision) if since.is_none_or(|since| revision.archived >= since) => revisions.push(revision),
+            Ok(_) => {}
+            Err(e) => warn!("Skipping unreadable archive entry {}: {}", path.display(), e),
+        }
+    }
+
+    if let Some(index) = read_index(effects, dir)? {
+        for entry in index.entries.iter().filter(|entry| since.is_none_or(|since| entry.archived >= since)) {
+            match read_member(effects, dir, entry) {
+                Ok(revision) => revisions.push(revision),
+                Err(e) => warn!("Skipping unreadable archive entry {} in {}: {}", entry.archived, entry.bundle, e),
+            }
+        }
+    }
+
+    revisions.sort_by_key(|revision| revision.archived);
+    Ok(revisions)
+}
+
+// The loose one-file-per-revision entries
+fn revision_files(effects: &Effects, dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut paths = effects
+        .list(dir)?
+        .into_iter()
+        .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("json") && !path.ends_with(INDEX_FILE))
+        .collect::<Vec<_>>();
+    paths.sort();
+    Ok(paths)
+}
+
+fn read_revision_file(effects: &Effects, path: &Path) -> Result<ArchivedRevision, String> {
+    let json = effects.read(path).ok_or_else(|| format!("can't read {}", path.display()))?;
+    serde_json::from_str(&json).map_err(|e| e.to_string())
+}
+
+fn read_index(effects: &Effects, dir: &Path) -> Result<Option<ArchiveIndex>, Box<dyn Error>> {
+    let path = dir.join(INDEX_FILE);
+    if !effects.exists(&path) {
+        return Ok(None);
+    }
+    let json = effects.read(&path).ok_or_else(|| format!("can't read {}", path.display()))?;
+    let index = serde_json::from_str(&json).map_err(|e| format!("{} is unreadable: {}", path.display(), e))?;
+    Ok(Some(index))
+}
+
+// Decompresses just the one member the entry points at
+fn read_member(effects: &Effects, dir: &Path, entry: &IndexEntry) -> Result<ArchivedRevision, String> {
+    let member = effects
+        .read_bytes(&dir.join(&entry.bundle), entry.offset, entry.length as usize)
+        .map_err(|e| e.to_string())?;
+
+    let mut json = String::new();
+    GzDecoder::new(member.as_slice())
+        .read_to_string(&mut json)
+        .map_err(|e| format!("corrupt member at offset {}: {}", entry.offset, e))?;
+    if content_hash(&json) != entry.hash {
+        return Err(format!("content hash mismatch at offset {}", entry.offset));
+    }
+
+    let revision = serde_json::from_str::<ArchivedRevision>(&json).map_err(|e| e.to_string())?;
+    if revision.archived != entry.archived {
+        return Err(format!("member at offset {} is from {}", entry.offset, revision.archived));
+    }
+    Ok(revision)
+}
+
+pub fn content_hash(text: &str) -> String {
+    format!("{:016x}", stable_hash(text))
+}
+
+pub struct VerifyReport {
+    pub checked: usize,
+    // Missing, corrupt or inconsistent entries
+    pub problems: Vec<String>,
+    // Harmless but worth knowing, e.g. the leftovers of an interrupted append
+    pub notes: Vec<String>,
+}
+
+// Reads back every loose file and indexed member, and checks the index accounts for every bundle's bytes
+pub fn verify_archive(effects: &Effects, dir: &Path) -> Result<VerifyReport, Box<dyn Error>> {
+    let mut report = VerifyReport {
+        checked: 0,
+        problems: Vec::new(),
+        notes: Vec::new(),
+    };
+    if !effects.exists(dir) {
+        report.notes.push(format!("{} does not exist", dir.display()));
+        return Ok(report);
+    }
+
+    for path in revision_files(effects, dir)? {
+        report.checked += 1;
+        if let Err(e) = read_revision_file(effects, &path) {
+            report.problems.push(format!("{}: {}", path.display(), e));
+        }
+    }
+
+    let index = match read_index(effects, dir) {
+        Ok(index) => index.unwrap_or_default(),
+        Err(e) => {
+            report.problems.push(e.to_string());
+            return Ok(report);
+        }
+    };
+
+    let mut by_bundle = BTreeMap::<&str, Vec<&IndexEntry>>::new();
+    for entry in &index.entries {
+        report.checked += 1;
+        if let Err(e) = read_member(effects, dir, entry) {
+            report.problems.push(format!("{} ({}): {}", entry.bundle, entry.archived, e));
+        }
+        by_bundle.entry(&entry.bundle).or_default().push(entry);
+    }
+
+    for path in effects.list(dir)? {
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) if name.ends_with(BUNDLE_EXTENSION) => name.to_string(),
+            _ => continue,
+        };
+        let length = effects.size(&path).ok_or_else(|| format!("can't read {}", path.display()))?;
+        let mut entries = by_bundle.remove(name.as_str()).unwrap_or_default();
+        entries.sort_by_key(|entry| entry.offset);
+
+        let mut covered = 0;
+        for entry in entries {
+            if entry.offset < covered {
+                report.problems.push(format!("{}: entries overlap at offset {}", name, entry.offset));
+            } else if entry.offset > covered {
+                report.notes.push(format!("{}: {} unindexed bytes at offset {}", name, entry.offset - covered, covered));
+            }
+            covered = covered.max(entry.offset + entry.length);
+        }
+        if covered < length {
+            report.notes.push(format!(
+                "{}: {} unindexed bytes at the end, likely an interrupted append",
+                name,
+                length - covered
+            ));
+        }
+    }
+    for bundle in by_bundle.keys() {
+        report.problems.push(format!("{} is indexed but missing", bundle));
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use std::sync::Arc;
+
+    fn revision(archived: &str, high: i32) -> ArchivedRevision {
+        let day_blocks = vec![format!("Today:\nHighs around {}F. NW winds 50 to 70 mph.", high)];
+        let fetched = FetchedForecast {
+            full: format!("Issued: 5:07 AM Friday, January 12, 2024\n\n{}", day_blocks[0]),
+            day_blocks,
+            source: "Higher Summits Forecast".to_string(),
+            captured: archived.parse().unwrap(),
+            fallback: false,
+        };
+        let forecast = Forecast::parse(&fetched.full, &fetched.day_blocks);
+        ArchivedRevision::new(&fetched, &forecast, fetched.captured)
+    }
+
+    fn effects() -> Effects {
+        Effects::with_storage(false, Arc::new(MemoryStorage::new()))
+    }
+
+    fn highs(revisions: &[ArchivedRevision]) -> Vec<Option<i32>> {
+        revisions.iter().map(|revision| revision.forecast.days[0].high_f).collect()
+    }
+
+    // Two revisions bundled in January and one in February
+    fn bundled(effects: &Effects) -> &'static Path {
+        let dir = Path::new("archive");
+        let revisions = [("2024-01-12T10:00:00Z", 5), ("2024-01-12T16:00:00Z", 8), ("2024-02-01T10:00:00Z", 12)];
+        for (archived, high) in revisions {
+            archive_revision(effects, dir, ArchiveFormat::Bundles, &revision(archived, high)).unwrap();
+        }
+        dir
+    }
+
+    fn index(effects: &Effects, dir: &Path) -> ArchiveIndex {
+        read_index(effects, dir).unwrap().unwrap()
+    }
+
+    #[test]
+    fn bundled_revisions_read_back() {
+        let effects = effects();
+        let dir = bundled(&effects);
+        let index = index(&effects, dir);
+        assert_eq!(
+            index.entries.iter().map(|entry| entry.bundle.as_str()).collect::<Vec<_>>(),
+            ["2024-01.json.gz", "2024-01.json.gz", "2024-02.json.gz"]
+        );
+        assert_eq!(read_member(&effects, dir, &index.entries[1]).unwrap().forecast.days[0].high_f, Some(8));
+
+        assert_eq!(highs(&load_revisions(&effects, dir, None).unwrap()), [Some(5), Some(8), Some(12)]);
+        let since = "2024-01-12T12:00:00Z".parse().unwrap();
+        assert_eq!(highs(&load_revisions(&effects, dir, Some(since)).unwrap()), [Some(8), Some(12)]);
+
+        let report = verify_archive(&effects, dir).unwrap();
+        assert_eq!(report.checked, 3);
+        assert!(report.problems.is_empty() && report.notes.is_empty(), "{:?} {:?}", report.problems, report.notes);
+    }
+
+    #[test]
+    fn a_hash_mismatch_is_a_problem() {
+        let effects = effects();
+        let dir = bundled(&effects);
+        let mut index = index(&effects, dir);
+        index.entries[0].hash = content_hash("something else");
+        effects.write(&dir.join(INDEX_FILE), &serde_json::to_string(&index).unwrap()).unwrap();
+
+        let report = verify_archive(&effects, dir).unwrap();
+        assert_eq!(report.problems.len(), 1, "{:?}", report.problems);
+        assert!(report.problems[0].contains("content hash mismatch at offset 0"), "{}", report.problems[0]);
+        // The rest still load
+        assert_eq!(highs(&load_revisions(&effects, dir, None).unwrap()), [Some(8), Some(12)]);
+    }
+
+    #[test]
+    fn an_interrupted_append_is_only_a_note() {
+        let effects = effects();
+        let dir = bundled(&effects);
+        // Appended, but raven stopped before the index was written
+        effects.append(&dir.join("2024-02.json.gz"), b"partial member").unwrap();
+
+        let report = verify_archive(&effects, dir).unwrap();
+        assert!(report.problems.is_empty(), "{:?}", report.problems);
+        assert_eq!(report.notes.len(), 1, "{:?}", report.notes);
+        assert!(report.notes[0].contains("14 unindexed bytes at the end, likely an interrupted append"));
+
+        // A later append follows the leftovers and reads back
+        archive_revision(&effects, dir, ArchiveFormat::Bundles, &revision("2024-02-02T10:00:00Z", 15)).unwrap();
+        assert_eq!(highs(&load_revisions(&effects, dir, None).unwrap()), [Some(5), Some(8), Some(12), Some(15)]);
+    }
+
+    #[test]
+    fn a_missing_bundle_is_a_problem() {
+        let effects = effects();
+        let dir = bundled(&effects);
+        let mut index = index(&effects, dir);
+        index.entries[2].bundle = "2024-03.json.gz".to_string();
+        effects.write(&dir.join(INDEX_FILE), &serde_json::to_string(&index).unwrap()).unwrap();
+
+        let report = verify_archive(&effects, dir).unwrap();
+        assert!(report.problems.iter().any(|problem| problem == "2024-03.json.gz is indexed but missing"));
+    }
+
+    #[test]
+    fn revisions_in_the_same_second_get_their_own_files() {
+        let effects = effects();
+        let dir = Path::new("archive");
+        let first = archive_revision(&effects, dir, ArchiveFormat::Files, &revision("2024-01-12T10:00:00.250Z", 5));
+        let second = archive_revision(&effects, dir, ArchiveFormat::Files, &revision("2024-01-12T10:00:00.750Z", 8));
+        assert_eq!(first.unwrap(), dir.join("20240112T100000250Z.json"));
+        assert_eq!(second.unwrap(), dir.join("20240112T100000750Z.json"));
+
+        // Never over an earlier one
+        let again = revision("2024-01-12T10:00:00.750Z", 12);
+        assert!(archive_revision(&effects, dir, ArchiveFormat::Files, &again).is_err());
+        assert_eq!(highs(&load_revisions(&effects, dir, None).unwrap()), [Some(5), Some(8)]);
+    }
+}
@@ -0,0 +1,256 @@
+use std::error::Error;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{NaiveDate, NaiveDateTime, TimeZone, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use ureq::{Agent, AgentBuilder};
+
+use crate::abbreviation::clean_block;
+use crate::archive::{archive_revision, content_hash, ArchivedRevision};
+use crate::config::{Config, InterceptConfig, Source};
+use crate::effects::Effects;
+use crate::error::RavenError;
+use crate::fetch::FetchedForecast;
+use crate::forecast::Forecast;
+use crate::intercept::extract_forecast;
+use crate::validation::validate_forecast;
+
+pub const PROGRESS_PATH: &str = "raven_backfill.json";
+// What backfilled revisions are archived as coming from
+pub const WAYBACK_SOURCE: &str = "wayback";
+
+const CDX_URL: &str = "https://web.archive.org/cdx/search/cdx";
+const WAYBACK_TIMESTAMP: &str = "%Y%m%d%H%M%S";
+// Backed off for when a 429 doesn't say how long
+const RATE_LIMIT_SECONDS: u64 = 60;
+const RATE_LIMIT_ATTEMPTS: u32 = 3;
+
+pub struct BackfillOptions {
+    pub from: NaiveDate,
+    // Inclusive
+    pub to: NaiveDate,
+    // Between requests to the Wayback Machine
+    pub delay: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct BackfillReport {
+    pub snapshots: usize,
+    pub archived: usize,
+    // The same forecast as the snapshot before
+    pub duplicates: usize,
+    // No outlook in them, or it didn't validate
+    pub rejected: usize,
+    // Done by an earlier, interrupted run
+    pub resumed: usize,
+}
+
+// How far a backfill got, so an interrupted one picks up after the last snapshot it handled
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Progress {
+    url: String,
+    from: Option<NaiveDate>,
+    // The last snapshot's timestamp, they're handled oldest first
+    through: Option<String>,
+    // Of the last snapshot archived, for collapsing duplicates across runs
+    last_hash: Option<String>,
+}
+
+struct Snapshot {
+    timestamp: String,
+    original: String,
+}
+
+// Archives the Wayback Machine's snapshots of the higher summits forecast between the dates, each
+// as a revision archived at its capture time. Plain HTTP, parsed like an intercepted response
+pub fn backfill(
+    config: &Config,
+    effects: &Effects,
+    options: &BackfillOptions,
+) -> Result<BackfillReport, Box<dyn Error>> {
+    if options.to < options.from {
+        let message = format!("--to {} is before --from {}", options.to, options.from);
+        return Err(RavenError::InvalidArgument(message).into());
+    }
+    let source = &config.source;
+    let agent = AgentBuilder::new()
+        .user_agent(&config.user_agent)
+        .timeout(Duration::from_secs(30))
+        .build();
+
+    let progress_path = Path::new(PROGRESS_PATH);
+    let mut progress = effects
+        .read(progress_path)
+        .and_then(|json| serde_json::from_str::<Progress>(&json).ok())
+        .filter(|progress| progress.url == source.url && progress.from == Some(options.from))
+        .unwrap_or_else(|| Progress {
+            url: source.url.clone(),
+            from: Some(options.from),
+            ..Progress::default()
+        });
+
+    let snapshots = list_snapshots(&agent, effects, source, options)?;
+    let mut report = BackfillReport {
+        snapshots: snapshots.len(),
+        ..BackfillReport::default()
+    };
+    info!(
+        "{} snapshots of {} between {} and {}",
+        snapshots.len(),
+        source.url,
+        options.from,
+        options.to
+    );
+
+    for snapshot in snapshots {
+        if progress.through.as_ref().is_some_and(|through| snapshot.timestamp <= *through) {
+            report.resumed += 1;
+            continue;
+        }
+        effects.clock().sleep(options.delay);
+
+        // The network failing stops the run, anything wrong with the snapshot itself skips it
+        let page = fetch_snapshot(&agent, effects, &snapshot)?;
+        match parse_snapshot(config, &snapshot, &page) {
+            Ok(fetched) => {
+                let hash = content_hash(&format!("{}\n{}", fetched.full_text(), fetched.abbreviated()));
+                if progress.last_hash.as_deref() == Some(&hash) {
+                    report.duplicates += 1;
+                } else {
+                    let forecast = Forecast::parse(&fetched.full, &fetched.day_blocks);
+                    let revision = ArchivedRevision::new(&fetched, &forecast, fetched.captured);
+                    archive_revision(effects, &config.archive.dir, config.archive.format, &revision)?;
+                    info!("Archived the forecast captured {}", fetched.captured.format("%Y-%m-%d %H:%M UTC"));
+                    report.archived += 1;
+                    progress.last_hash = Some(hash);
+                }
+            }
+            Err(e) => {
+                warn!("Skipping the snapshot from {}: {}", snapshot.timestamp, e);
+                report.rejected += 1;
+            }
+        }
+
+        progress.through = Some(snapshot.timestamp);
+        effects.write_atomic(progress_path, &serde_json::to_string_pretty(&progress)?)?;
+    }
+
+    Ok(report)
+}
+
+pub fn render_report(report: &BackfillReport) -> String {
+    format!(
+        "{} snapshots: {} archived, {} duplicates, {} rejected, {} done by an earlier run\n",
+        report.snapshots, report.archived, report.duplicates, report.rejected, report.resumed
+    )
+}
+
+// Oldest first, only successful captures, and runs of byte-identical captures collapsed by the CDX
+// server itself
+fn list_snapshots(
+    agent: &Agent,
+    effects: &Effects,
+    source: &Source,
+    options: &BackfillOptions,
+) -> Result<Vec<Snapshot>, Box<dyn Error>> {
+    let request = agent
+        .get(CDX_URL)
+        .query("url", &source.url)
+        .query("from", &options.from.format("%Y%m%d").to_string())
+        .query("to", &options.to.format("%Y%m%d").to_string())
+        .query("output", "json")
+        .query("fl", "timestamp,original")
+        .query("filter", "statuscode:200")
+        .query("collapse", "digest");
+    let body = get(request, effects)?;
+
+    // An empty range is an empty body rather than just the header row
+    if body.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let rows = serde_json::from_str::<Vec<Vec<String>>>(&body)
+        .map_err(|e| format!("unexpected response from the Wayback Machine: {}", e))?;
+    Ok(rows
+        .into_iter()
+        .skip(1)
+        .filter_map(|row| match row.as_slice() {
+            [timestamp, original] => Some(Snapshot {
+                timestamp: timestamp.clone(),
+                original: original.clone(),
+            }),
+            _ => None,
+        })
+        .collect())
+}
+
+// The page as it was captured, without the Wayback Machine's toolbar and rewritten links
+fn fetch_snapshot(agent: &Agent, effects: &Effects, snapshot: &Snapshot) -> Result<String, Box<dyn Error>> {
+    let url = format!("https://web.archive.org/web/{}id_/{}", snapshot.timestamp, snapshot.original);
+    get(agent.get(&url), effects)
+}
+
+fn parse_snapshot(config: &Config, snapshot: &Snapshot, page: &str) -> Result<FetchedForecast, Box<dyn Error>> {
+    let captured = NaiveDateTime::parse_from_str(&snapshot.timestamp, WAYBACK_TIMESTAMP)
+        .map(|naive| Utc.from_utc_datetime(&naive))
+        .map_err(|_| RavenError::UnknownCaptureTime {
+            source: format!("the snapshot from {}", snapshot.timestamp),
+        })?;
+    let source = &config.source;
+    let (full, day_blocks) = extract_forecast(page, &snapshot_intercept(source)).ok_or("no outlook in the page")?;
+    let fetched = FetchedForecast {
+        full,
+        day_blocks: day_blocks
+            .iter()
+            .filter_map(|block| clean_block(block, None, &source.abbreviation.strip, &[]))
+            .collect(),
+        source: WAYBACK_SOURCE.to_string(),
+        captured,
+        fallback: false,
+    };
+    // Validated as of its capture, every snapshot would be too old otherwise
+    validate_forecast(&fetched, &config.validation, captured)?;
+    Ok(fetched)
+}
+
+// Where the outlook is in the raw page, the intercept container when one's configured or else the
+// outlook selector's id
+fn snapshot_intercept(source: &Source) -> InterceptConfig {
+    let container = source
+        .intercept
+        .as_ref()
+        .and_then(|intercept| intercept.container.clone())
+        .or_else(|| {
+            let id = source.selectors.outlook.split('#').nth(1)?;
+            let id = id.split(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_')).next()?;
+            Some(format!("id=\"{}\"", id))
+        });
+    InterceptConfig {
+        url_contains: Vec::new(),
+        body_contains: Vec::new(),
+        json_pointer: None,
+        container,
+        timeout_seconds: 0,
+    }
+}
+
+// Waits out a 429 a few times, Retry-After when it's given, before giving up
+fn get(request: ureq::Request, effects: &Effects) -> Result<String, Box<dyn Error>> {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match request.clone().call() {
+            Ok(response) => return Ok(response.into_string()?),
+            Err(ureq::Error::Status(429, response)) if attempts < RATE_LIMIT_ATTEMPTS => {
+                let seconds = response
+                    .header("Retry-After")
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or(RATE_LIMIT_SECONDS * attempts as u64);
+                warn!("Rate limited by the Wayback Machine, waiting {}s", seconds);
+                effects.clock().sleep(Duration::from_secs(seconds));
+            }
+            Err(e) => return Err(Box::new(e)),
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use headless_chrome::protocol::cdp::Page::CaptureScreenshotFormatOption;
+use headless_chrome::Tab;
+use log::warn;
+
+use crate::config::{DebugConfig, Source};
+use crate::effects::Effects;
+use crate::fetch::FetchedForecast;
+use crate::forecast::Forecast;
+
+const CAPTURE_PREFIX: &str = "capture-";
+
+// Saves everything needed to reproduce a rejected fetch into a timestamped directory:
+// the outlook element's HTML, the whole page, a screenshot, the parsed output and the reason.
+// Each piece is best effort so one failing (e.g. the screenshot) doesn't lose the rest.
+pub fn capture_debug(
+    tab: &Arc<Tab>,
+    config: &DebugConfig,
+    effects: &Effects,
+    capture_dir: &Path,
+    source: &Source,
+    fetched: Option<&FetchedForecast>,
+    reason: &dyn Error,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let dir = capture_dir.join(format!("{}{}", CAPTURE_PREFIX, effects.now().format("%Y%m%dT%H%M%S")));
+    effects.create_dir_all(&dir)?;
+
+    effects.write(
+        &dir.join("reason.txt"),
+        &format!("source: {}\nurl: {}\nreason: {}\n", source.name, source.url, reason),
+    )?;
+
+    match tab.find_element(&source.selectors.outlook).and_then(|e| e.get_content()) {
+        Ok(html) => effects.write(&dir.join("outlook.html"), &html)?,
+        Err(e) => warn!("Debug capture could not read {}: {}", source.selectors.outlook, e),
+    }
+    match tab.get_content() {
+        Ok(html) => effects.write(&dir.join("page.html"), &html)?,
+        Err(e) => warn!("Debug capture could not read the page: {}", e),
+    }
+    match tab.capture_screenshot(CaptureScreenshotFormatOption::Png, None, None, true) {
+        Ok(png) => effects.write_bytes(&dir.join("screenshot.png"), &png)?,
+        Err(e) => warn!("Debug capture could not take a screenshot: {}", e),
+    }
+
+    if let Some(fetched) = fetched {
+        effects.write(&dir.join("full.txt"), &fetched.full)?;
+        effects.write(&dir.join("day_blocks.json"), &serde_json::to_string_pretty(&fetched.day_blocks)?)?;
+        let forecast = Forecast::parse(&fetched.full, &fetched.day_blocks);
+        effects.write(&dir.join("forecast.json"), &serde_json::to_string_pretty(&forecast)?)?;
+    }
+
+    prune_captures(effects, capture_dir, config.max_captures)?;
+    Ok(dir)
+}
+
+// Keeps only the newest `max_captures`, the timestamped names sort oldest first
+fn prune_captures(effects: &Effects, capture_dir: &Path, max_captures: usize) -> Result<(), Box<dyn Error>> {
+    let mut captures = effects
+        .list(capture_dir)?
+        .into_iter()
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(CAPTURE_PREFIX))
+        })
+        .collect::<Vec<_>>();
+    captures.sort();
+
+    let excess = captures.len().saturating_sub(max_captures);
+    for path in captures.into_iter().take(excess) {
+        effects.remove_dir_all(&path)?;
+    }
+    Ok(())
+}
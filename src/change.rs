@@ -0,0 +1,195 @@
+use serde::Deserialize;
+
+use crate::forecast::Forecast;
+use crate::trend::{numeric_changes, NumericChange};
+
+// How two revisions of the same text are told apart
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparator {
+    // Any difference at all
+    #[default]
+    Exact,
+    // Ignoring whitespace, so paragraph joins and reflowed text don't count
+    Normalized,
+    // Only the parsed numbers per day (highs, lows, wind), prose edits don't count. Falls back to
+    // Normalized when the previous revision was never parsed
+    Semantic,
+}
+
+// One side of a comparison, the text as persisted and its parse when there is one
+#[derive(Clone, Copy)]
+pub struct Revision<'a> {
+    pub text: &'a str,
+    pub forecast: Option<&'a Forecast>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ChangeResult {
+    // Nothing to compare against
+    FirstRun,
+    Unchanged,
+    Changed {
+        // Every changed number, biggest first, empty when the previous revision was never parsed
+        diff: Vec<NumericChange>,
+    },
+    // Different from the previous revision but the same as one shortly before it, the page flapping
+    // between two versions
+    FlapSuppressed,
+}
+
+impl ChangeResult {
+    // Whether the candidate should be written out, a flap still is so the files mirror the page
+    pub fn persist(&self) -> bool {
+        !matches!(self, ChangeResult::Unchanged)
+    }
+}
+
+// Decides whether a candidate revision is a change worth acting on. Pure, the caller reads the
+// previous revision and keeps the history of fingerprints
+pub struct ChangeDetector {
+    comparator: Comparator,
+}
+
+impl ChangeDetector {
+    pub fn new(comparator: Comparator) -> ChangeDetector {
+        ChangeDetector { comparator }
+    }
+
+    // `history` holds the fingerprints of the revisions before `previous`, a candidate matching one
+    // of them is a flap
+    pub fn detect(&self, previous: Option<Revision>, candidate: Revision, history: &[u64]) -> ChangeResult {
+        let previous = match previous {
+            Some(previous) => previous,
+            None => return ChangeResult::FirstRun,
+        };
+
+        let fingerprint = self.fingerprint(candidate, previous.forecast.is_some());
+        if fingerprint == self.fingerprint(previous, candidate.forecast.is_some()) {
+            return ChangeResult::Unchanged;
+        }
+        if history.contains(&fingerprint) {
+            return ChangeResult::FlapSuppressed;
+        }
+
+        let diff = match (previous.forecast, candidate.forecast) {
+            (Some(previous), Some(candidate)) => numeric_changes(previous, candidate),
+            _ => Vec::new(),
+        };
+        ChangeResult::Changed { diff }
+    }
+
+    // What `detect` compares. Semantic needs both sides parsed, `other_parsed` says whether the
+    // other side is, so both sides always fall back together
+    pub fn fingerprint(&self, revision: Revision, other_parsed: bool) -> u64 {
+        match (self.comparator, revision.forecast) {
+            (Comparator::Exact, _) => stable_hash(revision.text),
+            (Comparator::Semantic, Some(forecast)) if other_parsed => stable_hash(&semantic_text(forecast)),
+            (Comparator::Normalized, _) | (Comparator::Semantic, _) => {
+                stable_hash(&revision.text.split_whitespace().collect::<Vec<_>>().join(" "))
+            }
+        }
+    }
+}
+
+// The numbers Semantic compares, one line per day
+fn semantic_text(forecast: &Forecast) -> String {
+    forecast
+        .days
+        .iter()
+        .map(|day| format!("{}|{:?}|{:?}|{:?}", day.label, day.high_f, day.low_f, day.wind))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// 64-bit FNV-1a, unlike DefaultHasher it's stable across Rust versions so it can be persisted
+pub fn stable_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Revision<'_> {
+        Revision { text, forecast: None }
+    }
+
+    #[test]
+    fn a_paragraph_join_is_not_a_change() {
+        // Persisted before paragraphs were joined with blank lines
+        let single = "Issued: 5:07 AM Saturday, February 10, 2024 Arctic air settles over the summits.";
+        let joined = "Issued: 5:07 AM Saturday, February 10, 2024\n\nArctic air settles over the summits.";
+        let detector = ChangeDetector::new(Comparator::Normalized);
+        assert!(matches!(detector.detect(Some(text(single)), text(joined), &[]), ChangeResult::Unchanged));
+    }
+
+    fn parsed(block: &str) -> Forecast {
+        Forecast::parse("", &[block.to_string()])
+    }
+
+    fn revision<'a>(text: &'a str, forecast: &'a Forecast) -> Revision<'a> {
+        Revision {
+            text,
+            forecast: Some(forecast),
+        }
+    }
+
+    const TODAY: &str = "Today:\nIn the clouds. Highs around 5F. NW winds 50 to 70 mph.";
+    const REWORDED: &str = "Today:\nSummits in the clouds. Highs around 5F. NW winds 50 to 70 mph.";
+    const WARMER: &str = "Today:\nIn the clouds. Highs around 12F. NW winds 50 to 70 mph.";
+
+    #[test]
+    fn nothing_to_compare_against_is_a_first_run() {
+        for comparator in [Comparator::Exact, Comparator::Normalized, Comparator::Semantic] {
+            let detector = ChangeDetector::new(comparator);
+            assert!(matches!(detector.detect(None, text(TODAY), &[]), ChangeResult::FirstRun));
+        }
+        assert!(ChangeResult::FirstRun.persist());
+    }
+
+    #[test]
+    fn exact_counts_any_difference() {
+        let detector = ChangeDetector::new(Comparator::Exact);
+        assert!(matches!(detector.detect(Some(text(TODAY)), text(TODAY), &[]), ChangeResult::Unchanged));
+        let reflowed = TODAY.replace(". ", ".\n");
+        assert!(matches!(detector.detect(Some(text(TODAY)), text(&reflowed), &[]), ChangeResult::Changed { .. }));
+    }
+
+    #[test]
+    fn normalized_ignores_whitespace_only() {
+        let detector = ChangeDetector::new(Comparator::Normalized);
+        let reflowed = TODAY.replace(". ", ".\n  ");
+        assert!(matches!(detector.detect(Some(text(TODAY)), text(&reflowed), &[]), ChangeResult::Unchanged));
+        assert!(matches!(detector.detect(Some(text(TODAY)), text(REWORDED), &[]), ChangeResult::Changed { .. }));
+    }
+
+    #[test]
+    fn semantic_only_counts_the_numbers() {
+        let detector = ChangeDetector::new(Comparator::Semantic);
+        let (today, reworded, warmer) = (parsed(TODAY), parsed(REWORDED), parsed(WARMER));
+        let previous = Some(revision(TODAY, &today));
+        assert!(matches!(detector.detect(previous, revision(REWORDED, &reworded), &[]), ChangeResult::Unchanged));
+        match detector.detect(previous, revision(WARMER, &warmer), &[]) {
+            ChangeResult::Changed { diff } => {
+                assert_eq!(diff.len(), 1, "{:?}", diff);
+                assert_eq!((diff[0].old, diff[0].new), (5, 12));
+            }
+            result => panic!("expected the new high, got {:?}", result),
+        }
+        // Without a parse of the previous revision it compares like Normalized
+        let unparsed = detector.detect(Some(text(TODAY)), revision(REWORDED, &reworded), &[]);
+        assert!(matches!(unparsed, ChangeResult::Changed { .. }));
+    }
+
+    #[test]
+    fn going_back_to_a_recent_revision_is_a_flap() {
+        let detector = ChangeDetector::new(Comparator::Exact);
+        let history = [detector.fingerprint(text(TODAY), false)];
+        let result = detector.detect(Some(text(WARMER)), text(TODAY), &history);
+        assert!(matches!(result, ChangeResult::FlapSuppressed));
+        assert!(result.persist());
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, Utc};
+
+// Where the loop, schedule, caps and backoff get the time from and how they wait, so a whole day
+// can run against a clock that only moves when told to
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    // Waits `duration` unless something arrives on `wake` first, true if it did
+    fn wait(&self, duration: Duration, wake: &Receiver<()>) -> bool;
+
+    fn local_now(&self) -> DateTime<Local> {
+        self.now().with_timezone(&Local)
+    }
+
+    fn sleep(&self, duration: Duration) {
+        // Held until the wait is over, a dropped sender would end it at once
+        let (_sender, wake) = mpsc::channel();
+        self.wait(duration, &wake);
+    }
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn wait(&self, duration: Duration, wake: &Receiver<()>) -> bool {
+        match wake.recv_timeout(duration) {
+            Ok(()) => true,
+            Err(RecvTimeoutError::Timeout) => false,
+            // Nobody can wake it any more, keep the cadence
+            Err(RecvTimeoutError::Disconnected) => {
+                thread::sleep(duration);
+                false
+            }
+        }
+    }
+}
+
+// Only moves when advanced, waiting moves it on by the whole wait at once
+pub struct ManualClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl ManualClock {
+    pub fn new(start: DateTime<Utc>) -> ManualClock {
+        ManualClock { now: Mutex::new(start) }
+    }
+
+    pub fn advance(&self, by: chrono::Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+
+    fn wait(&self, duration: Duration, wake: &Receiver<()>) -> bool {
+        if wake.try_recv().is_ok() {
+            return true;
+        }
+        self.advance(chrono::Duration::from_std(duration).unwrap_or_else(|_| chrono::Duration::zero()));
+        false
+    }
+}
@@ -0,0 +1,254 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Local, Utc};
+use headless_chrome::Tab;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::config::ConditionsConfig;
+use crate::cycle::STATE_PATH;
+use crate::effects::Effects;
+use crate::locale::Language;
+use crate::ocr::element_text;
+use crate::state::State;
+
+// Every sample ever taken, one JSON object per line. The state file only keeps what the trend line needs
+pub const OBSERVATION_LOG_PATH: &str = "observations.jsonl";
+
+// One reading of the observed summit conditions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Observation {
+    pub observed: DateTime<Utc>,
+    pub temperature_f: Option<i32>,
+    pub wind_mph: Option<u32>,
+    // Which of the values were read by OCR rather than from the page's text
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ocr: Vec<String>,
+}
+
+// Samples the conditions page when the last sample is older than the sampling interval, so the
+// cadence is independent of how often the forecast is polled. Failures are logged, never fatal
+pub fn sample_if_due(tab: &Arc<Tab>, config: &ConditionsConfig, effects: &Effects) {
+    let now = effects.now();
+    let mut state = State::load(effects, Path::new(STATE_PATH));
+    let interval = Duration::seconds(config.sample_interval_seconds as i64);
+    let due = state
+        .observations
+        .last()
+        .is_none_or(|last| now.signed_duration_since(last.observed) >= interval);
+    if !due {
+        return;
+    }
+
+    match fetch_observation(tab, config, now) {
+        Ok(observation) => {
+            let ocr = if observation.ocr.is_empty() {
+                String::new()
+            } else {
+                format!(" (OCR: {})", observation.ocr.join(", "))
+            };
+            info!(
+                "Summit conditions: {}, wind {}{}",
+                temperature(observation.temperature_f, Language::En),
+                speed(observation.wind_mph, Language::En),
+                ocr
+            );
+            match serde_json::to_string(&observation) {
+                Ok(line) => {
+                    if let Err(e) = effects.append(Path::new(OBSERVATION_LOG_PATH), format!("{}\n", line).as_bytes()) {
+                        warn!("Could not log summit conditions: {}", e);
+                    }
+                }
+                Err(e) => warn!("Could not log summit conditions: {}", e),
+            }
+            state.observations.push(observation);
+        }
+        Err(e) => {
+            warn!("Could not sample summit conditions: {}", e);
+            return;
+        }
+    }
+
+    // Only as much history as the trend line can look back on
+    let cutoff = now - Duration::hours(config.history_hours as i64);
+    state.observations.retain(|observation| observation.observed >= cutoff);
+    if let Err(e) = state.save(effects, Path::new(STATE_PATH)) {
+        warn!("Could not save summit conditions: {}", e);
+    }
+}
+
+// The logged samples with any the state file has that the log doesn't, e.g. from before it existed,
+// oldest first. Unreadable lines are skipped
+pub fn load_observations(effects: &Effects) -> Vec<Observation> {
+    let mut observations = effects
+        .read(Path::new(OBSERVATION_LOG_PATH))
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str::<Observation>(line) {
+            Ok(observation) => Some(observation),
+            Err(e) => {
+                warn!("Skipping an unreadable line in {}: {}", OBSERVATION_LOG_PATH, e);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    let state = State::load(effects, Path::new(STATE_PATH));
+    for observation in state.observations {
+        if !observations.iter().any(|logged| logged.observed == observation.observed) {
+            observations.push(observation);
+        }
+    }
+    observations.sort_by_key(|observation| observation.observed);
+    observations
+}
+
+fn fetch_observation(
+    tab: &Arc<Tab>,
+    config: &ConditionsConfig,
+    observed: DateTime<Utc>,
+) -> Result<Observation, Box<dyn Error>> {
+    tab.navigate_to(&config.url)?;
+    let (temperature_f, temperature_ocr) = read_number(tab, &config.temperature_selector, config.ocr_fallback)?;
+    let (wind, wind_ocr) = read_number(tab, &config.wind_selector, config.ocr_fallback)?;
+
+    let ocr = [("temperature_f", temperature_ocr), ("wind_mph", wind_ocr)]
+        .iter()
+        .filter(|(_, ocr)| *ocr)
+        .map(|(field, _)| field.to_string())
+        .collect();
+    Ok(Observation {
+        observed,
+        temperature_f,
+        wind_mph: wind.and_then(|mph| u32::try_from(mph).ok()),
+        ocr,
+    })
+}
+
+// The element's number and whether OCR read it. OCR only runs when the text has no number, so it
+// never overrides what the page says
+fn read_number(tab: &Arc<Tab>, selector: &str, ocr_fallback: bool) -> Result<(Option<i32>, bool), Box<dyn Error>> {
+    let element = tab.wait_for_element(selector)?;
+    let text = element.get_inner_text()?;
+    if let Some(number) = first_number(&text) {
+        return Ok((Some(number), false));
+    }
+    if !ocr_fallback {
+        return Ok((None, false));
+    }
+
+    match element_text(&element) {
+        Ok(text) => match first_number(&text) {
+            Some(number) => {
+                warn!("No number in the text of {}, OCR read {}", selector, number);
+                Ok((Some(number), true))
+            }
+            None => {
+                warn!("No number in the text of {}, nor by OCR ({:?})", selector, text);
+                Ok((None, false))
+            }
+        },
+        Err(e) => {
+            warn!("No number in the text of {} and OCR failed: {}", selector, e);
+            Ok((None, false))
+        }
+    }
+}
+
+// "-3°F" or "W 45 mph"
+fn first_number(text: &str) -> Option<i32> {
+    text.split(|c: char| !(c.is_ascii_digit() || c == '-'))
+        .filter(|word| !word.is_empty() && *word != "-")
+        .find_map(|word| word.parse().ok())
+}
+
+// "Summit now 3F, was 11F at 7a; wind up 20 mph". Anything without a sample close enough in time
+// is "n/a", e.g. when raven wasn't running
+pub fn trend_line(observations: &[Observation], now: DateTime<Utc>, config: &ConditionsConfig, lang: Language) -> String {
+    let strings = lang.strings();
+    let tolerance = Duration::seconds(config.sample_interval_seconds as i64 * 2);
+    let then = now - Duration::hours(config.trend_hours as i64);
+
+    let latest = closest(observations, now, tolerance);
+    let earlier = closest(observations, then, tolerance);
+
+    let was = match earlier {
+        Some(earlier) => format!(
+            "{} {} {} {}",
+            strings.was,
+            temperature(earlier.temperature_f, lang),
+            strings.at,
+            short_time(earlier.observed)
+        ),
+        None => format!("{} {} {}h", strings.was, strings.not_available, config.trend_hours),
+    };
+
+    let wind = match (
+        latest.and_then(|o| o.wind_mph),
+        earlier.and_then(|o| o.wind_mph),
+    ) {
+        (Some(now), Some(then)) if now > then => format!("{} {} {} mph", strings.wind, strings.up, now - then),
+        (Some(now), Some(then)) if now < then => format!("{} {} {} mph", strings.wind, strings.down, then - now),
+        (Some(now), Some(_)) => format!("{} {} {} mph", strings.wind, strings.steady, now),
+        _ => format!("{} {}", strings.wind, strings.not_available),
+    };
+
+    format!(
+        "{} {}, {}; {}",
+        strings.summit_now,
+        temperature(latest.and_then(|o| o.temperature_f), lang),
+        was,
+        wind
+    )
+}
+
+fn closest(observations: &[Observation], at: DateTime<Utc>, tolerance: Duration) -> Option<&Observation> {
+    observations
+        .iter()
+        .map(|observation| (observation, (observation.observed - at).num_seconds().abs()))
+        .filter(|(_, distance)| *distance <= tolerance.num_seconds())
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(observation, _)| observation)
+}
+
+fn temperature(value: Option<i32>, lang: Language) -> String {
+    value
+        .map(|t| format!("{}F", t))
+        .unwrap_or_else(|| lang.strings().not_available.to_string())
+}
+
+fn speed(value: Option<u32>, lang: Language) -> String {
+    value
+        .map(|mph| format!("{} mph", mph))
+        .unwrap_or_else(|| lang.strings().not_available.to_string())
+}
+
+// "7a", "12p"
+fn short_time(time: DateTime<Utc>) -> String {
+    let local = time.with_timezone(&Local);
+    format!("{}{}", local.format("%-I"), &local.format("%P").to_string()[..1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_number_reads_signed_values() {
+        assert_eq!(first_number("-3°F"), Some(-3));
+        assert_eq!(first_number("W 45 mph"), Some(45));
+        assert_eq!(first_number("- calm -"), None);
+        assert_eq!(first_number(""), None);
+    }
+
+    // Needs tesseract and its English data installed
+    #[cfg(feature = "ocr")]
+    #[test]
+    fn ocr_reads_the_fixture_image() {
+        let text = crate::ocr::image_text(include_bytes!("../fixtures/ocr_minus_12.png")).unwrap();
+        assert_eq!(first_number(&text), Some(-12), "OCR read {:?}", text);
+    }
+}
@@ -0,0 +1,977 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::{env, fs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::abbreviation::UI_LABELS;
+use crate::archive::ArchiveFormat;
+use crate::change::Comparator;
+use crate::error::RavenError;
+use crate::format::validate_templates;
+use crate::inreach::Transport;
+use crate::locale::Language;
+use crate::retry::{retryable, Operation, RetryPolicy};
+use crate::routing::{parse_window, validate_routing, EventKind, RoutingMode, Severity};
+use crate::schedule::validate_schedule;
+
+const HIGHER_SUMMITS_FORECAST_URL: &str =
+    "https://www.mountwashington.org/experience-the-weather/higher-summit-forecast.aspx";
+
+const DEFAULT_USER_AGENT: &str = concat!(
+    "raven-forecast-bot/",
+    env!("CARGO_PKG_VERSION"),
+    " (+https://github.com/thsowers/raven)"
+);
+
+// Polling faster than this is unkind to mountwashington.org and needs an explicit override
+pub const MIN_INTERVAL_SECONDS: u64 = 30;
+
+// Everything is optional, a missing config file means the original higher summits behavior
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Identifies raven to the site operators, sent by the browser and any plain HTTP requests
+    pub user_agent: String,
+    // Chrome or Edge to run, found automatically when unset
+    pub browser_path: Option<PathBuf>,
+    // Language of the compact renderings (en, fr), the forecast prose itself is never translated
+    pub lang: String,
+    pub polling: PollingConfig,
+    pub source: Source,
+    pub fallback: FallbackConfig,
+    pub validation: ValidationConfig,
+    pub inreach: InReachConfig,
+    pub sms: SmsConfig,
+    pub html: HtmlConfig,
+    pub output: OutputConfig,
+    pub debug: DebugConfig,
+    pub archive: ArchiveConfig,
+    pub change: ChangeConfig,
+    pub notify: NotifyConfig,
+    pub failures: FailuresConfig,
+    pub drift: DriftConfig,
+    pub delivery: DeliveryConfig,
+    pub conditions: Option<ConditionsConfig>,
+    pub schedule: Option<ScheduleConfig>,
+    pub twilio: Option<TwilioConfig>,
+    // Other MWOBS products fetched alongside the higher summits forecast each cycle
+    pub products: Vec<ProductConfig>,
+    pub routing: Option<RoutingConfig>,
+    pub retry: RetryConfig,
+    pub nws: Option<NwsConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            browser_path: None,
+            lang: "en".to_string(),
+            polling: PollingConfig::default(),
+            source: Source::default(),
+            fallback: FallbackConfig::default(),
+            validation: ValidationConfig::default(),
+            inreach: InReachConfig::default(),
+            sms: SmsConfig::default(),
+            html: HtmlConfig::default(),
+            output: OutputConfig::default(),
+            debug: DebugConfig::default(),
+            archive: ArchiveConfig::default(),
+            change: ChangeConfig::default(),
+            notify: NotifyConfig::default(),
+            failures: FailuresConfig::default(),
+            drift: DriftConfig::default(),
+            delivery: DeliveryConfig::default(),
+            conditions: None,
+            schedule: None,
+            twilio: None,
+            products: Vec::new(),
+            routing: None,
+            retry: RetryConfig::default(),
+            nws: None,
+        }
+    }
+}
+
+// Command line settings that override the config file, applied again to a reloaded one
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    pub debug_capture_dir: Option<PathBuf>,
+    pub lang: Option<String>,
+    pub days: Option<usize>,
+    pub note: Option<String>,
+}
+
+impl Overrides {
+    pub fn apply(&self, config: &mut Config) {
+        if self.debug_capture_dir.is_some() {
+            config.debug.capture_dir = self.debug_capture_dir.clone();
+        }
+        if let Some(lang) = &self.lang {
+            config.lang = lang.clone();
+        }
+        if self.days.is_some() {
+            config.source.abbreviation.days = self.days;
+        }
+        if self.note.is_some() {
+            config.inreach.note = self.note.clone();
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        Config::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Config, Box<dyn Error>> {
+        let config = toml::from_str(text)?;
+        Ok(config)
+    }
+
+    // Checks that can't be expressed in the types, run at startup and on every reload
+    pub fn validate(&self, allow_fast_polling: bool) -> Result<(), RavenError> {
+        if self.polling.interval_seconds < MIN_INTERVAL_SECONDS && !allow_fast_polling {
+            return Err(RavenError::InvalidConfig(format!(
+                "polling.interval_seconds is {}, the minimum is {} (override with --i-know-what-im-doing)",
+                self.polling.interval_seconds, MIN_INTERVAL_SECONDS
+            )));
+        }
+        if self.polling.fast_interval_seconds < MIN_INTERVAL_SECONDS && !allow_fast_polling {
+            return Err(RavenError::InvalidConfig(format!(
+                "polling.fast_interval_seconds is {}, the minimum is {} (override with --i-know-what-im-doing)",
+                self.polling.fast_interval_seconds, MIN_INTERVAL_SECONDS
+            )));
+        }
+        if let Some(e) = self.polling.fast_windows.iter().find_map(|window| parse_window(window).err()) {
+            return Err(RavenError::InvalidConfig(format!("polling.fast_windows has an invalid window: {}", e)));
+        }
+        if self.inreach.transport == Transport::Account && self.inreach.account.conversation.is_none() {
+            return Err(RavenError::InvalidConfig(
+                "inreach.transport is account but inreach.account.conversation isn't set".to_string(),
+            ));
+        }
+        // Without a capture time a cached copy would count as captured now, however old it is
+        if let Some(source) = self.fallback.sources.iter().find(|source| source.capture_time.is_none()) {
+            return Err(RavenError::InvalidConfig(format!(
+                "fallback source {} has no capture_time, its forecast could be any age",
+                source.name
+            )));
+        }
+        if self.polling.tabs == 0 {
+            return Err(RavenError::InvalidConfig(
+                "polling.tabs is 0, the primary forecast needs a tab".to_string(),
+            ));
+        }
+        if self.validation.max_bytes < self.validation.min_length {
+            return Err(RavenError::InvalidConfig(format!(
+                "validation.max_bytes is {}, below validation.min_length ({})",
+                self.validation.max_bytes, self.validation.min_length
+            )));
+        }
+        self.lang.parse::<Language>()?;
+
+        // The higher summits forecast owns the unprefixed files
+        let mut prefixes = vec![""];
+        let products = self.products.iter().map(|product| (format!("product {}", product.name), &product.prefix));
+        let nws = self.nws.as_ref().map(|nws| ("the NWS fallback".to_string(), &nws.prefix));
+        for (name, prefix) in products.chain(nws) {
+            if prefixes.contains(&prefix.as_str()) {
+                return Err(RavenError::InvalidConfig(format!(
+                    "{} needs a prefix of its own, {:?} is already used",
+                    name, prefix
+                )));
+            }
+            prefixes.push(prefix);
+        }
+        let mut sources = std::iter::once(&self.source).chain(self.products.iter().map(|product| &product.source));
+        if let Some(source) = sources.find(|source| source.abbreviation.days == Some(0)) {
+            return Err(RavenError::InvalidConfig(format!(
+                "{} has abbreviation.days = 0, which would leave nothing to send",
+                source.name
+            )));
+        }
+        if self.nws.as_ref().is_some_and(|nws| nws.url.trim().is_empty()) {
+            return Err(RavenError::InvalidConfig(
+                "nws.url is empty, set it to the zone forecast to fall back to".to_string(),
+            ));
+        }
+        if self.nws.as_ref().is_some_and(|nws| nws.days == Some(0)) {
+            return Err(RavenError::InvalidConfig(
+                "nws.days is 0, which would leave nothing to send".to_string(),
+            ));
+        }
+        if self.drift.enabled && (self.drift.min_samples == 0 || self.drift.min_samples > self.drift.window) {
+            return Err(RavenError::InvalidConfig(format!(
+                "drift.min_samples is {}, it has to be between 1 and drift.window ({})",
+                self.drift.min_samples, self.drift.window
+            )));
+        }
+        validate_routing(self)?;
+        validate_schedule(self)?;
+        validate_templates(self)?;
+        if self.conditions.as_ref().is_some_and(|conditions| conditions.ocr_fallback) && !cfg!(feature = "ocr") {
+            return Err(RavenError::InvalidConfig(
+                "conditions.ocr_fallback needs raven built with --features ocr".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    // Checked by `validate`, so anything unparseable here is a config that was never validated
+    pub fn language(&self) -> Language {
+        self.lang.parse().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PollingConfig {
+    pub interval_seconds: u64,
+    // Up to this much is randomly added to each interval so a fleet of ravens doesn't synchronize
+    pub jitter_seconds: u64,
+    // Browser tabs fetching at once, the primary forecast's included. Extra products share the rest
+    pub tabs: usize,
+    // Sources not started by then are given up on for the cycle, counted from the browser launch
+    pub cycle_deadline_seconds: u64,
+    // Local times to poll every fast_interval_seconds instead, e.g. ["05:00-07:00", "15:30-17:00"]
+    pub fast_windows: Vec<String>,
+    pub fast_interval_seconds: u64,
+    // "auto" polls fast around the times updates have been seen instead, on days with enough of them
+    pub schedule: PollSchedule,
+    // How far either side of a learned update time polling is fast, on top of how much it varies
+    pub auto_margin_minutes: u64,
+}
+
+impl Default for PollingConfig {
+    fn default() -> Self {
+        PollingConfig {
+            interval_seconds: 60,
+            jitter_seconds: 5,
+            tabs: 2,
+            cycle_deadline_seconds: 240,
+            fast_windows: Vec::new(),
+            fast_interval_seconds: 30,
+            schedule: PollSchedule::Fixed,
+            auto_margin_minutes: 15,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PollSchedule {
+    // Only fast_windows
+    #[default]
+    Fixed,
+    Auto,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Source {
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub selectors: Selectors,
+    #[serde(default)]
+    pub abbreviation: AbbreviationConfig,
+    // How to tell when a cached copy of the page was captured, None means the page is live
+    #[serde(default)]
+    pub capture_time: Option<CaptureTime>,
+    // Experimental, read the forecast from the network response carrying it instead of the rendered page
+    #[serde(default)]
+    pub intercept: Option<InterceptConfig>,
+    // Adds a throwaway query parameter to the url on every fetch, for caches the browser's own
+    // cache disabling doesn't reach. Not for archive urls
+    #[serde(default)]
+    pub cache_bust: bool,
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Source {
+            name: "mountwashington.org".to_string(),
+            url: HIGHER_SUMMITS_FORECAST_URL.to_string(),
+            selectors: Selectors::default(),
+            abbreviation: AbbreviationConfig::default(),
+            capture_time: None,
+            intercept: None,
+            cache_bust: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Selectors {
+    // Waited on once the page loads, which also waits for the javascript to render the forecast
+    pub outlook: String,
+    // Whether to click the outlook once it appears, some pages only expand it on a click
+    pub click_outlook: bool,
+    pub full: String,
+    pub days: String,
+    // Consent banners and popups dismissed (clicked) before touching the outlook, when present
+    pub overlays: Vec<String>,
+}
+
+impl Default for Selectors {
+    fn default() -> Self {
+        Selectors {
+            outlook: "div#SummitOutlook".to_string(),
+            click_outlook: true,
+            full: "#SummitOutlook > p".to_string(),
+            days: "#SummitOutlook > div".to_string(),
+            overlays: vec![
+                "#onetrust-accept-btn-handler".to_string(),
+                ".cc-window .cc-dismiss".to_string(),
+                "button.pum-close".to_string(),
+            ],
+        }
+    }
+}
+
+// How the day blocks making up the abbreviated forecast are picked out and tidied
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AbbreviationConfig {
+    // Selectors for the day blocks, in page order, instead of selectors.days when set
+    pub include: Vec<String>,
+    // Blocks matching any of these are skipped, and the text of matching elements inside a block removed
+    pub exclude: Vec<String>,
+    // Selector for each block's heading inside it, which then leads the block as its "Tonight:" label
+    pub heading: Option<String>,
+    // Lines of a block that are only one of these, ignoring case, are dropped. By default the button
+    // labels seen on the page
+    pub strip: Vec<String>,
+    // Only the first this many days, e.g. 3 for tonight and the next two. The full forecast and the
+    // archive keep them all
+    pub days: Option<usize>,
+}
+
+impl Default for AbbreviationConfig {
+    fn default() -> Self {
+        AbbreviationConfig {
+            include: Vec::new(),
+            exclude: vec!["button".to_string()],
+            heading: None,
+            strip: UI_LABELS.iter().map(|label| label.to_string()).collect(),
+            days: None,
+        }
+    }
+}
+
+// An additional page, e.g. the regional forecast, processed like the higher summits forecast but
+// into its own files and only sent where routed
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProductConfig {
+    pub name: String,
+    pub source: Source,
+    // Prepended to each output file name, "regional_" writes regional_forecast_full.txt and so on
+    pub prefix: String,
+    #[serde(default)]
+    pub inreach: bool,
+    // Every other configured channel, e.g. Twilio
+    #[serde(default)]
+    pub notify: bool,
+}
+
+// Which response carries the outlook and where in it the forecast is. Anything unmatched within the
+// timeout, unparseable or failing validation falls back to scraping the page
+#[derive(Debug, Clone, Deserialize)]
+pub struct InterceptConfig {
+    // Candidate responses have a URL containing any of these
+    pub url_contains: Vec<String>,
+    // and a body containing all of these
+    #[serde(default)]
+    pub body_contains: Vec<String>,
+    // For JSON responses, the string holding the outlook's HTML, e.g. "/d"
+    #[serde(default)]
+    pub json_pointer: Option<String>,
+    // Text in the opening tag of the outlook's container, None takes the whole body as the container
+    #[serde(default = "default_intercept_container")]
+    pub container: Option<String>,
+    #[serde(default = "default_intercept_timeout_seconds")]
+    pub timeout_seconds: u64,
+}
+
+fn default_intercept_container() -> Option<String> {
+    Some("id=\"SummitOutlook\"".to_string())
+}
+
+fn default_intercept_timeout_seconds() -> u64 {
+    10
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "from", rename_all = "snake_case")]
+pub enum CaptureTime {
+    // Wayback Machine URLs embed the capture timestamp once redirected, /web/20240210123456/...
+    WaybackUrl,
+    // Text of an element on the page parsed with a chrono format string, always treated as UTC
+    Element { selector: String, format: String },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FallbackConfig {
+    // Consecutive primary failures before the fallback sources are tried
+    pub after_failures: u32,
+    // Cached copies, each needs a capture_time so the max age check has something to go by
+    pub sources: Vec<Source>,
+}
+
+impl Default for FallbackConfig {
+    fn default() -> Self {
+        FallbackConfig {
+            after_failures: 3,
+            sources: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ValidationConfig {
+    pub min_length: usize,
+    // Anything bigger is rejected like a too short forecast
+    pub max_bytes: usize,
+    // Require a day name (Today, Tonight, Saturday...) in the day blocks, catches markup changes
+    pub require_day_names: bool,
+    // Oldest capture time accepted, protects against sending an ancient cached forecast as current
+    pub max_age_hours: i64,
+    // Page furniture that must not make up most of what's sent to the inReach, matched as whole words ignoring case
+    pub stop_phrases: Vec<String>,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        ValidationConfig {
+            min_length: 200,
+            max_bytes: 64 * 1024,
+            require_day_names: true,
+            max_age_hours: 12,
+            stop_phrases: UI_LABELS
+                .iter()
+                .copied()
+                .chain(["Loading"])
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct InReachConfig {
+    pub transport: Transport,
+    // The reply URL itself is a secret, read from this environment variable
+    pub reply_url_env: String,
+    pub message_selector: String,
+    pub send_selector: String,
+    // Form field carrying the message text, for the http transport
+    pub message_field: String,
+    // Pause between messages on the http transport so Garmin doesn't rate limit us
+    pub http_delay_seconds: u64,
+    // A standing line appended to every forecast sent, e.g. "Bail via Lion Head if winds >80"
+    pub note: Option<String>,
+    // For the account transport
+    pub account: AccountConfig,
+}
+
+impl InReachConfig {
+    pub fn reply_url(&self) -> Option<String> {
+        env::var(&self.reply_url_env).ok()
+    }
+}
+
+impl Default for InReachConfig {
+    fn default() -> Self {
+        InReachConfig {
+            transport: Transport::default(),
+            reply_url_env: "GARMIN_MESSAGE_REPLY_URL".to_string(),
+            message_selector: "#ReplyMessage".to_string(),
+            send_selector: "#sendBtn".to_string(),
+            message_field: "ReplyMessage".to_string(),
+            http_delay_seconds: 5,
+            note: None,
+            account: AccountConfig::default(),
+        }
+    }
+}
+
+// Sending from a Garmin Explore account's inbox. The username and password are read from the
+// environment variables named here, never from the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AccountConfig {
+    pub username_env: String,
+    pub password_env: String,
+    pub messages_url: String,
+    // The inbox entry to send in, matched against its text, e.g. the inReach's name
+    pub conversation: Option<String>,
+    // raven's own Chrome profile, the login session lives here between sends
+    pub profile_dir: PathBuf,
+    pub username_selector: String,
+    pub password_selector: String,
+    pub login_selector: String,
+    // Only on the page when Garmin wants a verification code
+    pub mfa_selector: String,
+    // Each conversation in the inbox, only there once logged in
+    pub conversation_selector: String,
+    pub message_selector: String,
+    pub send_selector: String,
+}
+
+impl Default for AccountConfig {
+    fn default() -> Self {
+        AccountConfig {
+            username_env: "GARMIN_USERNAME".to_string(),
+            password_env: "GARMIN_PASSWORD".to_string(),
+            messages_url: "https://explore.garmin.com/Inbox".to_string(),
+            conversation: None,
+            profile_dir: PathBuf::from("raven_chrome_profile"),
+            username_selector: "#email".to_string(),
+            password_selector: "#password".to_string(),
+            login_selector: "#login-btn-signin".to_string(),
+            mfa_selector: "#verificationCodeInput, input[name=\"verificationCode\"]".to_string(),
+            conversation_selector: ".conversation-list .conversation".to_string(),
+            message_selector: "textarea.message-input".to_string(),
+            send_selector: "button.send-message".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SmsConfig {
+    // Send a summary of what changed as message 1, so the rest can be skipped if nothing matters
+    pub summary_message: bool,
+    // Hold a changed forecast this long before sending it, restarting the wait on every further
+    // revision so a burst of updates goes out once. 0 sends immediately. Trend alerts never wait
+    pub settle_seconds: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HtmlConfig {
+    // Write a static forecast page on every change, e.g. for serving from nginx
+    pub enabled: bool,
+    pub path: PathBuf,
+    pub refresh_seconds: u32,
+}
+
+impl Default for HtmlConfig {
+    fn default() -> Self {
+        HtmlConfig {
+            enabled: false,
+            path: PathBuf::from("forecast.html"),
+            refresh_seconds: 300,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct OutputConfig {
+    // Also write forecast_day_N.txt/.json per published day, e.g. for a kiosk showing today and tomorrow
+    pub day_files: bool,
+    pub day_files_dir: PathBuf,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            day_files: false,
+            day_files_dir: PathBuf::from("."),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DebugConfig {
+    // Where to save a debug capture whenever a fetched forecast is rejected, None disables captures
+    pub capture_dir: Option<PathBuf>,
+    pub max_captures: usize,
+}
+
+impl Default for DebugConfig {
+    fn default() -> Self {
+        DebugConfig {
+            capture_dir: None,
+            max_captures: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ArchiveConfig {
+    // Keep every changed revision, read back by `raven digest`
+    pub enabled: bool,
+    pub dir: PathBuf,
+    // files, or bundles to keep the file count down over a winter
+    pub format: ArchiveFormat,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        ArchiveConfig {
+            enabled: true,
+            dir: PathBuf::from("archive"),
+            format: ArchiveFormat::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ChangeConfig {
+    // How the abbreviated forecast is compared to the last one: exact, normalized or semantic
+    pub comparator: Comparator,
+    // A revision matching one of this many before the last is the page flapping and isn't sent, 0 turns it off
+    pub flap_history: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NotifyConfig {
+    // Channels other than the inReach still running this long into waiting on them are logged and abandoned
+    pub timeout_seconds: u64,
+    // Message layouts keyed like routing channels (inreach, twilio, twilio:<name>), the most specific
+    // wins. {heading}, {alerts}, {body} and {trend} are filled in, e.g. "{alerts} {body} {trend}"
+    pub templates: BTreeMap<String, String>,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        NotifyConfig {
+            timeout_seconds: 60,
+            templates: BTreeMap::new(),
+        }
+    }
+}
+
+// Notifying raven's own failures, sent wherever routing sends error and recovery events
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FailuresConfig {
+    // Cycles that have to fail in a row before it's notified
+    pub after: u32,
+    // While still failing, notify again at most this often, 0 for only once per run of failures
+    pub repeat_minutes: u64,
+    // Notify the first good cycle after a notified failure
+    pub recovery: bool,
+}
+
+impl Default for FailuresConfig {
+    fn default() -> Self {
+        FailuresConfig {
+            after: 1,
+            repeat_minutes: 0,
+            recovery: true,
+        }
+    }
+}
+
+// Early warning of a page redesign while extraction still works: every cycle's page structure is
+// compared with a rolling baseline, drift is logged and sent wherever routing explicitly sends drift
+// events
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DriftConfig {
+    pub enabled: bool,
+    // Cycles the baseline is made of
+    pub window: usize,
+    // Cycles seen before anything is compared
+    pub min_samples: usize,
+    // How far the number of day blocks may be from the usual number
+    pub children_tolerance: usize,
+    // How far the average day block length may move, as a fraction of the usual
+    pub length_tolerance: f64,
+    // Selectors for elements the page should have, e.g. "#SummitOutlook .outlook-controls"
+    pub expect: Vec<String>,
+    // Save a debug capture when the drift starts, with debug.capture_dir set
+    pub capture: bool,
+}
+
+impl Default for DriftConfig {
+    fn default() -> Self {
+        DriftConfig {
+            enabled: true,
+            window: 20,
+            min_samples: 5,
+            children_tolerance: 1,
+            length_tolerance: 0.5,
+            expect: Vec::new(),
+            capture: true,
+        }
+    }
+}
+
+// Whether what went to the inReach shows up in the MapShare conversation, checked after a send by
+// loading the reply url again. Off by default, it costs page loads and needs the url still valid
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DeliveryConfig {
+    pub enabled: bool,
+    // After the send, the first check
+    pub check_after_minutes: u64,
+    // After the first check, the second and last. Missing parts are notified after it
+    pub recheck_after_minutes: u64,
+}
+
+impl Default for DeliveryConfig {
+    fn default() -> Self {
+        DeliveryConfig {
+            enabled: false,
+            check_after_minutes: 10,
+            recheck_after_minutes: 10,
+        }
+    }
+}
+
+// When a new forecast is expected, a deadline passing without a new issue time is notified
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduleConfig {
+    // Times by which an update should have been seen since the deadline before, e.g. ["07:00"]
+    pub deadlines: Vec<String>,
+    // What the deadlines are in, whatever zone raven runs in. The observatory's by default
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    // How often to remind while it's still missing, 0 for only the first notice
+    #[serde(default = "default_reminder_hours")]
+    pub reminder_hours: u64,
+}
+
+fn default_timezone() -> String {
+    "America/New_York".to_string()
+}
+
+fn default_reminder_hours() -> u64 {
+    3
+}
+
+// The NWS zone forecast, sent in place of the higher summits forecast once every source of it has
+// failed. Plain HTTP, no browser
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NwsConfig {
+    // The zone forecast text product, ".TODAY...Sunny." periods ending in "$$". No default, the zone
+    // covering the summits has to be picked
+    pub url: String,
+    // Prepended to its output file names, "nws_" writes nws_forecast_full.txt and so on
+    pub prefix: String,
+    // Periods of only the first this many days go out, the zone forecast runs a week ahead
+    pub days: Option<usize>,
+}
+
+impl Default for NwsConfig {
+    fn default() -> Self {
+        NwsConfig {
+            url: String::new(),
+            prefix: "nws_".to_string(),
+            days: Some(2),
+        }
+    }
+}
+
+// Observed summit conditions, sampled for the "Summit now 3F, was 11F at 7a" trend line
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConditionsConfig {
+    #[serde(default = "default_conditions_url")]
+    pub url: String,
+    pub temperature_selector: String,
+    pub wind_selector: String,
+    #[serde(default = "default_sample_interval_seconds")]
+    pub sample_interval_seconds: u64,
+    // How far back the trend line compares, and how much history is kept
+    #[serde(default = "default_trend_hours")]
+    pub trend_hours: u64,
+    #[serde(default = "default_history_hours")]
+    pub history_hours: u64,
+    // Also on the inReach, but only when it fits in the last message's spare room
+    #[serde(default)]
+    pub append_to_inreach: bool,
+    // Read a number off a screenshot of its element when the element's text has none, needs raven
+    // built with the ocr feature
+    #[serde(default)]
+    pub ocr_fallback: bool,
+}
+
+fn default_conditions_url() -> String {
+    "https://www.mountwashington.org/experience-the-weather/current-summit-conditions.aspx".to_string()
+}
+
+fn default_sample_interval_seconds() -> u64 {
+    900
+}
+
+fn default_trend_hours() -> u64 {
+    6
+}
+
+fn default_history_hours() -> u64 {
+    24
+}
+
+// SMS to regular phones through Twilio, credentials come from TWILIO_ACCOUNT_SID/TWILIO_AUTH_TOKEN
+#[derive(Debug, Clone, Deserialize)]
+pub struct TwilioConfig {
+    pub from: String,
+    pub recipients: Vec<TwilioRecipient>,
+    // Per recipient, Twilio charges per message
+    #[serde(default)]
+    pub max_messages_per_day: Option<u32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TwilioRecipient {
+    pub number: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+// Which channels each event goes to, e.g. alerts to the inReach and Twilio but only Twilio overnight.
+// In first_match mode the first matching rule decides, in all_matches every matching rule's channels
+// are combined. A changed forecast no rule matches goes everywhere, an error only goes where a rule sends it
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RoutingConfig {
+    pub mode: RoutingMode,
+    pub rules: Vec<RoutingRule>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RoutingRule {
+    // changed, alert, error, recovery, missed_update or drift, empty matches every event but drift
+    #[serde(default)]
+    pub events: Vec<EventKind>,
+    #[serde(default)]
+    pub min_severity: Severity,
+    // Local time, e.g. "21:00-05:00", unset matches all day
+    #[serde(default)]
+    pub between: Option<String>,
+    // inreach, twilio or twilio:<recipient name>, empty sends nowhere
+    #[serde(default)]
+    pub channels: Vec<String>,
+    // Forecasts the rule matches wait until it no longer does, then go wherever the rules send them
+    // then, e.g. for quiet hours. Anything else it matches goes to `channels`
+    #[serde(default)]
+    pub hold: bool,
+}
+
+// Global settings in [retry], any of them overridden per operation in [retry.navigation],
+// [retry.element_wait], [retry.persist], [retry.inreach] and [retry.notify]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub multiplier: f64,
+    pub max_delay_ms: u64,
+    pub jitter: f64,
+    pub navigation: RetryOverride,
+    pub element_wait: RetryOverride,
+    pub persist: RetryOverride,
+    pub inreach: RetryOverride,
+    pub notify: RetryOverride,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_attempts: 1,
+            base_delay_ms: 1000,
+            multiplier: 2.0,
+            max_delay_ms: 30_000,
+            jitter: 0.1,
+            navigation: RetryOverride::default(),
+            element_wait: RetryOverride::default(),
+            persist: RetryOverride::default(),
+            inreach: RetryOverride::default(),
+            notify: RetryOverride::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RetryOverride {
+    pub max_attempts: Option<u32>,
+    pub base_delay_ms: Option<u64>,
+    pub multiplier: Option<f64>,
+    pub max_delay_ms: Option<u64>,
+    pub jitter: Option<f64>,
+}
+
+impl RetryConfig {
+    pub fn policy(&self, operation: Operation) -> RetryPolicy {
+        let overrides = match operation {
+            Operation::Navigation => &self.navigation,
+            Operation::ElementWait => &self.element_wait,
+            Operation::Persist => &self.persist,
+            Operation::InReach => &self.inreach,
+            Operation::Notify => &self.notify,
+        };
+        RetryPolicy {
+            max_attempts: overrides.max_attempts.unwrap_or(self.max_attempts).max(1),
+            base_delay: Duration::from_millis(overrides.base_delay_ms.unwrap_or(self.base_delay_ms)),
+            multiplier: overrides.multiplier.unwrap_or(self.multiplier),
+            max_delay: Duration::from_millis(overrides.max_delay_ms.unwrap_or(self.max_delay_ms)),
+            jitter: overrides.jitter.unwrap_or(self.jitter).clamp(0.0, 1.0),
+            retryable,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(text: &str) -> Result<(), RavenError> {
+        Config::parse(text).unwrap().validate(false)
+    }
+
+    #[test]
+    fn the_default_config_validates() {
+        assert!(validate("").is_ok());
+    }
+
+    #[test]
+    fn a_fallback_source_needs_a_capture_time() {
+        let without = r#"
+            [[fallback.sources]]
+            name = "mirror"
+            url = "https://mirror.example/forecast.html"
+        "#;
+        assert!(matches!(validate(without), Err(RavenError::InvalidConfig(e)) if e.contains("mirror")));
+
+        let with = r#"
+            [[fallback.sources]]
+            name = "wayback"
+            url = "https://web.archive.org/web/2/https://www.mountwashington.org/"
+            capture_time = { from = "wayback_url" }
+        "#;
+        assert!(validate(with).is_ok());
+    }
+
+    #[test]
+    fn the_nws_fallback_needs_a_url() {
+        assert!(matches!(validate("[nws]"), Err(RavenError::InvalidConfig(e)) if e.contains("nws.url")));
+        assert!(validate("[nws]\nurl = \"https://zone.example/nhz002.txt\"").is_ok());
+    }
+}
@@ -0,0 +1,1344 @@
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+use chrono::Local;
+use log::{debug, info, warn};
+
+use crate::archive::{archive_revision, ArchivedRevision};
+use crate::change::{stable_hash, ChangeDetector, ChangeResult, Comparator, Revision};
+use crate::conditions::{sample_if_due, trend_line};
+use crate::config::{Config, NwsConfig, ProductConfig, Source};
+use crate::delivery::{conversation_text, DeliveryCheck};
+use crate::drift::{deviations, fingerprint};
+use crate::effects::Effects;
+use crate::error::RavenError;
+use crate::fetch::{save_capture, FetchedForecast};
+use crate::forecast::Forecast;
+use crate::format::{inreach_messages, Content, Summary};
+use crate::html::render_html;
+use crate::note::active_note;
+use crate::notify::{notify_all, PendingNotifications};
+use crate::nws::{fetch_zone_forecast, nws_product};
+use crate::output::{
+    mark_sent, persist_day_files, persist_forecast, persist_structured_forecast, read_structured_forecast,
+    OutputFiles, Provenance,
+};
+use crate::retry::{retry_with_clock, Operation};
+use crate::routing::{
+    drift_destinations, error_destinations, forecast_destinations, missed_update_destinations, recovery_destinations,
+    holds, undelivered_destinations, Destinations, EventKind, Severity,
+};
+use crate::schedule::{deadlines, missed_deadline, notice, timezone, ScheduleNotice};
+use crate::sms::append_line;
+use crate::source::ForecastSource;
+use crate::state::{MissedUpdate, OutboundChunk, OutboundSend, PendingSend, SeenIssue, State};
+use crate::trend::trend_alerts;
+use crate::validation::{content_hash, validate_outgoing};
+use crate::version::ConfigFingerprint;
+
+pub const STATE_PATH: &str = "raven_state.json";
+
+// What a single fetch cycle did, left to the caller to report
+pub struct CycleOutcome {
+    // The structured forecast from before this cycle, None on the first run
+    pub previous: Option<Forecast>,
+    pub forecast: Forecast,
+    // Set when the corresponding file was written this cycle, first run included
+    pub full: Option<String>,
+    pub abbreviated: Option<String>,
+    pub alerts: Vec<String>,
+    // Rendered for the inReach when the abbreviated forecast changed
+    pub messages: Vec<String>,
+    // What `messages` were rendered from, every other channel renders its own
+    pub content: Option<Content>,
+    // What went to the inReach this cycle, possibly an earlier revision that settled
+    pub sent: Vec<String>,
+    // Wait on these before starting the next cycle, every product's included
+    pub notifications: PendingNotifications,
+    // The extra products, in config order
+    pub products: Vec<ProductOutcome>,
+    // The delivery check made this cycle, if one was due
+    pub delivery: Option<DeliveryCheck>,
+}
+
+// A failed cycle and the stage it failed at, displayed as the underlying error
+#[derive(Debug)]
+pub struct CycleError {
+    pub stage: CycleStage,
+    pub error: Box<dyn Error>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CycleStage {
+    Fetch,
+    Processing,
+    State,
+    InReach,
+}
+
+impl CycleStage {
+    pub fn describe(self) -> &'static str {
+        match self {
+            CycleStage::Fetch => "fetching the forecast",
+            CycleStage::Processing => "processing the forecast",
+            CycleStage::State => "saving the state file",
+            CycleStage::InReach => "sending to the inReach",
+        }
+    }
+}
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl Error for CycleError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+fn at(stage: CycleStage) -> impl FnOnce(Box<dyn Error>) -> Box<dyn Error> {
+    move |error| Box::new(CycleError { stage, error })
+}
+
+pub struct ProductOutcome {
+    pub name: String,
+    // Each product fails on its own, without affecting the others or the higher summits forecast
+    pub result: Result<CycleOutcome, String>,
+}
+
+// One fetch, persist and notify. Nothing goes out over the satellite unless `send` is set, which only
+// `--send` does
+pub fn run_cycle(
+    config: &Config,
+    effects: &Effects,
+    source: &mut dyn ForecastSource,
+    send: bool,
+) -> Result<CycleOutcome, Box<dyn Error>> {
+    // Any left over are from an earlier cycle, not this one's failure
+    effects.take_captures();
+
+    // Fetch forecasts
+    let (fetched, mut outcome) = match source.fetch(config, effects) {
+        Ok(fetched) => {
+            let outcome = process_forecast(config, effects, &fetched).map_err(at(CycleStage::Processing))?;
+            (Some(fetched), outcome)
+        }
+        Err(e) => match cached_outcome(effects, e.as_ref(), &OutputFiles::default()) {
+            Some(outcome) => (None, outcome),
+            None => {
+                // Still worth trying the other products, the browser may well be up
+                let mut notifications = check_schedule(config, effects, source, send, None);
+                notifications.merge(run_products(config, effects, source, send).1);
+                let failed_before = State::load(effects, Path::new(STATE_PATH)).consecutive_failures;
+                let exhausted = sources_exhausted(config, failed_before, e.as_ref());
+                if let Some(nws) = config.nws.as_ref().filter(|_| exhausted) {
+                    match run_nws_fallback(config, effects, source, nws, send) {
+                        Ok(nws_notifications) => notifications.merge(nws_notifications),
+                        Err(e) => warn!("The NWS fallback failed too: {}", e),
+                    }
+                }
+                notifications.wait(effects, Path::new(STATE_PATH)).map_err(at(CycleStage::State))?;
+                return Err(at(CycleStage::Fetch)(e));
+            }
+        },
+    };
+    // Before sampling the conditions takes the tab elsewhere
+    let drift_notifications = match &fetched {
+        Some(fetched) => check_drift(config, effects, source, send, fetched),
+        None => PendingNotifications::none(),
+    };
+
+    if let (Some(conditions), Some(tab)) = (&config.conditions, source.tab()) {
+        sample_if_due(tab, conditions, effects);
+    }
+    // Fallback copies can be hours old, only the live page says the forecast updated. A cached one says nothing new
+    let issued = outcome
+        .forecast
+        .issued
+        .as_deref()
+        .filter(|_| fetched.as_ref().is_some_and(|fetched| !fetched.fallback));
+    let schedule_notifications = check_schedule(config, effects, source, send, issued);
+
+    let mut state = State::load(effects, Path::new(STATE_PATH));
+
+    let settle = chrono::Duration::seconds(config.sms.settle_seconds as i64);
+    let to_send = if !outcome.messages.is_empty() {
+        // A newer revision always replaces whatever was still waiting
+        let pending = PendingSend {
+            messages: outcome.messages.clone(),
+            content: outcome.content.clone().unwrap_or_default(),
+            forecast: outcome.forecast.clone(),
+            changed: effects.now(),
+            refused: None,
+        };
+
+        // Alerts are what coalescing must never delay
+        if !outcome.alerts.is_empty() {
+            state.pending_send = None;
+            Some((pending, EventKind::Alert))
+        } else if config.sms.settle_seconds == 0 {
+            state.pending_send = None;
+            Some((pending, EventKind::Changed))
+        } else {
+            info!(
+                "Holding {} messages for {}s in case the forecast is revised again",
+                pending.messages.len(),
+                config.sms.settle_seconds
+            );
+            state.pending_send = Some(pending);
+            state.save(effects, Path::new(STATE_PATH)).map_err(at(CycleStage::State))?;
+            None
+        }
+    } else {
+        match &state.pending_send {
+            Some(pending) if effects.now().signed_duration_since(pending.changed) >= settle => {
+                state.pending_send.take().map(|pending| (pending, EventKind::Changed))
+            }
+            _ => None,
+        }
+    };
+
+    // Kept as the pending send until the routing stops holding it
+    let to_send = match to_send {
+        Some((pending, kind)) if forecast_held(config, effects, kind) => {
+            info!("Routing holds this {:?} event for now", kind);
+            state.pending_send = Some(pending);
+            state.save(effects, Path::new(STATE_PATH)).map_err(at(CycleStage::State))?;
+            None
+        }
+        to_send => to_send,
+    };
+
+    if let Some((pending, kind)) = to_send {
+        // A settled send is cleared before it goes out, from here the outbound send tracks any retry
+        state.save(effects, Path::new(STATE_PATH)).map_err(at(CycleStage::State))?;
+
+        let trend = config
+            .conditions
+            .as_ref()
+            .map(|conditions| trend_line(&state.observations, effects.now(), conditions, config.language()));
+        let mut content = pending.content.clone();
+        // A send held by an older raven only has the inReach's messages
+        if content.body.is_empty() {
+            content = Content::text(pending.messages.join(" "));
+        }
+        // The note as it is now, it may have been set or cleared while the send was held
+        let note = active_note(effects, config.inreach.note.as_deref());
+        let messages = if note == content.note {
+            pending.messages.clone()
+        } else {
+            content.note = note;
+            inreach_messages(config, &content)
+        };
+        let inreach_messages = match (&trend, &config.conditions) {
+            (Some(trend), Some(conditions)) if conditions.append_to_inreach => append_line(&messages, trend, false),
+            _ => messages,
+        };
+        content.trend = trend;
+
+        let destinations = forecast_destinations(
+            config,
+            effects.notifiers(config),
+            kind,
+            forecast_severity(kind),
+            effects.clock().local_now().time(),
+        );
+
+        // Other channels are kicked off first but only the inReach is waited for. A send the inReach
+        // refused earlier has already been to them
+        if pending.refused.is_none() {
+            outcome.notifications = notify_all(destinations.notifiers, config, effects, &state, &content);
+        }
+
+        if send && !destinations.inreach {
+            info!("Routing keeps this {:?} event off the inReach", kind);
+        }
+        if send && destinations.inreach {
+            let hash = content_hash(&inreach_messages);
+            let last_sent = state.last_sent_content;
+            // Only the inReach send is skipped, it's tried again once the note or the last send changes
+            let refused = pending.refused == Some(hash)
+                || gate_send(config, effects, source, &config.source, &content.body, &inreach_messages, last_sent)
+                    .is_err();
+            if refused {
+                debug!("Holding the refused inReach send for a later cycle");
+                state.pending_send = Some(PendingSend {
+                    refused: Some(hash),
+                    ..pending
+                });
+                state.save(effects, Path::new(STATE_PATH)).map_err(at(CycleStage::State))?;
+            } else {
+                let outbound = outbound_send(pending.forecast, inreach_messages).map_err(at(CycleStage::Processing))?;
+                let outbound = resume_outbound(state.outbound.take(), outbound);
+                outcome.sent =
+                    send_outbound(config, effects, source, &mut state, outbound).map_err(at(CycleStage::InReach))?;
+            }
+        }
+    } else if send {
+        if let Some(outbound) = state.outbound.take() {
+            info!(
+                "Resuming a partial send, {} of {} parts already sent",
+                outbound.sent_count(),
+                outbound.chunks.len()
+            );
+            outcome.sent =
+                send_outbound(config, effects, source, &mut state, outbound).map_err(at(CycleStage::InReach))?;
+        }
+    }
+
+    let (delivery, delivery_notifications) = check_delivery(config, effects, source, send, &mut state);
+    outcome.delivery = delivery;
+    outcome.notifications.merge(delivery_notifications);
+
+    // Only a cycle that got this far counts as recovered
+    if let Some(failing_since) = state.failing_since.take() {
+        let failures = std::mem::take(&mut state.consecutive_failures);
+        let notified = state.failure_notified.take();
+        info!("Fetching again after {} failed cycles", failures);
+        if state.nws_last_sent.take().is_some() {
+            info!("Back on {}, no longer sending the NWS zone forecast", config.source.name);
+        }
+        state.save(effects, Path::new(STATE_PATH)).map_err(at(CycleStage::State))?;
+        if notified.is_some() && config.failures.recovery {
+            let text = format!(
+                "raven: recovered after {} failed cycles since {}",
+                failures,
+                failing_since.with_timezone(&Local).format("%a %H:%M")
+            );
+            let destinations = recovery_destinations(config, effects.notifiers(config), effects.clock().local_now().time());
+            outcome
+                .notifications
+                .merge(send_notice(config, effects, source, send, &state, destinations, &text));
+        }
+    }
+
+    let (products, notifications) = run_products(config, effects, source, send);
+    outcome.products = products;
+    outcome.notifications.merge(notifications);
+    outcome.notifications.merge(schedule_notifications);
+    outcome.notifications.merge(drift_notifications);
+
+    Ok(outcome)
+}
+
+// Once a delivery check is due, loads the MapShare conversation and records which of the last send's
+// parts are on it. Parts still missing after the second check are notified. Returns the check when
+// one ran
+fn check_delivery(
+    config: &Config,
+    effects: &Effects,
+    source: &dyn ForecastSource,
+    send: bool,
+    state: &mut State,
+) -> (Option<DeliveryCheck>, PendingNotifications) {
+    let now = effects.now();
+    if !config.delivery.enabled || !state.delivery.as_ref().is_some_and(|check| check.due(&config.delivery, now)) {
+        return (None, PendingNotifications::none());
+    }
+
+    let conversation = match (source.tab(), config.inreach.reply_url()) {
+        (Some(tab), Some(reply_url)) => conversation_text(tab, &reply_url)
+            .map_err(|e| warn!("Could not load the MapShare conversation: {}", e))
+            .ok(),
+        _ => None,
+    };
+    let check = match state.delivery.as_mut() {
+        Some(check) => check,
+        None => return (None, PendingNotifications::none()),
+    };
+    check.record(conversation.as_deref());
+    info!("inReach delivery check {}: {}", check.checks, check.summary());
+    let check = check.clone();
+    if let Err(e) = state.save(effects, Path::new(STATE_PATH)) {
+        warn!("Could not record the delivery check: {}", e);
+    }
+    if !check.undelivered() {
+        return (Some(check), PendingNotifications::none());
+    }
+
+    let text = format!(
+        "raven: {} of the {} inReach messages sent {} aren't on the MapShare conversation, the forecast may not \
+         have arrived",
+        check.missing(),
+        check.parts.len(),
+        check.sent.with_timezone(&Local).format("%a %H:%M")
+    );
+    warn!("{}", text);
+    let destinations = undelivered_destinations(config, effects.notifiers(config), effects.clock().local_now().time());
+    let notifications = send_notice(config, effects, source, send, state, destinations, &text);
+    (Some(check), notifications)
+}
+
+// Counts a failed cycle and, once `failures.after` have failed in a row, reports the failing stage
+// and error to wherever routing sends errors, every channel but the inReach by default. The count and
+// the last notice are kept in the state file, so a restart neither resets nor repeats them
+pub fn notify_failure(
+    config: &Config,
+    effects: &Effects,
+    source: &dyn ForecastSource,
+    send: bool,
+    error: &(dyn Error + 'static),
+) -> PendingNotifications {
+    let now = effects.now();
+    let mut state = State::load(effects, Path::new(STATE_PATH));
+    let failing_since = *state.failing_since.get_or_insert(now);
+    state.consecutive_failures += 1;
+
+    let (stage, cause) = match error.downcast_ref::<CycleError>() {
+        Some(failed) => (failed.stage.describe(), failed.error.as_ref()),
+        None => ("running the cycle", error),
+    };
+    let repeat = chrono::Duration::minutes(config.failures.repeat_minutes as i64);
+    let due = match cause.downcast_ref::<RavenError>() {
+        // Nothing raven can retry its way out of, so notified at once and only once until a send gets through
+        Some(RavenError::ManualLoginRequired { .. }) => {
+            let first = state.login_required.is_none();
+            state.login_required.get_or_insert(now);
+            first
+        }
+        _ => {
+            state.consecutive_failures >= config.failures.after
+                && state.failure_notified.is_none_or(|notified| {
+                    config.failures.repeat_minutes > 0 && now.signed_duration_since(notified) >= repeat
+                })
+        }
+    };
+    if due {
+        state.failure_notified = Some(now);
+    }
+    if let Err(e) = state.save(effects, Path::new(STATE_PATH)) {
+        warn!("Could not record the failure: {}", e);
+    }
+    if !due {
+        return PendingNotifications::none();
+    }
+
+    let severity = match cause.downcast_ref::<RavenError>() {
+        Some(RavenError::AllSourcesFailed) => Severity::Critical,
+        _ => Severity::Warning,
+    };
+    let repeated = match state.consecutive_failures {
+        1 => String::new(),
+        failures => format!(
+            ", {} cycles in a row since {}",
+            failures,
+            failing_since.with_timezone(&Local).format("%a %H:%M")
+        ),
+    };
+    let mut text = format!("raven: failed {}{}: {}", stage, repeated, cause);
+    if let Some(capture) = effects.take_captures().last() {
+        text.push_str(&format!(", debug capture saved to {}", capture.display()));
+    }
+
+    let destinations = error_destinations(config, effects.notifiers(config), severity, now.with_timezone(&Local).time());
+    send_notice(config, effects, source, send, &state, destinations, &text)
+}
+
+// Sends one of raven's own notices, e.g. a failure or a missed update, to the inReach when routed there
+// and kicks off the other channels
+fn send_notice(
+    config: &Config,
+    effects: &Effects,
+    source: &dyn ForecastSource,
+    send: bool,
+    state: &State,
+    destinations: Destinations,
+    text: &str,
+) -> PendingNotifications {
+    let content = Content::text(text);
+    if send && destinations.inreach {
+        let messages = inreach_messages(config, &content);
+        if let Err(e) = effects.send_inreach(source.tab(), config, &messages, &mut |_| {}) {
+            warn!("Could not send {:?} to the inReach: {}", text, e);
+        }
+    }
+    notify_all(destinations.notifiers, config, effects, state, &content)
+}
+
+// Records a new issue time, then notifies an expected-update deadline passing without one, reminds at
+// the configured cadence and sends an all-clear once one turns up. `issued` is None when there's
+// nothing to go by this cycle
+pub fn check_schedule(
+    config: &Config,
+    effects: &Effects,
+    source: &dyn ForecastSource,
+    send: bool,
+    issued: Option<&str>,
+) -> PendingNotifications {
+    let mut state = State::load(effects, Path::new(STATE_PATH));
+    let local_now = effects.clock().local_now();
+    let mut changed = false;
+    if let Some(issued) = issued {
+        if state.issue.as_ref().is_none_or(|seen| seen.issued != issued) {
+            // The first issue time seen is just when raven started, only a change is an update
+            if state.issue.is_some() {
+                state.update_times.record(local_now.naive_local());
+            }
+            state.issue = Some(SeenIssue {
+                issued: issued.to_string(),
+                seen: effects.now(),
+            });
+            changed = true;
+        }
+    }
+
+    let schedule = match &config.schedule {
+        Some(schedule) => schedule,
+        None => {
+            if changed {
+                if let Err(e) = state.save(effects, Path::new(STATE_PATH)) {
+                    warn!("Could not record the issue time: {}", e);
+                }
+            }
+            return PendingNotifications::none();
+        }
+    };
+    // Checked at startup
+    let deadlines = deadlines(schedule).unwrap_or_default();
+    let timezone = timezone(schedule).unwrap_or(chrono_tz::America::New_York);
+
+    let now = effects.now().with_timezone(&timezone).naive_local();
+    let last_update = state
+        .issue
+        .as_ref()
+        .map(|seen| seen.seen.with_timezone(&timezone).naive_local());
+    let notified = state.missed_update.as_ref().map(|missed| (missed.deadline, missed.reminded));
+    let reminder = Some(chrono::Duration::hours(schedule.reminder_hours as i64)).filter(|_| schedule.reminder_hours > 0);
+    let notice = notice(missed_deadline(&deadlines, last_update, now), notified, reminder, now);
+
+    let last_issued = state
+        .issue
+        .as_ref()
+        .map(|seen| seen.issued.clone())
+        .unwrap_or_else(|| "n/a".to_string());
+    let (text, severity) = match notice {
+        None => {
+            if changed {
+                if let Err(e) = state.save(effects, Path::new(STATE_PATH)) {
+                    warn!("Could not record the issue time: {}", e);
+                }
+            }
+            return PendingNotifications::none();
+        }
+        Some(ScheduleNotice::Missed { deadline }) => {
+            state.missed_update = Some(MissedUpdate { deadline, reminded: now });
+            (
+                format!("raven: no new forecast by {}, the last was issued {}", deadline.format("%H:%M"), last_issued),
+                Severity::Warning,
+            )
+        }
+        Some(ScheduleNotice::Reminder { deadline }) => {
+            state.missed_update = Some(MissedUpdate { deadline, reminded: now });
+            (
+                format!(
+                    "raven: still no new forecast since the {} deadline, the last was issued {}",
+                    deadline.format("%a %H:%M"),
+                    last_issued
+                ),
+                Severity::Warning,
+            )
+        }
+        Some(ScheduleNotice::AllClear) => {
+            state.missed_update = None;
+            (format!("raven: the forecast is updating again, issued {}", last_issued), Severity::Info)
+        }
+    };
+    if let Err(e) = state.save(effects, Path::new(STATE_PATH)) {
+        warn!("Could not record the missed update: {}", e);
+    }
+
+    info!("{}", text);
+    let destinations = missed_update_destinations(config, effects.notifiers(config), severity, local_now.time());
+    send_notice(config, effects, source, send, &state, destinations, &text)
+}
+
+// Compares the structure of the page the forecast was just read from with its rolling baseline. Drift
+// is logged every cycle it lasts, notified and captured once when it starts
+fn check_drift(
+    config: &Config,
+    effects: &Effects,
+    source: &dyn ForecastSource,
+    send: bool,
+    fetched: &FetchedForecast,
+) -> PendingNotifications {
+    // A fallback's page has a structure of its own
+    let tab = match source.tab() {
+        Some(tab) if config.drift.enabled && !fetched.fallback => tab,
+        _ => return PendingNotifications::none(),
+    };
+    let current = fingerprint(tab, &config.source, &config.drift.expect, fetched);
+
+    let mut state = State::load(effects, Path::new(STATE_PATH));
+    let deviations = deviations(&state.structure.samples, &current, &config.drift);
+    state.structure.push(current, config.drift.window);
+    let started = !deviations.is_empty() && state.structure.drifting_since.is_none();
+    if deviations.is_empty() {
+        if state.structure.drifting_since.take().is_some() {
+            info!("The page structure is back within tolerance of its baseline");
+        }
+    } else {
+        warn!("The page structure has drifted from its baseline: {}", deviations.join("; "));
+        state.structure.drifting_since.get_or_insert(effects.now());
+    }
+    if let Err(e) = state.save(effects, Path::new(STATE_PATH)) {
+        warn!("Could not record the page structure: {}", e);
+    }
+    if !started {
+        return PendingNotifications::none();
+    }
+
+    let text = format!(
+        "raven: the forecast page's structure has drifted, check the selectors: {}",
+        deviations.join("; ")
+    );
+    if config.drift.capture {
+        let reason: Box<dyn Error> = text.clone().into();
+        save_capture(tab, config, effects, &config.source, Some(fetched), reason.as_ref());
+    }
+    let destinations = drift_destinations(config, effects.notifiers(config), effects.clock().local_now().time());
+    send_notice(config, effects, source, send, &state, destinations, &text)
+}
+
+fn run_products(
+    config: &Config,
+    effects: &Effects,
+    source: &mut dyn ForecastSource,
+    send: bool,
+) -> (Vec<ProductOutcome>, PendingNotifications) {
+    let mut notifications = PendingNotifications::none();
+    let products = config
+        .products
+        .iter()
+        .map(|product| {
+            let result = match run_product(config, effects, source, product, send) {
+                Ok(mut outcome) => {
+                    notifications.merge(std::mem::replace(&mut outcome.notifications, PendingNotifications::none()));
+                    Ok(outcome)
+                }
+                Err(e) => {
+                    warn!("{} failed: {}", product.name, e);
+                    Err(e.to_string())
+                }
+            };
+            ProductOutcome {
+                name: product.name.clone(),
+                result,
+            }
+        })
+        .collect();
+
+    (products, notifications)
+}
+
+// An extra product goes straight out to wherever it's routed, without the higher summits
+// forecast's settle period or trend line. A failed inReach send is resumed next cycle
+fn run_product(
+    config: &Config,
+    effects: &Effects,
+    source: &mut dyn ForecastSource,
+    product: &ProductConfig,
+    send: bool,
+) -> Result<CycleOutcome, Box<dyn Error>> {
+    let files = OutputFiles::with_prefix(&product.prefix);
+    let known = read_structured_forecast(effects, &files);
+    let mut outcome = match source.fetch_product(config, effects, &product.source, known.as_ref()) {
+        Ok(fetched) => detect_changes(config, effects, &fetched, Some(product))?,
+        Err(e) => cached_outcome(effects, e.as_ref(), &files).ok_or(e)?,
+    };
+
+    if let (true, Some(content)) = (product.notify, &outcome.content) {
+        outcome.notifications = notify_all(
+            effects.notifiers(config),
+            config,
+            effects,
+            &State::load(effects, Path::new(STATE_PATH)),
+            content,
+        );
+    }
+    if product.inreach && send {
+        // A send that failed last cycle is resumed even though its forecast has been persisted since
+        let mut state = State::load(effects, Path::new(STATE_PATH));
+        let previous = state.product_outbound.remove(&product.name);
+        let last_sent = state.product_sent_content.get(&product.name).copied();
+        let outbound = match &outcome.content {
+            Some(content) => {
+                match gate_send(config, effects, source, &product.source, &content.body, &outcome.messages, last_sent) {
+                    Ok(()) => {
+                        let outbound = outbound_send(outcome.forecast.clone(), outcome.messages.clone())?;
+                        Some(resume_outbound(previous, outbound))
+                    }
+                    // A refused send is skipped, the product's next revision goes out as usual
+                    Err(_) => previous,
+                }
+            }
+            None => previous,
+        };
+        if let Some(outbound) = outbound {
+            let forecast = outbound.forecast.clone();
+            outcome.sent = send_product_outbound(config, effects, source, &product.name, outbound)?;
+            mark_sent(effects, &files, &forecast, effects.now());
+        }
+    }
+
+    Ok(outcome)
+}
+
+// Sends an extra product's unsent chunks, kept in the state under its name until every one has gone so
+// a failed send resumes next cycle. Returns the chunks sent this time
+fn send_product_outbound(
+    config: &Config,
+    effects: &Effects,
+    source: &dyn ForecastSource,
+    name: &str,
+    outbound: OutboundSend,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let unsent = outbound.unsent();
+    let messages = unsent.iter().map(|i| outbound.chunks[*i].text.clone()).collect::<Vec<_>>();
+    let mut state = State::load(effects, Path::new(STATE_PATH));
+    state.product_outbound.insert(name.to_string(), outbound);
+    state.save(effects, Path::new(STATE_PATH))?;
+
+    effects.send_inreach(source.tab(), config, &messages, &mut |i| {
+        if let Some(outbound) = state.product_outbound.get_mut(name) {
+            outbound.chunks[unsent[i]].sent = true;
+        }
+        if let Err(e) = state.save(effects, Path::new(STATE_PATH)) {
+            warn!("Could not record sent part {} of {}: {}", unsent[i] + 1, name, e);
+        }
+    })?;
+
+    if let Some(outbound) = state.product_outbound.remove(name) {
+        let texts = outbound.chunks.iter().map(|chunk| chunk.text.clone()).collect::<Vec<_>>();
+        state.product_sent_content.insert(name.to_string(), content_hash(&texts));
+    }
+    state.login_required = None;
+    state.save(effects, Path::new(STATE_PATH))?;
+    Ok(messages)
+}
+
+// A page still showing an older forecast after the hard reload is a cache somewhere upstream rather than
+// a failure, so the cycle goes on with the forecast already in `files` as unchanged. None for any other error
+fn cached_outcome(effects: &Effects, error: &(dyn Error + 'static), files: &OutputFiles) -> Option<CycleOutcome> {
+    let (source, rendered, known) = match error.downcast_ref::<RavenError>() {
+        Some(RavenError::CachedPage { source, rendered, known }) => (source, rendered, known),
+        _ => return None,
+    };
+    let forecast = read_structured_forecast(effects, files)?;
+    info!("{} still shows the forecast issued {} after a hard reload, keeping {}", source, rendered, known);
+    Some(CycleOutcome {
+        previous: Some(forecast.clone()),
+        forecast,
+        full: None,
+        abbreviated: None,
+        alerts: Vec::new(),
+        messages: Vec::new(),
+        content: None,
+        sent: Vec::new(),
+        notifications: PendingNotifications::none(),
+        products: Vec::new(),
+        delivery: None,
+    })
+}
+
+// Once every source has failed, or with no fallback sources, once the primary has failed as many cycles
+// in a row as the fallback sources would have waited. `failed_before` doesn't count this cycle's `error`
+fn sources_exhausted(config: &Config, failed_before: u32, error: &(dyn Error + 'static)) -> bool {
+    match error.downcast_ref::<RavenError>() {
+        Some(RavenError::AllSourcesFailed) => true,
+        _ => config.fallback.sources.is_empty() && failed_before + 1 >= config.fallback.after_failures,
+    }
+}
+
+// The NWS zone forecast in place of the higher summits forecast, routed like a changed one but kept
+// in its own files and sent again only once it differs from its own last send. The inReach goes
+// first, a failed send is retried along with the other channels next cycle
+fn run_nws_fallback(
+    config: &Config,
+    effects: &Effects,
+    source: &dyn ForecastSource,
+    nws: &NwsConfig,
+    send: bool,
+) -> Result<PendingNotifications, Box<dyn Error>> {
+    let product = nws_product(nws);
+    let fetched = fetch_zone_forecast(config, nws, effects)?;
+    let forecast = detect_changes(config, effects, &fetched, Some(&product))?.forecast;
+    let mut state = State::load(effects, Path::new(STATE_PATH));
+    let issued = forecast.issued.clone().unwrap_or_else(|| "n/a".to_string());
+    if state.nws_last_sent.as_ref() == Some(&forecast) {
+        debug!("The NWS zone forecast issued {} has already been sent", issued);
+        return Ok(PendingNotifications::none());
+    }
+
+    warn!("Every source of {} failed, sending the NWS zone forecast issued {}", config.source.name, issued);
+    let body = fetched.with_days(product.source.abbreviation.days).abbreviated();
+    let note = active_note(effects, config.inreach.note.as_deref());
+    let content = forecast_content(config, Some(&product), body, Vec::new(), None, &forecast, note);
+    let destinations = forecast_destinations(
+        config,
+        effects.notifiers(config),
+        EventKind::Changed,
+        Severity::Info,
+        effects.clock().local_now().time(),
+    );
+    let messages = inreach_messages(config, &content);
+    let last_sent = state.product_sent_content.get(&product.name).copied();
+    // A refused send only keeps it off the inReach, the other channels still get it
+    if send
+        && destinations.inreach
+        && gate_send(config, effects, source, &product.source, &content.body, &messages, last_sent).is_ok()
+    {
+        effects.send_inreach(source.tab(), config, &messages, &mut |_| {})?;
+        state.product_sent_content.insert(product.name.clone(), content_hash(&messages));
+        state.login_required = None;
+        mark_sent(effects, &OutputFiles::with_prefix(&product.prefix), &forecast, effects.now());
+    }
+
+    state.nws_last_sent = Some(forecast);
+    state.save(effects, Path::new(STATE_PATH))?;
+    Ok(notify_all(destinations.notifiers, config, effects, &state, &content))
+}
+
+fn outbound_send(forecast: Forecast, messages: Vec<String>) -> Result<OutboundSend, Box<dyn Error>> {
+    Ok(OutboundSend {
+        forecast_hash: stable_hash(&serde_json::to_string(&forecast)?),
+        forecast,
+        chunks: messages
+            .into_iter()
+            .map(|text| OutboundChunk {
+                hash: stable_hash(&text),
+                text,
+                sent: false,
+            })
+            .collect(),
+    })
+}
+
+// Picks up where a partial send of the same forecast left off, one of a forecast that has changed since
+// is abandoned for the new one
+fn resume_outbound(previous: Option<OutboundSend>, outbound: OutboundSend) -> OutboundSend {
+    match previous {
+        Some(previous) if previous.forecast_hash == outbound.forecast_hash => outbound.resume(&previous),
+        Some(previous) => {
+            info!(
+                "Abandoning a partial send ({} of {} parts sent), the forecast has changed since",
+                previous.sent_count(),
+                previous.chunks.len()
+            );
+            outbound
+        }
+        None => outbound,
+    }
+}
+
+// Forecasts leading with trend alerts are warnings, the rest info
+fn forecast_severity(kind: EventKind) -> Severity {
+    match kind {
+        EventKind::Alert => Severity::Warning,
+        _ => Severity::Info,
+    }
+}
+
+// Whether the routing holds a forecast at the clock's local time
+fn forecast_held(config: &Config, effects: &Effects, kind: EventKind) -> bool {
+    let now = effects.clock().local_now().time();
+    config.routing.as_ref().is_some_and(|routing| holds(routing, kind, forecast_severity(kind), now))
+}
+
+// The last check before satellite messages are spent. A refusal is logged and captured for debugging,
+// nothing is recorded as sent so the next send is compared against the last good one
+fn gate_send(
+    config: &Config,
+    effects: &Effects,
+    source: &dyn ForecastSource,
+    page_source: &Source,
+    body: &str,
+    messages: &[String],
+    last_sent: Option<u64>,
+) -> Result<(), RavenError> {
+    if let Err(e) = validate_outgoing(body, messages, last_sent, &config.validation) {
+        warn!("{}", e);
+        if let Some(tab) = source.tab() {
+            save_capture(tab, config, effects, page_source, None, &e);
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+// Sends the unsent chunks, recording each one in the state file as it goes. A failure leaves the
+// send in the state for the next cycle to resume. Returns the chunks sent this time
+fn send_outbound(
+    config: &Config,
+    effects: &Effects,
+    source: &dyn ForecastSource,
+    state: &mut State,
+    outbound: OutboundSend,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let messages = outbound
+        .unsent()
+        .iter()
+        .map(|i| outbound.chunks[*i].text.clone())
+        .collect::<Vec<_>>();
+    state.outbound = Some(outbound);
+    state.save(effects, Path::new(STATE_PATH))?;
+
+    // Each attempt picks up from the first part still unsent
+    let policy = config.retry.policy(Operation::InReach);
+    retry_with_clock(&policy, effects.clock(), "Sending to the inReach", || {
+        let (unsent, remaining) = match &state.outbound {
+            Some(outbound) => {
+                let unsent = outbound.unsent();
+                let remaining = unsent.iter().map(|i| outbound.chunks[*i].text.clone()).collect::<Vec<_>>();
+                (unsent, remaining)
+            }
+            None => return Ok(()),
+        };
+        effects.send_inreach(source.tab(), config, &remaining, &mut |i| {
+            if let Some(outbound) = &mut state.outbound {
+                outbound.chunks[unsent[i]].sent = true;
+            }
+            if let Err(e) = state.save(effects, Path::new(STATE_PATH)) {
+                warn!("Could not record sent part {}: {}", unsent[i] + 1, e);
+            }
+        })
+    })?;
+
+    let outbound = state.outbound.take();
+    if let Some(outbound) = &outbound {
+        let parts = outbound.chunks.iter().map(|chunk| chunk.text.clone()).collect::<Vec<_>>();
+        state.last_sent_content = Some(content_hash(&parts));
+        if config.delivery.enabled {
+            state.delivery = Some(DeliveryCheck::new(&parts, effects.now()));
+        }
+    }
+    state.last_sent = outbound.map(|outbound| outbound.forecast);
+    state.login_required = None;
+    state.save(effects, Path::new(STATE_PATH))?;
+    if let Some(sent) = &state.last_sent {
+        mark_sent(effects, &OutputFiles::default(), sent, effects.now());
+    }
+    Ok(messages)
+}
+
+// Everything a cycle does with a fetched forecast short of sending it: change detection,
+// persistence, alerts and message preparation. All IO goes through `effects`, so this runs
+// the same against the live site, a replayed archive or in-memory storage
+pub fn process_forecast(
+    config: &Config,
+    effects: &Effects,
+    fetched: &FetchedForecast,
+) -> Result<CycleOutcome, Box<dyn Error>> {
+    let outcome = detect_changes(config, effects, fetched, None)?;
+    // Everything but the files a send is made from keeps every day
+    let forecast = &Forecast::parse(&fetched.full, &fetched.day_blocks);
+    let lang = config.language();
+
+    // Each day file is only rewritten when its own content changed, so this is cheap to run every cycle
+    if config.output.day_files {
+        if let Err(e) = persist_day_files(effects, &config.output.day_files_dir, forecast, lang) {
+            warn!("Could not write day files: {}", e);
+        }
+    }
+
+    let changed = outcome.full.is_some() || outcome.abbreviated.is_some();
+    if config.archive.enabled && changed {
+        let revision = ArchivedRevision::new(fetched, forecast, effects.now());
+        if let Err(e) = archive_revision(effects, &config.archive.dir, config.archive.format, &revision) {
+            warn!("Could not archive revision: {}", e);
+        }
+    }
+
+    // The web page is a nicety, a rendering problem must never hold up the text files
+    if config.html.enabled && (changed || !effects.exists(&config.html.path)) {
+        let html = render_html(
+            forecast,
+            &fetched.full_text(),
+            config.html.refresh_seconds,
+            effects.clock().local_now(),
+        );
+        if let Err(e) = effects.write_atomic(&config.html.path, &html) {
+            warn!("Could not write {}: {}", config.html.path.display(), e);
+        }
+    }
+
+    Ok(outcome)
+}
+
+// Compares against and updates one product's files, the higher summits forecast's when `product` is None
+fn detect_changes(
+    config: &Config,
+    effects: &Effects,
+    fetched: &FetchedForecast,
+    product: Option<&ProductConfig>,
+) -> Result<CycleOutcome, Box<dyn Error>> {
+    let full_forecast = fetched.full_text();
+    // A fallback is limited as the primary would be
+    let source = product.map(|product| &product.source).unwrap_or(&config.source);
+    let fetched = &fetched.with_days(source.abbreviation.days);
+    let abbreviated_forecast = fetched.abbreviated();
+    let forecast = Forecast::parse(&fetched.full, &fetched.day_blocks);
+    let files = product
+        .map(|product| OutputFiles::with_prefix(&product.prefix))
+        .unwrap_or_default();
+    let provenance = Provenance::new(
+        fetched,
+        source_url(config, product, &fetched.source),
+        &forecast,
+        &ConfigFingerprint::of(config).hash,
+    );
+
+    let mut outcome = CycleOutcome {
+        previous: read_structured_forecast(effects, &files),
+        forecast: forecast.clone(),
+        full: None,
+        abbreviated: None,
+        alerts: Vec::new(),
+        messages: Vec::new(),
+        content: None,
+        sent: Vec::new(),
+        notifications: PendingNotifications::none(),
+        products: Vec::new(),
+        delivery: None,
+    };
+
+    let lang = config.language();
+    // The structured forecast is otherwise only written with a change, created up front so the next
+    // cycle has something to diff against
+    if !effects.exists(&files.json) {
+        persist_structured_forecast(effects, &files, &forecast, lang, &provenance)?;
+    }
+
+    // Whitespace-insensitive so paragraph joins don't register as a change against older single-paragraph files
+    let previous_full = read_previous(effects, &files.full);
+    let full_change = ChangeDetector::new(Comparator::Normalized).detect(
+        previous_full.as_deref().map(|text| Revision { text, forecast: None }),
+        Revision {
+            text: &full_forecast,
+            forecast: None,
+        },
+        &[],
+    );
+    if full_change.persist() {
+        persist_forecast(effects, &full_forecast, &files.full, &provenance)?;
+        outcome.full = Some(full_forecast);
+    }
+
+    // Only the higher summits forecast keeps what was last sent and the flap history
+    let mut state = match product {
+        Some(_) => None,
+        None => Some(State::load(effects, Path::new(STATE_PATH))),
+    };
+    let detector = ChangeDetector::new(config.change.comparator);
+    let previous_abbreviated = read_previous(effects, &files.abbreviated);
+    let previous_forecast = outcome.previous.as_ref();
+    let previous = previous_abbreviated.as_deref().map(|text| Revision {
+        text,
+        forecast: previous_forecast,
+    });
+    let candidate = Revision {
+        text: &abbreviated_forecast,
+        forecast: Some(&forecast),
+    };
+    let history = state.as_ref().map(|state| state.recent_revisions.as_slice()).unwrap_or_default();
+    let change = detector.detect(previous, candidate, history);
+
+    match &change {
+        ChangeResult::Unchanged => {}
+        ChangeResult::FirstRun => {
+            persist_forecast(effects, &abbreviated_forecast, &files.abbreviated, &provenance)?;
+            outcome.abbreviated = Some(abbreviated_forecast.clone());
+        }
+        ChangeResult::FlapSuppressed => {
+            info!("The forecast went back to a recent revision, not sending it again");
+            persist_forecast(effects, &abbreviated_forecast, &files.abbreviated, &provenance)?;
+            persist_structured_forecast(effects, &files, &forecast, lang, &provenance)?;
+        }
+        ChangeResult::Changed { diff } => {
+            debug!("{} numbers changed since the last revision", diff.len());
+            persist_forecast(effects, &abbreviated_forecast, &files.abbreviated, &provenance)?;
+            outcome.abbreviated = Some(abbreviated_forecast.clone());
+
+            if let Some(previous) = previous_forecast {
+                outcome.alerts = trend_alerts(previous, &forecast, lang);
+            }
+            persist_structured_forecast(effects, &files, &forecast, lang, &provenance)?;
+
+            let content = forecast_content(
+                config,
+                product,
+                abbreviated_forecast.clone(),
+                outcome.alerts.clone(),
+                state.as_ref().and_then(|state| state.last_sent.clone()),
+                &forecast,
+                active_note(effects, config.inreach.note.as_deref()),
+            );
+            outcome.messages = inreach_messages(config, &content);
+            outcome.content = Some(content);
+        }
+    }
+
+    // The revision being replaced joins the history, so flipping back to it later is recognised
+    if let (Some(state), Some(previous), ChangeResult::Changed { .. } | ChangeResult::FlapSuppressed) =
+        (state.as_mut(), previous, &change)
+    {
+        if config.change.flap_history > 0 {
+            state
+                .recent_revisions
+                .push(detector.fingerprint(previous, candidate.forecast.is_some()));
+            let excess = state.recent_revisions.len().saturating_sub(config.change.flap_history);
+            state.recent_revisions.drain(..excess);
+            state.save(effects, Path::new(STATE_PATH))?;
+        }
+    }
+
+    Ok(outcome)
+}
+
+// What a changed forecast goes out as, `raven render` previews through here too
+pub fn forecast_content(
+    config: &Config,
+    product: Option<&ProductConfig>,
+    body: String,
+    alerts: Vec<String>,
+    last_sent: Option<Forecast>,
+    forecast: &Forecast,
+    note: Option<String>,
+) -> Content {
+    Content {
+        heading: product.map(|product| product.name.clone()),
+        alerts,
+        body,
+        note,
+        trend: None,
+        summary: config.sms.summary_message.then(|| Summary {
+            last_sent,
+            forecast: forecast.clone(),
+        }),
+    }
+}
+
+// The configured url of the source a forecast came from, by name
+fn source_url<'a>(config: &'a Config, product: Option<&'a ProductConfig>, name: &str) -> Option<&'a str> {
+    product
+        .map(|product| &product.source)
+        .into_iter()
+        .chain(std::iter::once(&config.source))
+        .chain(&config.fallback.sources)
+        .find(|source| source.name == name)
+        .map(|source| source.url.as_str())
+}
+
+// The persisted text of the last revision, None before the first run (or one a dry run would have created)
+fn read_previous(effects: &Effects, path: &Path) -> Option<String> {
+    if !effects.exists(path) {
+        return None;
+    }
+    Some(effects.read(path).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forecast(high: i32) -> Forecast {
+        let blocks = vec![format!("Today:\nHighs around {}F. NW winds 50 to 70 mph.", high)];
+        Forecast::parse("Issued: 5:07 AM Friday, January 12, 2024", &blocks)
+    }
+
+    fn parts() -> Vec<String> {
+        (1..=4).map(|i| format!("({}/4) part {}", i, i)).collect()
+    }
+
+    // What the state holds after the send died with two of the four parts out
+    fn failed_after_two(high: i32) -> OutboundSend {
+        let mut outbound = outbound_send(forecast(high), parts()).unwrap();
+        outbound.chunks[0].sent = true;
+        outbound.chunks[1].sent = true;
+        outbound
+    }
+
+    #[test]
+    fn a_send_that_failed_after_two_parts_resumes_at_the_third() {
+        let retry = outbound_send(forecast(5), parts()).unwrap();
+        let resumed = resume_outbound(Some(failed_after_two(5)), retry);
+        assert_eq!(resumed.sent_count(), 2);
+        assert_eq!(resumed.unsent(), vec![2, 3]);
+    }
+
+    #[test]
+    fn a_changed_part_and_everything_after_it_go_again() {
+        let mut messages = parts();
+        messages[1] = "(2/4) part 2, revised".to_string();
+        let retry = outbound_send(forecast(5), messages).unwrap();
+        assert_eq!(resume_outbound(Some(failed_after_two(5)), retry).unsent(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn a_changed_forecast_abandons_the_partial_send() {
+        let retry = outbound_send(forecast(12), parts()).unwrap();
+        let resumed = resume_outbound(Some(failed_after_two(5)), retry);
+        assert_eq!(resumed.sent_count(), 0);
+        assert_eq!(resumed.unsent(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn outbound_hashes_are_stable() {
+        // Persisted in the state file, so they have to come out the same from one build to the next
+        let outbound = outbound_send(forecast(5), parts()).unwrap();
+        assert_eq!(outbound.chunks[0].hash, stable_hash("(1/4) part 1"));
+        assert_eq!(outbound.forecast_hash, stable_hash(&serde_json::to_string(&forecast(5)).unwrap()));
+    }
+
+    // Always the live page still showing the forecast from before the known one
+    struct CachedSource;
+
+    impl ForecastSource for CachedSource {
+        fn fetch(&mut self, _config: &Config, _effects: &Effects) -> Result<FetchedForecast, Box<dyn Error>> {
+            Err(Box::new(RavenError::CachedPage {
+                source: "higher summits".to_string(),
+                rendered: "5:07 AM Thursday, January 11, 2024".to_string(),
+                known: "5:07 AM Friday, January 12, 2024".to_string(),
+            }))
+        }
+    }
+
+    #[test]
+    fn a_page_still_cached_after_the_hard_reload_is_unchanged() {
+        let config = Config::default();
+        let effects = Effects::with_storage(false, std::sync::Arc::new(crate::storage::MemoryStorage::new()));
+        let day_blocks = vec![
+            "Today:\nIn the clouds with snow showers. Highs around 5F. NW winds 50 to 70 mph.".to_string(),
+            "Tonight:\nIn the clouds with snow. Lows around -10F. NW winds 60 to 80 mph.".to_string(),
+            "Saturday:\nClearing in the afternoon. Highs around 5F. W winds 40 to 60 mph.".to_string(),
+        ];
+        let fetched = FetchedForecast {
+            full: format!("Issued: 5:07 AM Friday, January 12, 2024\n\n{}", day_blocks.join("\n\n")),
+            day_blocks,
+            source: config.source.name.clone(),
+            captured: effects.now(),
+            fallback: false,
+        };
+        let known = process_forecast(&config, &effects, &fetched).unwrap().forecast;
+
+        let outcome = run_cycle(&config, &effects, &mut CachedSource, false).unwrap();
+        assert_eq!(outcome.forecast, known);
+        assert!(outcome.full.is_none() && outcome.abbreviated.is_none() && outcome.messages.is_empty());
+        assert_eq!(State::load(&effects, Path::new(STATE_PATH)).consecutive_failures, 0);
+    }
+
+    #[test]
+    fn a_cached_page_with_nothing_known_is_still_an_error() {
+        let effects = Effects::with_storage(false, std::sync::Arc::new(crate::storage::MemoryStorage::new()));
+        assert!(run_cycle(&Config::default(), &effects, &mut CachedSource, false).is_err());
+    }
+
+    // Deadlines at 7:00 Eastern, first seeing an issue time at `start` and then checking at each of `checks`
+    fn missed_by(start: &str, checks: &[&str]) -> Vec<Option<chrono::NaiveDateTime>> {
+        let config = Config::parse("[schedule]\ndeadlines = [\"07:00\"]").unwrap();
+        let clock = std::sync::Arc::new(crate::clock::ManualClock::new(start.parse().unwrap()));
+        let storage = std::sync::Arc::new(crate::storage::MemoryStorage::new());
+        let effects = Effects::with_storage(false, storage).with_clock(clock.clone());
+        let source = crate::source::FixtureSource::new(Vec::new());
+
+        check_schedule(&config, &effects, &source, false, Some("5:07 AM"));
+        checks
+            .iter()
+            .map(|check| {
+                clock.set(check.parse().unwrap());
+                check_schedule(&config, &effects, &source, false, Some("5:07 AM"));
+                State::load(&effects, Path::new(STATE_PATH)).missed_update.map(|missed| missed.deadline)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn deadlines_are_eastern_whatever_the_local_zone() {
+        // Last seen at 6:00 EST the day before, so the 7:00 EST deadline (12:00 UTC) is missed
+        let missed = missed_by("2024-01-11T11:00:00Z", &["2024-01-12T11:59:00Z", "2024-01-12T12:00:00Z"]);
+        let deadline = "2024-01-12T07:00:00".parse().unwrap();
+        assert_eq!(missed, [None, Some(deadline)]);
+    }
+
+    #[test]
+    fn deadlines_follow_daylight_saving_time() {
+        // 7:00 EDT is 11:00 UTC
+        let missed = missed_by("2024-07-11T10:00:00Z", &["2024-07-12T10:59:00Z", "2024-07-12T11:00:00Z"]);
+        let deadline = "2024-07-12T07:00:00".parse().unwrap();
+        assert_eq!(missed, [None, Some(deadline)]);
+    }
+
+    // Every send the inReach was asked for
+    #[derive(Default)]
+    struct Recorder(std::sync::Mutex<Vec<Vec<String>>>);
+
+    impl crate::inreach::InReachSender for Recorder {
+        fn send(&self, messages: &[String], on_sent: &mut dyn FnMut(usize)) -> Result<(), Box<dyn Error>> {
+            self.0.lock().unwrap().push(messages.to_vec());
+            (0..messages.len()).for_each(on_sent);
+            Ok(())
+        }
+    }
+
+    fn fetched(effects: &Effects, today: &str) -> FetchedForecast {
+        let day_blocks = vec![
+            format!("Today:\n{}", today),
+            "Tonight:\nIn the clouds with snow. Lows around -10F. NW winds 60 to 80 mph.".to_string(),
+            "Saturday:\nClearing in the afternoon. Highs around 5F. W winds 40 to 60 mph.".to_string(),
+        ];
+        FetchedForecast {
+            full: format!("Issued: 5:07 AM Friday, January 12, 2024\n\n{}", day_blocks.join("\n\n")),
+            day_blocks,
+            source: "higher summits".to_string(),
+            captured: effects.now(),
+            fallback: false,
+        }
+    }
+
+    #[test]
+    fn a_refused_send_is_kept_for_a_later_cycle() {
+        let recorder = std::sync::Arc::new(Recorder::default());
+        let storage = std::sync::Arc::new(crate::storage::MemoryStorage::new());
+        let effects = Effects::with_storage(false, storage).with_inreach(recorder.clone());
+        let config = Config::default();
+        let mut source = crate::source::FixtureSource::new(vec![
+            fetched(&effects, "In the clouds with snow showers. Highs around 5F. NW winds 50 to 70 mph."),
+            fetched(&effects, "In the clouds with snow showers. Highs around 8F. NW winds 50 to 70 mph."),
+            // A new revision to the comparator, the same messages once sanitized
+            fetched(&effects, "In the clouds with snow showers.  Highs around 8F. NW winds 50 to 70 mph."),
+            fetched(&effects, "In the clouds with snow showers.  Highs around 8F. NW winds 50 to 70 mph."),
+        ]);
+
+        // The first run sends nothing, the revision after it goes out
+        run_cycle(&config, &effects, &mut source, true).unwrap();
+        run_cycle(&config, &effects, &mut source, true).unwrap();
+        assert_eq!(recorder.0.lock().unwrap().len(), 1);
+
+        let refused = run_cycle(&config, &effects, &mut source, true).unwrap();
+        assert!(!refused.messages.is_empty() && refused.sent.is_empty());
+        let state = State::load(&effects, Path::new(STATE_PATH));
+        assert!(state.pending_send.is_some_and(|pending| pending.refused.is_some()));
+        assert_eq!(state.consecutive_failures, 0);
+
+        // The note makes it a different send
+        crate::note::set_note(&effects, "Bail via Lion Head").unwrap();
+        let outcome = run_cycle(&config, &effects, &mut source, true).unwrap();
+        assert!(outcome.messages.is_empty() && !outcome.sent.is_empty());
+        let sends = recorder.0.lock().unwrap();
+        assert_eq!(sends.len(), 2);
+        assert!(sends[1].join(" ").contains("Bail via Lion Head"));
+        assert!(State::load(&effects, Path::new(STATE_PATH)).pending_send.is_none());
+    }
+
+    #[test]
+    fn sources_are_exhausted_once_the_primary_has_failed_long_enough() {
+        let config = Config::default();
+        let error: Box<dyn Error> = "timed out".into();
+        assert!(!sources_exhausted(&config, 1, error.as_ref()));
+        assert!(sources_exhausted(&config, 2, error.as_ref()));
+        assert!(sources_exhausted(&config, 0, &RavenError::AllSourcesFailed));
+    }
+}
@@ -0,0 +1,117 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use headless_chrome::Tab;
+use serde::{Deserialize, Serialize};
+
+use crate::config::DeliveryConfig;
+
+// Characters of a part matched against the conversation, enough to tell parts apart without
+// depending on how the page wraps or truncates long messages
+const FINGERPRINT_LENGTH: usize = 24;
+const CHECKS: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    // Not on the conversation page yet
+    Pending,
+    Delivered,
+    // The conversation couldn't be loaded to tell
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartDelivery {
+    pub fingerprint: String,
+    pub status: DeliveryStatus,
+}
+
+// The last send's parts and what the conversation showed of them, kept in the state file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryCheck {
+    pub sent: DateTime<Utc>,
+    pub parts: Vec<PartDelivery>,
+    pub checks: u32,
+}
+
+impl DeliveryCheck {
+    pub fn new(parts: &[String], sent: DateTime<Utc>) -> DeliveryCheck {
+        DeliveryCheck {
+            sent,
+            parts: parts
+                .iter()
+                .map(|part| PartDelivery {
+                    fingerprint: fingerprint(part),
+                    status: DeliveryStatus::Pending,
+                })
+                .collect(),
+            checks: 0,
+        }
+    }
+
+    // The first check is `check_after_minutes` after the send, the second `recheck_after_minutes`
+    // after that, none once everything is there
+    pub fn due(&self, config: &DeliveryConfig, now: DateTime<Utc>) -> bool {
+        let wait = match self.checks {
+            0 => config.check_after_minutes,
+            _ => config.check_after_minutes + config.recheck_after_minutes,
+        };
+        self.checks < CHECKS
+            && self.missing() > 0
+            && now.signed_duration_since(self.sent) >= Duration::minutes(wait as i64)
+    }
+
+    // `conversation` is the page's text, None when it couldn't be loaded
+    pub fn record(&mut self, conversation: Option<&str>) {
+        self.checks += 1;
+        let conversation = conversation.map(normalize);
+        for part in self.parts.iter_mut().filter(|part| part.status != DeliveryStatus::Delivered) {
+            part.status = match &conversation {
+                Some(text) if text.contains(&part.fingerprint) => DeliveryStatus::Delivered,
+                Some(_) => DeliveryStatus::Pending,
+                None => DeliveryStatus::Unknown,
+            };
+        }
+    }
+
+    pub fn missing(&self) -> usize {
+        self.parts.iter().filter(|part| part.status != DeliveryStatus::Delivered).count()
+    }
+
+    // Checked twice and still not all there, with the conversation loaded to say so
+    pub fn undelivered(&self) -> bool {
+        self.checks >= CHECKS && self.parts.iter().any(|part| part.status == DeliveryStatus::Pending)
+    }
+
+    pub fn summary(&self) -> String {
+        let count = |status| self.parts.iter().filter(|part| part.status == status).count();
+        format!(
+            "{} of {} parts delivered, {} pending, {} unknown",
+            count(DeliveryStatus::Delivered),
+            self.parts.len(),
+            count(DeliveryStatus::Pending),
+            count(DeliveryStatus::Unknown)
+        )
+    }
+}
+
+// The conversation's text, the reply url shows the messages sent to the inReach
+pub fn conversation_text(tab: &Arc<Tab>, reply_url: &str) -> Result<String, Box<dyn Error>> {
+    tab.navigate_to(reply_url)?.wait_until_navigated()?;
+    Ok(tab.wait_for_element("body")?.get_inner_text()?)
+}
+
+// The start of the part, lowercase with runs of anything but letters and digits as one space
+fn fingerprint(part: &str) -> String {
+    normalize(part).chars().take(FINGERPRINT_LENGTH).collect::<String>().trim().to_string()
+}
+
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
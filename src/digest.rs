@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Utc, Weekday};
+
+use crate::archive::ArchivedRevision;
+use crate::error::RavenError;
+use crate::locale::Language;
+
+// How many of the largest revision swings to list
+const TOP_SWINGS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DigestFormat {
+    Text,
+    Markdown,
+}
+
+impl FromStr for DigestFormat {
+    type Err = RavenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(DigestFormat::Text),
+            "markdown" | "md" => Ok(DigestFormat::Markdown),
+            _ => Err(RavenError::InvalidArgument(format!("unknown format {}, expected text or markdown", s))),
+        }
+    }
+}
+
+// "7d", "12h" or "2w"
+pub fn parse_since(since: &str) -> Result<Duration, RavenError> {
+    let invalid = || RavenError::InvalidArgument(format!("can't parse {}, expected e.g. 7d, 12h or 2w", since));
+    let since = since.trim();
+    let unit = since.chars().last().ok_or_else(invalid)?;
+    let amount = since[..since.len() - unit.len_utf8()].parse::<i64>().map_err(|_| invalid())?;
+
+    match unit {
+        'h' => Ok(Duration::hours(amount)),
+        'd' => Ok(Duration::days(amount)),
+        'w' => Ok(Duration::weeks(amount)),
+        _ => Err(invalid()),
+    }
+}
+
+// A forecast period pinned to a calendar date, so "Saturday" in Thursday's and Friday's revisions line up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Period {
+    pub date: NaiveDate,
+    pub night: bool,
+}
+
+impl Period {
+    // Labels are relative to when the revision was archived: "Today", "Tonight", "Saturday", "Saturday Night"
+    pub fn resolve(label: &str, archived: DateTime<Utc>) -> Option<Period> {
+        let today = archived.with_timezone(&Local).date_naive();
+        let label = label.to_lowercase();
+        let night = label.contains("night") || label == "tonight";
+
+        if label.starts_with("today") || label == "tonight" {
+            return Some(Period { date: today, night });
+        }
+
+        let weekday = label.split_whitespace().next()?.parse::<Weekday>().ok()?;
+        let ahead = (7 + weekday.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+        Some(Period {
+            date: today + Duration::days(ahead as i64),
+            night,
+        })
+    }
+
+    fn label(&self) -> String {
+        let date = self.date.format("%a %b %-d");
+        if self.night {
+            format!("{} night", date)
+        } else {
+            date.to_string()
+        }
+    }
+}
+
+struct Sample {
+    archived: DateTime<Utc>,
+    high_f: Option<i32>,
+    low_f: Option<i32>,
+    wind: Option<String>,
+    max_mph: Option<u32>,
+}
+
+// Offline summary of how the forecast evolved since `since`: revisions per day, every revision's
+// numbers for each forecast period and the biggest swings between revisions
+pub fn render_digest(revisions: &[ArchivedRevision], since: DateTime<Utc>, format: DigestFormat) -> String {
+    let mut out = String::new();
+    let since_date = since.with_timezone(&Local).date_naive();
+    let today = Local::now().date_naive();
+    out.push_str(&heading(
+        format,
+        1,
+        &format!("Forecast digest {} to {}", since_date, today),
+    ));
+
+    if revisions.is_empty() {
+        out.push_str("No archived revisions in this range.\n");
+        return out;
+    }
+
+    // Days without any revision show up as 0, e.g. when raven wasn't running
+    let mut per_day: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    let mut date = since_date;
+    while date <= today {
+        per_day.insert(date, 0);
+        date += Duration::days(1);
+    }
+    for revision in revisions {
+        *per_day.entry(revision.archived.with_timezone(&Local).date_naive()).or_insert(0) += 1;
+    }
+
+    out.push_str(&heading(format, 2, "Revisions per day"));
+    let rows = per_day
+        .iter()
+        .map(|(date, count)| vec![date.format("%a %b %-d").to_string(), count.to_string()])
+        .collect::<Vec<_>>();
+    out.push_str(&table(format, &["Day", "Revisions"], &rows));
+
+    let mut periods: BTreeMap<Period, Vec<Sample>> = BTreeMap::new();
+    for revision in revisions {
+        for day in &revision.forecast.days {
+            if let Some(period) = Period::resolve(&day.label, revision.archived) {
+                periods.entry(period).or_default().push(Sample {
+                    archived: revision.archived,
+                    high_f: day.high_f,
+                    low_f: day.low_f,
+                    wind: day.wind.as_ref().map(|wind| wind.compact(Language::En)),
+                    max_mph: day.wind.as_ref().and_then(|wind| wind.max_mph.or(wind.min_mph)),
+                });
+            }
+        }
+    }
+
+    for (period, samples) in &periods {
+        out.push_str(&heading(format, 2, &period.label()));
+        let rows = samples
+            .iter()
+            .map(|sample| {
+                vec![
+                    sample.archived.with_timezone(&Local).format("%a %H:%M").to_string(),
+                    temperature(sample.high_f),
+                    temperature(sample.low_f),
+                    sample.wind.clone().unwrap_or_else(|| "-".to_string()),
+                ]
+            })
+            .collect::<Vec<_>>();
+        out.push_str(&table(format, &["Revision", "High", "Low", "Wind"], &rows));
+    }
+
+    let swings = biggest_swings(&periods);
+    out.push_str(&heading(format, 2, "Biggest revision swings"));
+    if swings.is_empty() {
+        out.push_str("No period was revised.\n");
+    } else {
+        let rows = swings
+            .iter()
+            .map(|(period, field, min, max, unit)| {
+                vec![
+                    period.label(),
+                    field.to_string(),
+                    format!("{}{} to {}{}", min, unit, max, unit),
+                    format!("{}{}", max - min, unit),
+                ]
+            })
+            .collect::<Vec<_>>();
+        out.push_str(&table(format, &["Period", "Field", "Range", "Swing"], &rows));
+    }
+
+    out
+}
+
+type Swing = (Period, &'static str, i64, i64, &'static str);
+
+fn biggest_swings(periods: &BTreeMap<Period, Vec<Sample>>) -> Vec<Swing> {
+    let mut swings = Vec::new();
+    for (period, samples) in periods {
+        let fields: [(&str, &str, Vec<i64>); 3] = [
+            ("high", "F", samples.iter().filter_map(|s| s.high_f).map(i64::from).collect()),
+            ("low", "F", samples.iter().filter_map(|s| s.low_f).map(i64::from).collect()),
+            ("wind", "mph", samples.iter().filter_map(|s| s.max_mph).map(i64::from).collect()),
+        ];
+        for (field, unit, values) in fields.iter() {
+            if let (Some(min), Some(max)) = (values.iter().min(), values.iter().max()) {
+                if max > min {
+                    swings.push((*period, *field, *min, *max, *unit));
+                }
+            }
+        }
+    }
+
+    swings.sort_by_key(|(_, _, min, max, _)| -(max - min));
+    swings.truncate(TOP_SWINGS);
+    swings
+}
+
+fn temperature(value: Option<i32>) -> String {
+    value.map(|t| format!("{}F", t)).unwrap_or_else(|| "-".to_string())
+}
+
+fn heading(format: DigestFormat, level: usize, title: &str) -> String {
+    match format {
+        DigestFormat::Markdown => format!("\n{} {}\n\n", "#".repeat(level), title),
+        DigestFormat::Text => {
+            let underline = if level == 1 { '=' } else { '-' };
+            format!("\n{}\n{}\n", title, underline.to_string().repeat(title.chars().count()))
+        }
+    }
+}
+
+fn table(format: DigestFormat, headers: &[&str], rows: &[Vec<String>]) -> String {
+    match format {
+        DigestFormat::Markdown => {
+            let mut out = format!("| {} |\n", headers.join(" | "));
+            out.push_str(&format!("|{}\n", "---|".repeat(headers.len())));
+            for row in rows {
+                out.push_str(&format!("| {} |\n", row.join(" | ")));
+            }
+            out
+        }
+        DigestFormat::Text => {
+            let widths = headers
+                .iter()
+                .enumerate()
+                .map(|(i, header)| {
+                    rows.iter()
+                        .map(|row| row[i].chars().count())
+                        .chain(std::iter::once(header.chars().count()))
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect::<Vec<_>>();
+
+            let line = |cells: Vec<&str>| {
+                cells
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_string()
+                    + "\n"
+            };
+
+            let mut out = line(headers.to_vec());
+            for row in rows {
+                out.push_str(&line(row.iter().map(String::as_str).collect()));
+            }
+            out
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use headless_chrome::Tab;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{DriftConfig, Source};
+use crate::fetch::FetchedForecast;
+
+// A cheap outline of the page's structure, read off the page the forecast was just scraped from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    // Elements matching selectors.days, "#SummitOutlook > div" by default
+    pub children: usize,
+    // The configured expected selectors that matched nothing
+    pub missing: Vec<String>,
+    // In characters, over the day blocks as extracted
+    pub mean_block_length: usize,
+}
+
+// A rolling window of fingerprints, kept in the state file. A lasting change becomes the new
+// baseline once it's most of the window
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StructureBaseline {
+    // Newest last
+    pub samples: Vec<Fingerprint>,
+    // When the structure first drifted from the baseline, so it's only notified once
+    pub drifting_since: Option<DateTime<Utc>>,
+}
+
+impl StructureBaseline {
+    pub fn push(&mut self, fingerprint: Fingerprint, window: usize) {
+        self.samples.push(fingerprint);
+        let excess = self.samples.len().saturating_sub(window);
+        self.samples.drain(..excess);
+    }
+}
+
+// Only queries the already loaded page, never navigates
+pub fn fingerprint(tab: &Arc<Tab>, source: &Source, expect: &[String], fetched: &FetchedForecast) -> Fingerprint {
+    let count = |selector: &str| tab.find_elements(selector).map(|elements| elements.len()).unwrap_or(0);
+    let lengths = fetched.day_blocks.iter().map(|block| block.chars().count()).collect::<Vec<_>>();
+
+    Fingerprint {
+        children: count(&source.selectors.days),
+        missing: expect.iter().filter(|selector| count(selector) == 0).cloned().collect(),
+        mean_block_length: lengths.iter().sum::<usize>() / lengths.len().max(1),
+    }
+}
+
+// How `current` differs from the baseline beyond the configured tolerances, e.g. "5 day blocks,
+// usually 3". Nothing until the baseline has `min_samples`
+pub fn deviations(baseline: &[Fingerprint], current: &Fingerprint, config: &DriftConfig) -> Vec<String> {
+    if baseline.is_empty() || baseline.len() < config.min_samples {
+        return Vec::new();
+    }
+    let mut deviations = Vec::new();
+
+    let usual = most_common(baseline.iter().map(|sample| sample.children));
+    if current.children.abs_diff(usual) > config.children_tolerance {
+        deviations.push(format!("{} day blocks, usually {}", current.children, usual));
+    }
+
+    // Missing now, but there in most of the baseline
+    for selector in &current.missing {
+        let seen = baseline.iter().filter(|sample| !sample.missing.contains(selector)).count();
+        if seen * 2 > baseline.len() {
+            deviations.push(format!("no {} on the page", selector));
+        }
+    }
+
+    let usual = baseline.iter().map(|sample| sample.mean_block_length).sum::<usize>() / baseline.len();
+    let change = current.mean_block_length.abs_diff(usual) as f64 / usual.max(1) as f64;
+    if change > config.length_tolerance {
+        deviations.push(format!(
+            "day blocks average {} characters, usually {}",
+            current.mean_block_length, usual
+        ));
+    }
+
+    deviations
+}
+
+// The smaller on a tie
+fn most_common(values: impl Iterator<Item = usize>) -> usize {
+    let mut counts = BTreeMap::<usize, usize>::new();
+    for value in values {
+        *counts.entry(value).or_default() += 1;
+    }
+    counts
+        .iter()
+        .fold((0, 0), |best, (value, count)| if *count > best.1 { (*value, *count) } else { best })
+        .0
+}
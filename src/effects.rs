@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use headless_chrome::Tab;
+use log::info;
+
+use crate::clock::{Clock, SystemClock};
+use crate::config::Config;
+use crate::inreach::{send_message_to_inreach, InReachSender};
+use crate::notify::{configured_notifiers, Notifier};
+use crate::retry::{retry_with_clock, RetryPolicy};
+use crate::storage::{FileStorage, Storage};
+
+// Every side effect of a cycle goes through here, so a dry run exercises the real code paths
+// and only the final write or send is swapped for a log line. Reads go through the same
+// storage, so a cycle can run against memory instead of the working directory. The time comes from
+// the same place, so a cycle can run at any time of day
+#[derive(Clone)]
+pub struct Effects {
+    dry_run: bool,
+    storage: Arc<dyn Storage>,
+    clock: Arc<dyn Clock>,
+    // Applied to writes, appends aren't retried since a partial one would be appended twice
+    persist_retry: RetryPolicy,
+    // Debug captures saved this cycle, for the failure notice to point at
+    captures: Arc<Mutex<Vec<PathBuf>>>,
+    // In place of the configured channels when set
+    notifiers: Option<Vec<Arc<dyn Notifier>>>,
+    // In place of the configured inReach transport when set
+    inreach: Option<Arc<dyn InReachSender + Send + Sync>>,
+    // Messages queued per notifier and not yet recorded in the state, counted against the daily caps
+    reserved: Arc<Mutex<BTreeMap<String, u32>>>,
+}
+
+impl Effects {
+    pub fn new(dry_run: bool) -> Effects {
+        Effects::with_storage(dry_run, Arc::new(FileStorage))
+    }
+
+    pub fn with_storage(dry_run: bool, storage: Arc<dyn Storage>) -> Effects {
+        Effects {
+            dry_run,
+            storage,
+            clock: Arc::new(SystemClock),
+            persist_retry: RetryPolicy::none(),
+            captures: Arc::new(Mutex::new(Vec::new())),
+            notifiers: None,
+            inreach: None,
+            reserved: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Effects {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_persist_retry(mut self, policy: RetryPolicy) -> Effects {
+        self.persist_retry = policy;
+        self
+    }
+
+    pub fn with_notifiers(mut self, notifiers: Vec<Arc<dyn Notifier>>) -> Effects {
+        self.notifiers = Some(notifiers);
+        self
+    }
+
+    pub fn with_inreach(mut self, sender: Arc<dyn InReachSender + Send + Sync>) -> Effects {
+        self.inreach = Some(sender);
+        self
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    pub fn write(&self, path: &Path, contents: &str) -> Result<(), Box<dyn Error>> {
+        if self.dry_run {
+            info!("Dry run: would write {} ({} chars)", path.display(), contents.chars().count());
+            return Ok(());
+        }
+
+        retry_with_clock(&self.persist_retry, self.clock(), &format!("Writing {}", path.display()), || {
+            Ok(self.storage.write(path, contents)?)
+        })
+    }
+
+    pub fn write_bytes(&self, path: &Path, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+        if self.dry_run {
+            info!("Dry run: would write {} ({} bytes)", path.display(), contents.len());
+            return Ok(());
+        }
+
+        retry_with_clock(&self.persist_retry, self.clock(), &format!("Writing {}", path.display()), || {
+            Ok(self.storage.write_bytes(path, contents)?)
+        })
+    }
+
+    pub fn write_atomic(&self, path: &Path, contents: &str) -> Result<(), Box<dyn Error>> {
+        if self.dry_run {
+            info!("Dry run: would write {} ({} chars)", path.display(), contents.chars().count());
+            return Ok(());
+        }
+
+        retry_with_clock(&self.persist_retry, self.clock(), &format!("Writing {}", path.display()), || {
+            Ok(self.storage.write_atomic(path, contents)?)
+        })
+    }
+
+    // The offset the bytes were appended at, 0 in a dry run
+    pub fn append(&self, path: &Path, bytes: &[u8]) -> Result<u64, Box<dyn Error>> {
+        if self.dry_run {
+            info!("Dry run: would append {} bytes to {}", bytes.len(), path.display());
+            return Ok(0);
+        }
+
+        Ok(self.storage.append(path, bytes)?)
+    }
+
+    pub fn remove(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if self.dry_run {
+            info!("Dry run: would remove {}", path.display());
+            return Ok(());
+        }
+
+        self.storage.remove(path)?;
+        Ok(())
+    }
+
+    pub fn remove_dir_all(&self, dir: &Path) -> Result<(), Box<dyn Error>> {
+        if self.dry_run {
+            info!("Dry run: would remove {}", dir.display());
+            return Ok(());
+        }
+
+        self.storage.remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    pub fn create_dir_all(&self, dir: &Path) -> Result<(), Box<dyn Error>> {
+        if self.dry_run {
+            return Ok(());
+        }
+
+        self.storage.create_dir_all(dir)?;
+        Ok(())
+    }
+
+    pub fn read(&self, path: &Path) -> Option<String> {
+        self.storage.read(path)
+    }
+
+    pub fn read_bytes(&self, path: &Path, offset: u64, length: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(self.storage.read_bytes(path, offset, length)?)
+    }
+
+    pub fn size(&self, path: &Path) -> Option<u64> {
+        self.storage.size(path)
+    }
+
+    pub fn exists(&self, path: &Path) -> bool {
+        self.storage.exists(path)
+    }
+
+    pub fn list(&self, dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        Ok(self.storage.list(dir)?)
+    }
+
+    pub fn record_capture(&self, dir: &Path) {
+        self.captures.lock().unwrap().push(dir.to_path_buf());
+    }
+
+    // The captures recorded since the last call, oldest first
+    pub fn take_captures(&self) -> Vec<PathBuf> {
+        std::mem::take(&mut *self.captures.lock().unwrap())
+    }
+
+    pub fn send_inreach(
+        &self,
+        tab: Option<&Arc<Tab>>,
+        config: &Config,
+        messages: &[String],
+        on_sent: &mut dyn FnMut(usize),
+    ) -> Result<(), Box<dyn Error>> {
+        if self.dry_run {
+            info!("Dry run: would send {} inReach messages", messages.len());
+            for (i, message) in messages.iter().enumerate() {
+                info!("Dry run:   {}", message);
+                on_sent(i);
+            }
+            return Ok(());
+        }
+
+        match &self.inreach {
+            Some(sender) => sender.send(messages, on_sent),
+            None => send_message_to_inreach(tab, config, self.clock(), messages, on_sent),
+        }
+    }
+
+    // Every channel other than the inReach events can be routed to
+    pub fn notifiers(&self, config: &Config) -> Vec<Arc<dyn Notifier>> {
+        match &self.notifiers {
+            Some(notifiers) => notifiers.clone(),
+            None => configured_notifiers(config, self.clock.clone()),
+        }
+    }
+
+    pub fn reserved_messages(&self, name: &str) -> u32 {
+        self.reserved.lock().unwrap().get(name).copied().unwrap_or(0)
+    }
+
+    pub fn reserve_messages(&self, name: &str, count: u32) {
+        *self.reserved.lock().unwrap().entry(name.to_string()).or_insert(0) += count;
+    }
+
+    pub fn release_messages(&self, name: &str, count: u32) {
+        let mut reserved = self.reserved.lock().unwrap();
+        if let Some(queued) = reserved.get_mut(name) {
+            *queued = queued.saturating_sub(count);
+            if *queued == 0 {
+                reserved.remove(name);
+            }
+        }
+    }
+
+    pub fn notify(&self, notifier: &dyn Notifier, messages: &[String]) -> Result<(), Box<dyn Error>> {
+        if self.dry_run {
+            info!("Dry run: would send {} messages to {}", messages.len(), notifier.name());
+            return Ok(());
+        }
+
+        notifier.notify(messages)
+    }
+}
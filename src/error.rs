@@ -0,0 +1,104 @@
+use std::error::Error;
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+
+#[derive(Debug)]
+pub enum RavenError {
+    ForecastTooShort { length: usize, min_length: usize },
+    ForecastTooLong { size: usize, max_bytes: usize },
+    MissingDayNames,
+    StaleForecast { source: String, captured: DateTime<Utc> },
+    UnknownCaptureTime { source: String },
+    // Something (usually a banner or popup) sits over the element, even after trying to dismiss it
+    ElementObscured { selector: String, obscured_by: String },
+    // The page still shows an older forecast than one already seen, even after a hard reload
+    CachedPage { source: String, rendered: String, known: String },
+    // The final check before spending satellite messages failed
+    SendRefused(SendRefusal),
+    AllSourcesFailed,
+    InvalidConfig(String),
+    InvalidArgument(String),
+    MissingReplyUrl { env: String },
+    // The MapShare reply link only works for a while after the inReach last messaged us
+    ReplyUrlExpired,
+    RateLimited { retry_after_seconds: Option<u64> },
+    InReachHttp { status: u16 },
+    MissingCredentials { env: String },
+    // The Garmin account needs someone to log in, e.g. to type a verification code
+    ManualLoginRequired { reason: String },
+    Notifier { channel: String, message: String },
+}
+
+impl fmt::Display for RavenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RavenError::ForecastTooShort { length, min_length } => write!(
+                f,
+                "forecast is {} characters, expected at least {}",
+                length, min_length
+            ),
+            RavenError::ForecastTooLong { size, max_bytes } => {
+                write!(f, "forecast is {} bytes, expected at most {}", size, max_bytes)
+            }
+            RavenError::MissingDayNames => write!(f, "no day names (Today, Tonight, ...) found in the day forecasts"),
+            RavenError::StaleForecast { source, captured } => write!(
+                f,
+                "forecast from {} was captured at {}, too old to use",
+                source,
+                captured.format("%Y-%m-%d %H:%M UTC")
+            ),
+            RavenError::UnknownCaptureTime { source } => {
+                write!(f, "could not determine when {} captured the forecast", source)
+            }
+            RavenError::ElementObscured { selector, obscured_by } => {
+                write!(f, "{} is covered by {}", selector, obscured_by)
+            }
+            RavenError::CachedPage { source, rendered, known } => write!(
+                f,
+                "{} still shows the forecast issued {} after a hard reload, {} was already seen",
+                source, rendered, known
+            ),
+            RavenError::SendRefused(refusal) => write!(f, "not sending to the inReach, {}", refusal),
+            RavenError::AllSourcesFailed => write!(f, "primary and all fallback sources failed"),
+            RavenError::InvalidConfig(message) => write!(f, "invalid configuration: {}", message),
+            RavenError::InvalidArgument(message) => write!(f, "invalid argument: {}", message),
+            RavenError::MissingReplyUrl { env } => write!(f, "Could not fetch value for envvar {}", env),
+            RavenError::ReplyUrlExpired => write!(f, "the inReach reply URL has expired or is invalid"),
+            RavenError::RateLimited { retry_after_seconds } => match retry_after_seconds {
+                Some(seconds) => write!(f, "rate limited by Garmin, retry after {} seconds", seconds),
+                None => write!(f, "rate limited by Garmin"),
+            },
+            RavenError::InReachHttp { status } => write!(f, "Garmin responded with HTTP {}", status),
+            RavenError::MissingCredentials { env } => write!(f, "{} is not set", env),
+            RavenError::ManualLoginRequired { reason } => {
+                write!(f, "manual login required, {}: run raven garmin-login", reason)
+            }
+            RavenError::Notifier { channel, message } => write!(f, "{}: {}", channel, message),
+        }
+    }
+}
+
+impl Error for RavenError {}
+
+#[derive(Debug)]
+pub enum SendRefusal {
+    Empty,
+    // Most of the text is these stop phrases
+    MostlyStopPhrases { phrases: Vec<String> },
+    NoDigits,
+    SameAsLastSent,
+}
+
+impl fmt::Display for SendRefusal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendRefusal::Empty => write!(f, "the content is empty once sanitized"),
+            SendRefusal::MostlyStopPhrases { phrases } => {
+                write!(f, "the content is mostly page furniture ({})", phrases.join(", "))
+            }
+            SendRefusal::NoDigits => write!(f, "the content has no numbers in it, probably a parse failure"),
+            SendRefusal::SameAsLastSent => write!(f, "the content is the forecast already sent"),
+        }
+    }
+}
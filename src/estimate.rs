@@ -0,0 +1,182 @@
+use std::path::Path;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Config, PollSchedule, PollingConfig};
+use crate::cycle::STATE_PATH;
+use crate::effects::Effects;
+use crate::routing::{in_window, parse_window};
+use crate::state::State;
+
+// Weight of each new observation once a slot has warmed up, lower follows a changed schedule more slowly
+const ALPHA: f64 = 0.2;
+// Updates seen within this of a slot's time are the same update, e.g. the morning issue
+const SLOT_MINUTES: f64 = 120.0;
+// Per day of the week, so a day's slots can't grow without bound
+const MAX_SLOTS: usize = 4;
+// Fewer than this and a slot isn't trusted, the configured windows are used instead
+pub const MIN_OBSERVATIONS: u32 = 5;
+
+// When forecast updates have been seen, per local day of the week and Monday first. Each day keeps a
+// few slots, one per update it usually gets, smoothed as they're seen. Updates don't come around
+// midnight, so a slot never wraps into the next day
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateTimes {
+    pub days: [Vec<UpdateSlot>; 7],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateSlot {
+    // Smoothed minutes after local midnight
+    pub minute: f64,
+    // Smoothed distance of the observations from `minute`, in minutes
+    pub deviation: f64,
+    pub observations: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpectedUpdate {
+    pub at: NaiveDateTime,
+    pub deviation_minutes: i64,
+    pub observations: u32,
+}
+
+impl UpdateSlot {
+    fn time_on(&self, date: NaiveDate) -> Option<NaiveDateTime> {
+        let seconds = (self.minute * 60.0).round() as u32;
+        NaiveTime::from_num_seconds_from_midnight_opt(seconds, 0).map(|time| date.and_time(time))
+    }
+
+    fn trusted(&self) -> bool {
+        self.observations >= MIN_OBSERVATIONS
+    }
+}
+
+impl UpdateTimes {
+    // An update first seen at `seen`, local time. The first few observations of a slot are averaged
+    // plainly, after that each moves it by ALPHA
+    pub fn record(&mut self, seen: NaiveDateTime) {
+        let slots = &mut self.days[seen.weekday().num_days_from_monday() as usize];
+        let minute = minute_of_day(seen);
+
+        let nearest = slots
+            .iter_mut()
+            .filter(|slot| (slot.minute - minute).abs() <= SLOT_MINUTES)
+            .min_by(|a, b| (a.minute - minute).abs().total_cmp(&(b.minute - minute).abs()));
+        match nearest {
+            Some(slot) => {
+                slot.observations = slot.observations.saturating_add(1);
+                let alpha = ALPHA.max(1.0 / slot.observations as f64);
+                let error = minute - slot.minute;
+                slot.minute += alpha * error;
+                slot.deviation += alpha * (error.abs() - slot.deviation);
+            }
+            None => {
+                // A full day gives up its least seen slot, a one-off late update shouldn't stay forever
+                if slots.len() >= MAX_SLOTS {
+                    if let Some(i) = (0..slots.len()).min_by_key(|i| slots[*i].observations) {
+                        slots.remove(i);
+                    }
+                }
+                slots.push(UpdateSlot {
+                    minute,
+                    deviation: 0.0,
+                    observations: 1,
+                });
+                slots.sort_by(|a, b| a.minute.total_cmp(&b.minute));
+            }
+        }
+    }
+
+    // The next trusted slot after `now`, looking a week ahead. None when no day has one yet
+    pub fn expected(&self, now: NaiveDateTime) -> Option<ExpectedUpdate> {
+        (0..=7)
+            .map(|days| now.date() + Duration::days(days))
+            .flat_map(|date| {
+                self.days[date.weekday().num_days_from_monday() as usize]
+                    .iter()
+                    .filter(|slot| slot.trusted())
+                    .filter_map(move |slot| Some((slot.time_on(date)?, slot)))
+            })
+            .find(|(at, _)| *at > now)
+            .map(|(at, slot)| ExpectedUpdate {
+                at,
+                deviation_minutes: slot.deviation.round() as i64,
+                observations: slot.observations,
+            })
+    }
+
+    // Every observation so far, for when nothing is trusted yet
+    pub fn observations(&self) -> u32 {
+        self.days.iter().flatten().map(|slot| slot.observations).sum()
+    }
+
+    // Whether `now` is within `margin_minutes`, plus twice the slot's deviation, of one of today's
+    // trusted slots. None when today has none, the configured windows decide then
+    fn near_expected(&self, now: NaiveDateTime, margin_minutes: u64) -> Option<bool> {
+        let trusted = self.days[now.weekday().num_days_from_monday() as usize]
+            .iter()
+            .filter(|slot| slot.trusted())
+            .collect::<Vec<_>>();
+        if trusted.is_empty() {
+            return None;
+        }
+        let minute = minute_of_day(now);
+        Some(
+            trusted
+                .iter()
+                .any(|slot| (slot.minute - minute).abs() <= margin_minutes as f64 + 2.0 * slot.deviation),
+        )
+    }
+}
+
+// Whether to poll at polling.fast_interval_seconds at `now`, local time. With schedule = "auto" the
+// learned update times decide, falling back to the configured windows on days without enough of them
+pub fn fast_polling(polling: &PollingConfig, times: &UpdateTimes, now: NaiveDateTime) -> bool {
+    let learned = match polling.schedule {
+        PollSchedule::Auto => times.near_expected(now, polling.auto_margin_minutes),
+        PollSchedule::Fixed => None,
+    };
+    learned.unwrap_or_else(|| {
+        polling
+            .fast_windows
+            .iter()
+            .filter_map(|window| parse_window(window).ok())
+            .any(|(start, end)| in_window(now.time(), start, end))
+    })
+}
+
+// The `raven status` line, e.g. "next update expected ~Tue 11:42 AM (±9 min), based on 23 observations"
+pub fn render_expected(config: &Config, effects: &Effects) -> String {
+    let times = State::load(effects, Path::new(STATE_PATH)).update_times;
+    let now = effects.clock().local_now().naive_local();
+    let polling = match config.polling.schedule {
+        PollSchedule::Auto if times.near_expected(now, config.polling.auto_margin_minutes).is_some() => {
+            "learned update times"
+        }
+        _ if config.polling.fast_windows.is_empty() => "a fixed interval",
+        _ => "the configured fast windows",
+    };
+
+    match times.expected(now) {
+        Some(expected) => format!(
+            "next update expected ~{} (±{} min), based on {} observations; polling on {} today\n",
+            expected.at.format("%a %-I:%M %p"),
+            expected.deviation_minutes,
+            expected.observations,
+            polling
+        ),
+        None => format!(
+            "next update expected: not enough observations yet ({} seen, {} needed at one time of day); \
+             polling on {} today\n",
+            times.observations(),
+            MIN_OBSERVATIONS,
+            polling
+        ),
+    }
+}
+
+fn minute_of_day(time: NaiveDateTime) -> f64 {
+    (time.hour() * 60 + time.minute()) as f64 + time.second() as f64 / 60.0
+}
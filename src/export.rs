@@ -0,0 +1,166 @@
+use std::str::FromStr;
+
+use crate::archive::ArchivedRevision;
+use crate::digest::Period;
+use crate::error::RavenError;
+use crate::forecast::{DayForecast, Forecast};
+use crate::verify::{cell, csv_quote};
+
+// Every column, in the default order
+pub const COLUMNS: [&str; 12] = [
+    "archived",
+    "issued",
+    "day",
+    "label",
+    "high_f",
+    "low_f",
+    "wind_min_mph",
+    "wind_max_mph",
+    "gust_mph",
+    "wind_chill_f",
+    "precip",
+    "in_clouds",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+}
+
+impl FromStr for ExportFormat {
+    type Err = RavenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            _ => Err(RavenError::InvalidArgument(format!("unknown format {}, expected csv", s))),
+        }
+    }
+}
+
+// "archived,day,high_f" picks those columns in that order, None is all of them
+pub fn parse_columns(columns: Option<&str>) -> Result<Vec<&'static str>, RavenError> {
+    let columns = match columns {
+        Some(columns) => columns,
+        None => return Ok(COLUMNS.to_vec()),
+    };
+    let selected = columns
+        .split(',')
+        .map(str::trim)
+        .filter(|column| !column.is_empty())
+        .map(|column| {
+            COLUMNS.iter().find(|known| **known == column).copied().ok_or_else(|| {
+                RavenError::InvalidArgument(format!("unknown column {}, expected some of {}", column, COLUMNS.join(",")))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if selected.is_empty() {
+        return Err(RavenError::InvalidArgument("--columns selects no columns".to_string()));
+    }
+    Ok(selected)
+}
+
+// One row per day of every revision, oldest revision first. The day blocks are parsed again, so older
+// revisions get whatever the parser has learned since. Anything that doesn't parse is an empty cell
+pub fn render_export(revisions: &[ArchivedRevision], columns: &[&str], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Csv => render_csv(revisions, columns),
+    }
+}
+
+fn render_csv(revisions: &[ArchivedRevision], columns: &[&str]) -> String {
+    let mut out = columns.join(",");
+    out.push('\n');
+    for revision in revisions {
+        let forecast = Forecast::parse(&revision.full, &revision.day_blocks);
+        for day in &forecast.days {
+            let fields = columns
+                .iter()
+                .map(|column| field(revision, &forecast, day, column))
+                .collect::<Vec<_>>();
+            out.push_str(&fields.join(","));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+// Free text is always quoted, issue times and labels can have commas in them
+fn field(revision: &ArchivedRevision, forecast: &Forecast, day: &DayForecast, column: &str) -> String {
+    let wind = day.wind.as_ref();
+    match column {
+        "archived" => revision.archived.to_rfc3339(),
+        "issued" => csv_quote(forecast.issued.as_deref().unwrap_or_default()),
+        "day" => cell(Period::resolve(&day.label, revision.archived).map(|period| period.date)),
+        "label" => csv_quote(&day.label),
+        "high_f" => cell(day.high_f),
+        "low_f" => cell(day.low_f),
+        "wind_min_mph" => cell(wind.and_then(|wind| wind.min_mph)),
+        "wind_max_mph" => cell(wind.and_then(|wind| wind.max_mph)),
+        "gust_mph" => cell(day.gust_mph()),
+        "wind_chill_f" => cell(day.wind_chill_f()),
+        "precip" => csv_quote(&day.precipitation().join(", ")),
+        "in_clouds" => day.in_clouds().to_string(),
+        // parse_columns only lets known columns through
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch::FetchedForecast;
+
+    fn revision(day_blocks: &[&str]) -> ArchivedRevision {
+        let day_blocks = day_blocks.iter().map(|block| block.to_string()).collect::<Vec<_>>();
+        let fetched = FetchedForecast {
+            full: format!("Issued: 5:07 AM Friday, January 12, 2024\n\n{}", day_blocks.join("\n\n")),
+            day_blocks,
+            source: "Higher Summits Forecast".to_string(),
+            captured: "2024-01-12T10:30:00Z".parse().unwrap(),
+            fallback: false,
+        };
+        let forecast = Forecast::parse(&fetched.full, &fetched.day_blocks);
+        ArchivedRevision::new(&fetched, &forecast, fetched.captured)
+    }
+
+    fn csv(revisions: &[ArchivedRevision], columns: Option<&str>) -> String {
+        render_export(revisions, &parse_columns(columns).unwrap(), ExportFormat::Csv)
+    }
+
+    #[test]
+    fn free_text_is_quoted() {
+        let revisions = [revision(&["Saturday \"the storm\":\nSnow. Highs around 5F. NW winds 50 to 70 mph."])];
+        assert_eq!(
+            csv(&revisions, Some("issued,label,high_f")),
+            "issued,label,high_f\n\"5:07 AM Friday, January 12, 2024\",\"Saturday \"\"the storm\"\"\",5\n"
+        );
+    }
+
+    #[test]
+    fn a_day_that_does_not_parse_has_empty_cells() {
+        let revisions = [revision(&["Today:\nIn the clouds with snow."])];
+        assert_eq!(
+            csv(&revisions, Some("label,high_f,low_f,wind_max_mph,wind_chill_f")),
+            "label,high_f,low_f,wind_max_mph,wind_chill_f\n\"Today\",,,,\n"
+        );
+    }
+
+    #[test]
+    fn columns_come_out_in_the_order_asked_for() {
+        let revisions = [revision(&[
+            "Today:\nHighs around 5F. NW winds 50 to 70 mph.",
+            "Tonight:\nLows around -10F. NW winds 60 to 80 mph.",
+        ])];
+        assert_eq!(
+            csv(&revisions, Some("wind_max_mph, day,archived")),
+            concat!(
+                "wind_max_mph,day,archived\n",
+                "70,2024-01-12,2024-01-12T10:30:00+00:00\n",
+                "80,2024-01-12,2024-01-12T10:30:00+00:00\n",
+            )
+        );
+        assert!(parse_columns(Some("day,dewpoint")).is_err());
+        assert!(parse_columns(Some(" , ")).is_err());
+    }
+}
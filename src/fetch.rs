@@ -0,0 +1,537 @@
+use std::env;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use headless_chrome::protocol::cdp::Network::SetCacheDisabled;
+use headless_chrome::{Browser, LaunchOptions, Tab};
+use log::{debug, info, warn};
+
+use crate::abbreviation::{clean_block, first_days};
+use crate::capture::capture_debug;
+use crate::config::{AbbreviationConfig, CaptureTime, Config, InterceptConfig, Source};
+use crate::effects::Effects;
+use crate::error::RavenError;
+use crate::forecast::{DayForecast, Forecast};
+use crate::intercept::{extract_forecast, Interceptor};
+use crate::output::{read_structured_forecast, OutputFiles};
+use crate::retry::{retry_with_clock, Operation};
+use crate::validation::{check_size, validate_forecast};
+use crate::windows::browser_executable;
+
+pub fn launch_browser(config: &Config) -> Result<Browser, Box<dyn Error>> {
+    launch_browser_with(config, None, true)
+}
+
+// With a `profile` Chrome keeps its cookies, and so any logins, in that directory between launches
+pub fn launch_browser_with(config: &Config, profile: Option<&Path>, headless: bool) -> Result<Browser, Box<dyn Error>> {
+    let user_agent = format!("--user-agent={}", config.user_agent);
+    let user_data_dir = match profile {
+        Some(profile) => Some(env::current_dir()?.join(profile)),
+        None => None,
+    };
+    let browser = Browser::new(LaunchOptions {
+        headless,
+        args: vec![OsStr::new(&user_agent)],
+        path: config.browser_path.clone().or_else(browser_executable),
+        user_data_dir,
+        ..Default::default()
+    })?;
+    Ok(browser)
+}
+
+#[derive(Debug, Clone)]
+pub struct FetchedForecast {
+    pub full: String,
+    pub day_blocks: Vec<String>,
+    pub source: String,
+    pub captured: DateTime<Utc>,
+    pub fallback: bool,
+}
+
+impl FetchedForecast {
+    // This abbreviated forecast is typically around ~700 characters
+    pub fn abbreviated(&self) -> String {
+        // Collect information for all days into one string
+        let abbreviated = self.day_blocks
+            .iter()
+            .map(|block| block.replace("\n", ""))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        self.mark(abbreviated)
+    }
+
+    // Only the day blocks of the first `days` days, all of them (with a warning) when their labels
+    // don't show where the days start
+    pub fn with_days(&self, days: Option<usize>) -> FetchedForecast {
+        let days = match days {
+            Some(days) => days,
+            None => return self.clone(),
+        };
+        let labels = self
+            .day_blocks
+            .iter()
+            .map(|block| DayForecast::parse(block).label)
+            .collect::<Vec<_>>();
+        match first_days(&labels, days) {
+            Some(keep) => FetchedForecast {
+                day_blocks: self.day_blocks[..keep].to_vec(),
+                ..self.clone()
+            },
+            None => {
+                warn!(
+                    "Could not tell where the days start ({}), keeping every day block",
+                    labels.join(", ")
+                );
+                self.clone()
+            }
+        }
+    }
+
+    pub fn full_text(&self) -> String {
+        self.mark(self.full.clone())
+    }
+
+    // Fallback content is prefixed with where it came from so it's never mistaken for the live page
+    fn mark(&self, text: String) -> String {
+        if self.fallback {
+            format!(
+                "[FALLBACK {} captured {}] {}",
+                self.source,
+                self.captured.format("%Y-%m-%d %H:%M UTC"),
+                text
+            )
+        } else {
+            text
+        }
+    }
+}
+
+// Tries the primary source, falling back through the configured sources once it has failed
+// `after_failures` cycles in a row
+pub fn fetch_with_fallback(
+    tab: &Arc<Tab>,
+    config: &Config,
+    effects: &Effects,
+    primary_failures: &mut u32,
+) -> Result<FetchedForecast, Box<dyn Error>> {
+    let known = read_structured_forecast(effects, &OutputFiles::default());
+    let primary_error = match fetch_validated(tab, config, effects, &config.source, false, known.as_ref()) {
+        Ok(forecast) => {
+            *primary_failures = 0;
+            return Ok(forecast);
+        }
+        // The page is up, just behind, the cycle goes on with the forecast already seen
+        Err(e) if matches!(e.downcast_ref::<RavenError>(), Some(RavenError::CachedPage { .. })) => return Err(e),
+        Err(e) => e,
+    };
+
+    *primary_failures += 1;
+    warn!("{} failed ({} in a row): {}", config.source.name, primary_failures, primary_error);
+    if *primary_failures < config.fallback.after_failures || config.fallback.sources.is_empty() {
+        return Err(primary_error);
+    }
+
+    for source in &config.fallback.sources {
+        match fetch_validated(tab, config, effects, source, true, known.as_ref()) {
+            Ok(forecast) => {
+                warn!("Using fallback forecast from {}", source.name);
+                return Ok(forecast);
+            }
+            Err(e) => warn!("Fallback {} failed: {}", source.name, e),
+        }
+    }
+
+    Err(Box::new(RavenError::AllSourcesFailed))
+}
+
+// Fetches one source and rejects (capturing the page) anything that fails validation. `known` is
+// the last forecast seen from it, a live page showing an older one is reloaded bypassing the cache.
+// One still older after that is a CachedPage, which the cycle takes as the known forecast unchanged
+pub fn fetch_validated(
+    tab: &Arc<Tab>,
+    config: &Config,
+    effects: &Effects,
+    source: &Source,
+    fallback: bool,
+    known: Option<&Forecast>,
+) -> Result<FetchedForecast, Box<dyn Error>> {
+    let mut forecast = fetch_forecast(tab, config, effects, source, fallback, false)?;
+    reject_oversized(tab, config, effects, source, &forecast)?;
+
+    if let Some((rendered, seen)) = older_than_known(source, &forecast, known) {
+        warn!(
+            "{} shows the forecast issued {}, older than {} already seen, hard reloading",
+            source.name, rendered, seen
+        );
+        forecast = fetch_forecast(tab, config, effects, source, fallback, true)?;
+        reject_oversized(tab, config, effects, source, &forecast)?;
+        if let Some((rendered, known)) = older_than_known(source, &forecast, known) {
+            return Err(Box::new(RavenError::CachedPage {
+                source: source.name.clone(),
+                rendered,
+                known,
+            }));
+        }
+    }
+
+    if let Err(e) = validate_forecast(&forecast, &config.validation, effects.now()) {
+        save_capture(tab, config, effects, source, Some(&forecast), &e);
+        return Err(Box::new(e));
+    }
+
+    Ok(forecast)
+}
+
+// Before the issue time is even parsed, the full validation checks the size again
+fn reject_oversized(
+    tab: &Arc<Tab>,
+    config: &Config,
+    effects: &Effects,
+    source: &Source,
+    forecast: &FetchedForecast,
+) -> Result<(), Box<dyn Error>> {
+    if let Err(e) = check_size(forecast, &config.validation) {
+        save_capture(tab, config, effects, source, Some(forecast), &e);
+        return Err(Box::new(e));
+    }
+    Ok(())
+}
+
+// Saves a debug capture when a capture dir is configured, never failing the fetch itself
+pub fn save_capture(
+    tab: &Arc<Tab>,
+    config: &Config,
+    effects: &Effects,
+    source: &Source,
+    fetched: Option<&FetchedForecast>,
+    reason: &dyn Error,
+) {
+    if let Some(capture_dir) = &config.debug.capture_dir {
+        if effects.is_dry_run() {
+            info!("Dry run: would save a debug capture to {}", capture_dir.display());
+        } else {
+            match capture_debug(tab, &config.debug, effects, capture_dir, source, fetched, reason) {
+                Ok(dir) => {
+                    warn!("{} from {}, debug capture saved to {}", reason, source.name, dir.display());
+                    effects.record_capture(&dir);
+                }
+                Err(capture_error) => warn!("Could not save debug capture: {}", capture_error),
+            }
+        }
+    }
+}
+
+// The rendered and already seen issue times when a live page shows an older forecast than the last
+// one seen. Issue times that don't parse can't be compared and pass
+fn older_than_known(source: &Source, fetched: &FetchedForecast, known: Option<&Forecast>) -> Option<(String, String)> {
+    if source.capture_time.is_some() {
+        return None;
+    }
+    let known = known?;
+    let rendered = Forecast::parse(&fetched.full, &fetched.day_blocks);
+    if rendered.issued_at()? < known.issued_at()? {
+        return Some((rendered.issued?, known.issued.clone()?));
+    }
+    None
+}
+
+// `hard_reload` reloads the page already on the tab ignoring the cache rather than navigating
+pub fn fetch_forecast(
+    tab: &Arc<Tab>,
+    config: &Config,
+    effects: &Effects,
+    source: &Source,
+    fallback: bool,
+    hard_reload: bool,
+) -> Result<FetchedForecast, Box<dyn Error>> {
+    // Has to be listening before the page makes its requests
+    let interceptor = source.intercept.as_ref().and_then(|intercept| match Interceptor::install(tab, intercept) {
+        Ok(interceptor) => Some(interceptor),
+        Err(e) => {
+            warn!("Could not intercept responses from {}, scraping the page: {}", source.name, e);
+            None
+        }
+    });
+
+    // A cached copy of the page once hid an update for over an hour
+    if let Err(e) = tab.call_method(SetCacheDisabled { cache_disabled: true }) {
+        debug!("Could not disable the browser cache: {}", e);
+    }
+    if hard_reload {
+        tab.reload(true, None)?.wait_until_navigated()?;
+    } else {
+        let url = page_url(source, effects.now());
+        let policy = config.retry.policy(Operation::Navigation);
+        retry_with_clock(&policy, effects.clock(), &format!("Navigating to {}", url), || {
+            tab.navigate_to(&url)?;
+            Ok(())
+        })?;
+    }
+
+    if let (Some(interceptor), Some(intercept)) = (interceptor, &source.intercept) {
+        if let Some(forecast) = intercepted_forecast(tab, config, effects, source, intercept, interceptor, fallback) {
+            return Ok(forecast);
+        }
+    }
+
+    // Wait for network/javascript/dom to load forecast
+    let outlook = &source.selectors.outlook;
+    let policy = config.retry.policy(Operation::ElementWait);
+    retry_with_clock(&policy, effects.clock(), &format!("Waiting for {}", outlook), || {
+        tab.wait_for_element(outlook)?;
+        Ok(())
+    })?;
+    dismiss_overlays(tab, &source.selectors.overlays);
+    if source.selectors.click_outlook {
+        click_outlook(tab, config, effects, source)?;
+    }
+
+    let full = fetch_higher_summits_forecast(tab, &source.selectors.full)?;
+    let day_blocks = fetch_day_blocks(tab, &source.selectors.days, &source.abbreviation)?;
+
+    Ok(FetchedForecast {
+        full,
+        day_blocks,
+        source: source.name.clone(),
+        captured: captured(tab, effects, source)?,
+        fallback,
+    })
+}
+
+// The forecast from the intercepted response, as long as it validates like a scraped one would.
+// None falls back to scraping the page
+fn intercepted_forecast(
+    tab: &Arc<Tab>,
+    config: &Config,
+    effects: &Effects,
+    source: &Source,
+    intercept: &InterceptConfig,
+    interceptor: Interceptor,
+    fallback: bool,
+) -> Option<FetchedForecast> {
+    let body = match interceptor.wait(effects.clock()) {
+        Some(body) => body,
+        None => {
+            info!("No response from {} matched within {}s, scraping the page", source.name, intercept.timeout_seconds);
+            return None;
+        }
+    };
+    let (full, day_blocks) = match extract_forecast(&body, intercept) {
+        Some(extracted) => extracted,
+        None => {
+            warn!("No outlook in the intercepted response from {}, scraping the page", source.name);
+            return None;
+        }
+    };
+
+    let forecast = FetchedForecast {
+        full,
+        day_blocks: day_blocks
+            .iter()
+            .filter_map(|block| clean_block(block, None, &source.abbreviation.strip, &[]))
+            .collect(),
+        source: source.name.clone(),
+        captured: captured(tab, effects, source).ok()?,
+        fallback,
+    };
+    match validate_forecast(&forecast, &config.validation, effects.now()) {
+        Ok(()) => {
+            info!("Using the intercepted forecast from {}", source.name);
+            Some(forecast)
+        }
+        Err(e) => {
+            warn!("Rejected the intercepted forecast from {} ({}), scraping the page", source.name, e);
+            None
+        }
+    }
+}
+
+// The source's url, with a throwaway parameter when it's set to bust caches
+fn page_url(source: &Source, now: DateTime<Utc>) -> String {
+    if !source.cache_bust {
+        return source.url.clone();
+    }
+    let (url, fragment) = match source.url.split_once('#') {
+        Some((url, fragment)) => (url, format!("#{}", fragment)),
+        None => (source.url.as_str(), String::new()),
+    };
+    let separator = if url.contains('?') { '&' } else { '?' };
+    format!("{}{}raven={}{}", url, separator, now.timestamp_millis(), fragment)
+}
+
+fn captured(tab: &Arc<Tab>, effects: &Effects, source: &Source) -> Result<DateTime<Utc>, RavenError> {
+    match &source.capture_time {
+        None => Ok(effects.now()),
+        Some(capture_time) => fetch_capture_time(tab, capture_time)
+            .ok_or_else(|| RavenError::UnknownCaptureTime { source: source.name.clone() }),
+    }
+}
+
+// Clicks whichever of the configured banners and popups are on the page
+fn dismiss_overlays(tab: &Arc<Tab>, overlays: &[String]) {
+    for selector in overlays {
+        if let Ok(element) = tab.find_element(selector) {
+            match element.click() {
+                Ok(_) => info!("Dismissed overlay {}", selector),
+                Err(e) => debug!("Could not dismiss overlay {}: {}", selector, e),
+            }
+        }
+    }
+}
+
+// Clicks the outlook, and when something covers it saves a capture, presses Escape (which closes
+// most modals) and tries once more before giving up naming what was in the way
+fn click_outlook(tab: &Arc<Tab>, config: &Config, effects: &Effects, source: &Source) -> Result<(), Box<dyn Error>> {
+    let selector = &source.selectors.outlook;
+    let error = match try_click(tab, selector) {
+        Ok(()) => return Ok(()),
+        Err(error) => error,
+    };
+
+    warn!("Could not click {} ({}), pressing Escape and retrying", selector, error);
+    save_capture(tab, config, effects, source, None, &error);
+    tab.press_key("Escape")?;
+    dismiss_overlays(tab, &source.selectors.overlays);
+
+    try_click(tab, selector).map_err(|e| e.into())
+}
+
+fn try_click(tab: &Arc<Tab>, selector: &str) -> Result<(), RavenError> {
+    let obscured = |obscured_by: String| RavenError::ElementObscured {
+        selector: selector.to_string(),
+        obscured_by,
+    };
+
+    // headless_chrome clicks whatever is at the element's centre, so check first that it's the element
+    if let Some(obscured_by) = obscuring_element(tab, selector) {
+        return Err(obscured(obscured_by));
+    }
+    match tab.find_element(selector).and_then(|element| element.click().map(|_| ())) {
+        Ok(()) => Ok(()),
+        Err(e) => Err(obscured(format!("unknown element ({})", e))),
+    }
+}
+
+// Describes the topmost element at the centre of `selector` when it isn't the element or one of its children
+fn obscuring_element(tab: &Arc<Tab>, selector: &str) -> Option<String> {
+    let script = format!(
+        r##"(function() {{
+            var element = document.querySelector({});
+            if (!element) return null;
+            var rect = element.getBoundingClientRect();
+            var top = document.elementFromPoint(rect.left + rect.width / 2, rect.top + rect.height / 2);
+            if (!top || element === top || element.contains(top)) return null;
+            var description = top.tagName.toLowerCase();
+            if (top.id) description += "#" + top.id;
+            if (typeof top.className === "string" && top.className.trim()) {{
+                description += "." + top.className.trim().split(/\s+/).join(".");
+            }}
+            return description;
+        }})()"##,
+        serde_json::to_string(selector).ok()?
+    );
+
+    let result = tab.evaluate(&script, false).ok()?;
+    result.value?.as_str().map(|description| description.to_string())
+}
+
+// This full, detailed summary is often around ~2k characters, split over one or more paragraphs
+fn fetch_higher_summits_forecast(tab: &Arc<Tab>, selector: &str) -> Result<String, Box<dyn Error>> {
+    // Snag larger forecast, keeping the paragraph breaks
+    let mut paragraphs = Vec::new();
+    for element in tab.wait_for_elements(selector)? {
+        paragraphs.push(element.get_inner_text()?);
+    }
+
+    Ok(join_paragraphs(paragraphs))
+}
+
+// Blank lines between the paragraphs, empty ones dropped. An intercepted forecast is joined the same way
+pub fn join_paragraphs<I: IntoIterator<Item = String>>(paragraphs: I) -> String {
+    paragraphs
+        .into_iter()
+        .map(|paragraph| paragraph.trim().to_string())
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+// One block per forecast period (Tonight, Saturday, ...), with the label on the first line
+fn fetch_day_blocks(
+    tab: &Arc<Tab>,
+    days: &str,
+    abbreviation: &AbbreviationConfig,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    // Selector lists match in page order
+    let include = if abbreviation.include.is_empty() {
+        days.to_string()
+    } else {
+        abbreviation.include.join(", ")
+    };
+    let exclude = abbreviation.exclude.join(", ");
+    let excluded = if exclude.is_empty() {
+        Vec::new()
+    } else {
+        tab.find_elements(&exclude)
+            .unwrap_or_default()
+            .iter()
+            .map(|element| element.backend_node_id)
+            .collect()
+    };
+
+    let mut day_blocks = Vec::new();
+    for element in tab.wait_for_elements(&include)? {
+        if excluded.contains(&element.backend_node_id) {
+            continue;
+        }
+        let mut inner_texts = Vec::new();
+        if !exclude.is_empty() {
+            for inner in element.find_elements(&exclude).unwrap_or_default() {
+                inner_texts.extend(inner.get_inner_text().ok().map(|text| text.trim().to_string()));
+            }
+        }
+        let heading = abbreviation
+            .heading
+            .as_ref()
+            .and_then(|heading| element.find_element(heading).ok())
+            .and_then(|heading| heading.get_inner_text().ok());
+
+        let text = element.get_inner_text()?;
+        if let Some(block) = clean_block(&text, heading.as_deref(), &abbreviation.strip, &inner_texts) {
+            day_blocks.push(block);
+        }
+    }
+
+    Ok(day_blocks)
+}
+
+fn fetch_capture_time(tab: &Arc<Tab>, capture_time: &CaptureTime) -> Option<DateTime<Utc>> {
+    let naive = match capture_time {
+        CaptureTime::WaybackUrl => {
+            let url = tab.get_url();
+            let timestamp = url.split("/web/").nth(1)?.get(..14)?;
+            NaiveDateTime::parse_from_str(timestamp, "%Y%m%d%H%M%S").ok()?
+        }
+        CaptureTime::Element { selector, format } => {
+            let text = tab.wait_for_element(selector).ok()?.get_inner_text().ok()?;
+            NaiveDateTime::parse_from_str(text.trim(), format).ok()?
+        }
+    };
+
+    Some(Utc.from_utc_datetime(&naive))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joins_paragraphs_with_blank_lines() {
+        let paragraphs = vec![" First.\n".to_string(), "  ".to_string(), "Second.".to_string(), "Third.".to_string()];
+        assert_eq!(join_paragraphs(paragraphs), "First.\n\nSecond.\n\nThird.");
+    }
+}
@@ -0,0 +1,222 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::locale::Language;
+use crate::wind::{parse_wind, Wind};
+
+// Per-day structured view of the abbreviated forecast, one entry per `#SummitOutlook > div`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Forecast {
+    // As printed on the page, e.g. "5:07 AM Saturday, February 10, 2024"
+    pub issued: Option<String>,
+    pub days: Vec<DayForecast>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DayForecast {
+    pub label: String,
+    pub summary: String,
+    pub high_f: Option<i32>,
+    pub low_f: Option<i32>,
+    pub wind: Option<Wind>,
+}
+
+impl Forecast {
+    pub fn parse(full: &str, day_blocks: &[String]) -> Forecast {
+        let days = day_blocks
+            .iter()
+            .map(|block| DayForecast::parse(block))
+            .filter(|day| !day.summary.is_empty())
+            .collect();
+
+        Forecast {
+            issued: parse_issued(full),
+            days,
+        }
+    }
+
+    // Compact rendering of every day for SMS, e.g. "Tonight: W→NW 50-70 Saturday: NW 40-60"
+    pub fn compact(&self, lang: Language) -> String {
+        self.days
+            .iter()
+            .map(|day| day.compact(lang))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn day(&self, label: &str) -> Option<&DayForecast> {
+        self.days.iter().find(|day| day.label == label)
+    }
+
+    // The issue time in the page's local time, None when it's missing or printed some other way
+    pub fn issued_at(&self) -> Option<NaiveDateTime> {
+        NaiveDateTime::parse_from_str(self.issued.as_deref()?, "%I:%M %p %A, %B %d, %Y").ok()
+    }
+}
+
+impl DayForecast {
+    // Day blocks render as the day's label on the first line followed by the prose
+    pub fn parse(block: &str) -> DayForecast {
+        let mut lines = block.lines().map(str::trim).filter(|l| !l.is_empty());
+        let first = lines.next().unwrap_or_default();
+        let rest = lines.collect::<Vec<_>>().join(" ");
+
+        let (label, summary) = if !rest.is_empty() {
+            (first.trim_end_matches(':').to_string(), rest)
+        } else {
+            match first.find(':') {
+                Some(i) => (first[..i].trim().to_string(), first[i + 1..].trim().to_string()),
+                None => (String::new(), first.to_string()),
+            }
+        };
+
+        DayForecast {
+            high_f: parse_temperature(&summary, "high"),
+            low_f: parse_temperature(&summary, "low"),
+            wind: parse_wind(&summary),
+            label,
+            summary,
+        }
+    }
+
+    // The day's numbers at a glance, e.g. "Tonight lo -5F, W→NW 50-70"
+    pub fn headline(&self, lang: Language) -> String {
+        format!("{} {}", lang.label(&self.label), self.numbers(lang)).trim().to_string()
+    }
+
+    // Just the numbers of the headline, "lo -5F, W→NW 50-70"
+    pub fn numbers(&self, lang: Language) -> String {
+        let strings = lang.strings();
+        let mut numbers = Vec::new();
+        if let Some(high) = self.high_f {
+            numbers.push(format!("{} {}F", strings.high, high));
+        }
+        if let Some(low) = self.low_f {
+            numbers.push(format!("{} {}F", strings.low, low));
+        }
+        if let Some(wind) = &self.wind {
+            numbers.push(wind.compact(lang));
+        }
+        numbers.join(", ")
+    }
+
+    // The wind, then the language's abbreviations for the rest, "Tonight: W→NW 50-70, G90, wc -40F, in clds"
+    pub fn compact(&self, lang: Language) -> String {
+        let mut parts = self.wind.iter().map(|w| w.compact(lang)).collect::<Vec<_>>();
+        if let Some(gust) = self.gust_mph() {
+            parts.push(format!("{}{}", lang.abbreviation("gusts"), gust));
+        }
+        if let Some(chill) = self.wind_chill_f() {
+            parts.push(format!("{} {}F", lang.abbreviation("wind chill"), chill));
+        }
+        if self.in_clouds() {
+            parts.push(lang.abbreviation("in the clouds").to_string());
+        }
+        let details = parts.join(", ");
+        if self.label.is_empty() {
+            details
+        } else {
+            format!("{}: {}", lang.label(&self.label), details).trim_end().to_string()
+        }
+    }
+
+    // "with gusts to 90 mph", "gusting over 100 mph", None for just "higher gusts"
+    pub fn gust_mph(&self) -> Option<u32> {
+        let words = self.summary.split_whitespace().collect::<Vec<_>>();
+        let index = words.iter().position(|w| w.to_lowercase().starts_with("gust"))?;
+        words[index + 1..]
+            .iter()
+            .take(4)
+            .find_map(|word| word.trim_end_matches(|c: char| !c.is_ascii_digit()).parse().ok())
+    }
+
+    // "Wind chills 30 below zero", the first value when a range is given
+    pub fn wind_chill_f(&self) -> Option<i32> {
+        parse_temperature(&self.summary, "chill")
+    }
+
+    // The kinds of precipitation mentioned, in the order they're mentioned, e.g. ["snow", "freezing rain"]
+    pub fn precipitation(&self) -> Vec<&'static str> {
+        let lower = self.summary.to_lowercase();
+        let words = lower
+            .split(|c: char| !c.is_alphabetic())
+            .filter(|w| !w.is_empty())
+            .collect::<Vec<_>>();
+
+        let mut kinds = Vec::new();
+        for (i, word) in words.iter().enumerate() {
+            let next = words.get(i + 1).copied().unwrap_or_default();
+            let kind = match (*word, next) {
+                ("freezing", "rain") => "freezing rain",
+                ("freezing", "drizzle") => "freezing drizzle",
+                ("ice", "pellets") => "ice pellets",
+                // Already counted as part of the two words before
+                ("rain", _) | ("drizzle", _) if i > 0 && words[i - 1] == "freezing" => continue,
+                ("snow", _) | ("snowfall", _) | ("flurries", _) => "snow",
+                ("rain", _) | ("rainfall", _) => "rain",
+                ("drizzle", _) => "drizzle",
+                ("sleet", _) => "sleet",
+                ("graupel", _) => "graupel",
+                _ => continue,
+            };
+            if !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+        }
+        kinds
+    }
+
+    // The summits in the clouds (or fog) at some point in the period
+    pub fn in_clouds(&self) -> bool {
+        let lower = self.summary.to_lowercase();
+        ["in the clouds", "of the clouds", "in the fog", "in-cloud", "in cloud"]
+            .iter()
+            .any(|phrase| lower.contains(phrase))
+    }
+}
+
+// Finds the line mentioning when the forecast was issued ("Issued: 5:07 AM ...")
+fn parse_issued(full: &str) -> Option<String> {
+    full.lines()
+        .map(str::trim)
+        .find(|line| line.to_lowercase().starts_with("issued"))
+        .map(|line| line["issued".len()..].trim_start_matches(':').trim().to_string())
+        .filter(|issued| !issued.is_empty())
+}
+
+// Finds the temperature following a keyword, "Highs around 12F", "Lows 5 below zero", "High: -3°F"
+fn parse_temperature(text: &str, keyword: &str) -> Option<i32> {
+    let words = text.split_whitespace().collect::<Vec<_>>();
+    let index = words.iter().position(|w| {
+        // "Highs" but not "higher"
+        let w = w.to_lowercase();
+        w.starts_with(keyword) && !w[keyword.len()..].starts_with(|c: char| c.is_alphabetic() && c != 's')
+    })?;
+
+    words[index + 1..]
+        .iter()
+        .take(4)
+        .enumerate()
+        .find_map(|(i, word)| {
+            let number = word.trim_end_matches(|c: char| !c.is_ascii_digit());
+            let temperature = number.parse::<i32>().ok()?;
+            match words.get(index + i + 2) {
+                Some(next) if next.to_lowercase().starts_with("below") => Some(-temperature),
+                _ => Some(temperature),
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_uses_the_languages_abbreviations() {
+        let day = DayForecast::parse(
+            "Tonight:\nIn the clouds with snow. NW winds 60 to 80 mph with gusts to 100 mph. Wind chills 40 below zero.",
+        );
+        assert_eq!(day.compact(Language::En), "Tonight: NW 60-80, G100, wc -40F, in clds");
+        assert_eq!(day.compact(Language::Fr), "Ce soir: NO 60-80, raf100, refr -40F, ds nuages");
+    }
+}
@@ -0,0 +1,309 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::error::RavenError;
+use crate::forecast::Forecast;
+use crate::locale::Language;
+use crate::routing::{known_channels, INREACH_CHANNEL};
+use crate::sms::{append_line, prepare_messages, prepare_messages_with_summary, sanitize};
+use crate::summary::summary_line;
+
+// What a template can use, anything else in braces is left as written
+const PLACEHOLDERS: [&str; 5] = ["heading", "alerts", "body", "note", "trend"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    // Folded by the SMS sanitizer, whitespace collapsed
+    Ascii,
+    Unicode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Markup {
+    Plain,
+    // Heading and alerts in bold
+    Markdown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Length {
+    // As many numbered SMS-sized parts ("1/3 ...") as it takes
+    SmsParts,
+    // One message cut short at this many characters
+    Single(usize),
+    Unlimited,
+}
+
+// What a channel can carry, each one renders the same content to fit its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Constraints {
+    pub length: Length,
+    pub charset: Charset,
+    pub markup: Markup,
+}
+
+impl Constraints {
+    // The inReach and plain SMS
+    pub const SMS: Constraints = Constraints {
+        length: Length::SmsParts,
+        charset: Charset::Ascii,
+        markup: Markup::Plain,
+    };
+}
+
+// Everything a notification is rendered from, kept with a held send so each channel renders it
+// when it goes out
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Content {
+    // The product's name, None for the higher summits forecast
+    #[serde(default)]
+    pub heading: Option<String>,
+    #[serde(default)]
+    pub alerts: Vec<String>,
+    pub body: String,
+    // A standing line from `raven note set` or the config, after the body
+    #[serde(default)]
+    pub note: Option<String>,
+    // The summit conditions trend, only known once the send goes out
+    #[serde(skip)]
+    pub trend: Option<String>,
+    // Leads split channels with a summary part
+    #[serde(default)]
+    pub summary: Option<Summary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Summary {
+    pub last_sent: Option<Forecast>,
+    pub forecast: Forecast,
+}
+
+impl Content {
+    pub fn text(body: impl Into<String>) -> Content {
+        Content {
+            body: body.into(),
+            ..Content::default()
+        }
+    }
+}
+
+// One channel's messages, laid out by `template` when the config has one for it
+pub fn render(content: &Content, constraints: Constraints, template: Option<&str>, lang: Language) -> Vec<String> {
+    let text = match template {
+        Some(template) => expand(template, content, constraints.markup),
+        None => compose(content, constraints.markup),
+    };
+    let text = match constraints.charset {
+        Charset::Ascii => sanitize(&text),
+        Charset::Unicode => text.trim().to_string(),
+    };
+    // A template places the trend itself
+    let trend = content.trend.as_deref().filter(|_| template.is_none());
+
+    match constraints.length {
+        Length::SmsParts => {
+            let parts = match &content.summary {
+                Some(summary) => prepare_messages_with_summary(&text, |total_parts, max_length| {
+                    summary_line(summary.last_sent.as_ref(), &summary.forecast, total_parts, max_length, lang)
+                }),
+                None => prepare_messages(&text),
+            };
+            match trend {
+                Some(trend) => append_line(&parts, trend, true),
+                None => parts,
+            }
+        }
+        Length::Single(max_length) => vec![truncate(&with_trend(text, trend), max_length)],
+        Length::Unlimited => vec![with_trend(text, trend)],
+    }
+}
+
+// What goes to the inReach, which isn't a notifier but takes a template like one
+pub fn inreach_messages(config: &Config, content: &Content) -> Vec<String> {
+    render(
+        content,
+        Constraints::SMS,
+        template(config, &[INREACH_CHANNEL.to_string()]),
+        config.language(),
+    )
+}
+
+// The most specific of `channels` with a template, e.g. twilio:alice before twilio
+pub fn template<'a>(config: &'a Config, channels: &[String]) -> Option<&'a str> {
+    channels
+        .iter()
+        .rev()
+        .find_map(|channel| config.notify.templates.get(channel))
+        .map(String::as_str)
+}
+
+// Templates for channels that don't exist or with placeholders that don't either fail startup
+pub fn validate_templates(config: &Config) -> Result<(), RavenError> {
+    let known = known_channels(config);
+    for (channel, template) in &config.notify.templates {
+        if !known.contains(channel) {
+            return Err(RavenError::InvalidConfig(format!(
+                "notify.templates has a template for unknown channel {}, configured channels are {}",
+                channel,
+                known.join(", ")
+            )));
+        }
+        if let Some(unknown) = placeholders(template).find(|name| !PLACEHOLDERS.contains(name)) {
+            return Err(RavenError::InvalidConfig(format!(
+                "the {} template uses {{{}}}, templates can use {}",
+                channel,
+                unknown,
+                PLACEHOLDERS.map(|name| format!("{{{}}}", name)).join(", ")
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Alerts lead so they survive a recipient only reading the first part, after the product's name so
+// it can't be mistaken for the higher summits forecast
+fn compose(content: &Content, markup: Markup) -> String {
+    content
+        .heading
+        .iter()
+        .map(|heading| emphasis(&format!("{}:", heading), markup))
+        .chain(content.alerts.iter().map(|alert| emphasis(&format!("ALERT {}.", alert), markup)))
+        .chain(std::iter::once(content.body.clone()))
+        .chain(content.note.clone())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn expand(template: &str, content: &Content, markup: Markup) -> String {
+    let alerts = content
+        .alerts
+        .iter()
+        .map(|alert| emphasis(&format!("ALERT {}.", alert), markup))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let heading = content
+        .heading
+        .as_deref()
+        .map(|heading| emphasis(heading, markup))
+        .unwrap_or_default();
+
+    // A template written before notes existed still gets one, after everything else
+    let template = match &content.note {
+        Some(_) if !template.contains("{note}") => format!("{} {{note}}", template),
+        _ => template.to_string(),
+    };
+    template
+        .replace("{heading}", &heading)
+        .replace("{alerts}", &alerts)
+        .replace("{body}", &content.body)
+        .replace("{note}", content.note.as_deref().unwrap_or_default())
+        .replace("{trend}", content.trend.as_deref().unwrap_or_default())
+}
+
+fn emphasis(text: &str, markup: Markup) -> String {
+    match markup {
+        Markup::Plain => text.to_string(),
+        Markup::Markdown => format!("*{}*", text),
+    }
+}
+
+fn with_trend(text: String, trend: Option<&str>) -> String {
+    match trend {
+        Some(trend) => format!("{}\n{}", text, trend),
+        None => text,
+    }
+}
+
+fn truncate(text: &str, max_length: usize) -> String {
+    if text.chars().count() <= max_length {
+        return text.to_string();
+    }
+    let mut cut = text.chars().take(max_length.saturating_sub(3)).collect::<String>();
+    cut.push_str("...");
+    cut
+}
+
+// The names inside braces, e.g. "body" for "{body}"
+fn placeholders(template: &str) -> impl Iterator<Item = &str> {
+    template
+        .split('{')
+        .skip(1)
+        .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Like Telegram: one long message, Markdown and anything Unicode
+    const CHAT: Constraints = Constraints {
+        length: Length::Single(4096),
+        charset: Charset::Unicode,
+        markup: Markup::Markdown,
+    };
+    // Like email or a webhook field: the whole text as is
+    const FULL: Constraints = Constraints {
+        length: Length::Unlimited,
+        charset: Charset::Unicode,
+        markup: Markup::Plain,
+    };
+
+    fn content() -> Content {
+        Content {
+            heading: Some("Regional".to_string()),
+            alerts: vec!["Tonight wind 90 (was 60)".to_string()],
+            body: format!("Tonight: W→NW 60-90 mph, −20°F. {}", "Summit in the clouds with snow. ".repeat(10)),
+            note: Some("Back Sunday".to_string()),
+            trend: Some("Summit now 3F".to_string()),
+            summary: None,
+        }
+    }
+
+    #[test]
+    fn sms_is_ascii_parts_of_160() {
+        let messages = render(&content(), Constraints::SMS, None, Language::En);
+        assert!(messages.len() > 1);
+        assert!(messages.iter().all(|message| message.chars().count() <= 160), "{:?}", messages);
+        assert!(messages.iter().all(|message| message.is_ascii()), "{:?}", messages);
+        assert!(messages[0].contains("Regional: ALERT Tonight wind 90 (was 60)."), "{}", messages[0]);
+        assert!(messages[0].contains("W>NW 60-90 mph, -20F."), "{}", messages[0]);
+        assert!(!messages.concat().contains('*'));
+    }
+
+    #[test]
+    fn chat_keeps_unicode_and_emphasises_with_markdown() {
+        let messages = render(&content(), CHAT, None, Language::En);
+        assert_eq!(messages.len(), 1);
+        let lead = "*Regional:* *ALERT Tonight wind 90 (was 60).* Tonight: W→NW 60-90 mph, −20°F.";
+        assert!(messages[0].starts_with(lead), "{}", messages[0]);
+        assert!(messages[0].ends_with("Back Sunday\nSummit now 3F"), "{}", messages[0]);
+    }
+
+    #[test]
+    fn a_single_message_is_cut_short_at_its_length() {
+        let short = Constraints {
+            length: Length::Single(40),
+            ..CHAT
+        };
+        let messages = render(&content(), short, None, Language::En);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].chars().count(), 40);
+        assert!(messages[0].ends_with("..."));
+    }
+
+    #[test]
+    fn unlimited_is_the_whole_text_unchanged() {
+        let content = content();
+        let messages = render(&content, FULL, None, Language::En);
+        assert_eq!(
+            messages,
+            [format!("Regional: ALERT Tonight wind 90 (was 60). {} Back Sunday\nSummit now 3F", content.body)]
+        );
+    }
+
+    #[test]
+    fn a_template_lays_out_the_message_and_places_the_trend() {
+        let messages = render(&content(), CHAT, Some("{heading} | {trend} | {alerts}"), Language::En);
+        assert_eq!(messages, ["*Regional* | Summit now 3F | *ALERT Tonight wind 90 (was 60).* Back Sunday"]);
+    }
+}
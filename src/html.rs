@@ -0,0 +1,96 @@
+use chrono::{DateTime, Local};
+
+use crate::forecast::{DayForecast, Forecast};
+use crate::locale::Language;
+
+const STYLE: &str = "body{font-family:sans-serif;max-width:48em;margin:1em auto;padding:0 1em;color:#222}\
+.days{display:flex;flex-wrap:wrap;gap:.5em}\
+.day{flex:1 1 12em;border:1px solid #ccc;border-radius:4px;padding:.5em}\
+.day h2{font-size:1.1em;margin:0 0 .3em}\
+.numbers{font-weight:bold}\
+footer{margin-top:2em;font-size:.8em;color:#666}";
+
+// Self-contained page for the latest forecast as of `updated`, everything scraped is escaped since it
+// comes from a web page
+pub fn render_html(forecast: &Forecast, full_forecast: &str, refresh_seconds: u32, updated: DateTime<Local>) -> String {
+    let days = forecast.days.iter().map(render_day).collect::<Vec<_>>().join("\n");
+    let narrative = full_forecast
+        .split("\n\n")
+        .filter(|p| !p.trim().is_empty())
+        .map(|p| format!("<p>{}</p>", escape(p.trim())))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta http-equiv="refresh" content="{refresh}">
+<title>Higher Summits Forecast</title>
+<style>{style}</style>
+</head>
+<body>
+<h1>Higher Summits Forecast</h1>
+<p>Issued {issued}</p>
+<div class="days">
+{days}
+</div>
+{narrative}
+<footer>Last updated by raven at {updated}</footer>
+</body>
+</html>
+"#,
+        refresh = refresh_seconds,
+        style = STYLE,
+        issued = escape(forecast.issued.as_deref().unwrap_or("n/a")),
+        days = days,
+        narrative = narrative,
+        updated = updated.format("%Y-%m-%d %H:%M %Z"),
+    )
+}
+
+fn render_day(day: &DayForecast) -> String {
+    format!(
+        "<div class=\"day\"><h2>{}</h2><div class=\"numbers\">{}</div><p>{}</p></div>",
+        escape(&day.label),
+        escape(&day.numbers(Language::En)),
+        escape(&day.summary)
+    )
+}
+
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn scraped_text_is_escaped() {
+        let blocks = vec!["Today <script>alert(1)</script>:\nSnow & \"blowing\" snow, it's cold.".to_string()];
+        let full = "Issued: 5:07 AM Friday, January 12, 2024\n\nThe <b>summits</b> & ridges.";
+        let forecast = Forecast::parse(full, &blocks);
+        let updated = Local.with_ymd_and_hms(2024, 1, 12, 5, 30, 0).unwrap();
+        let html = render_html(&forecast, full, 300, updated);
+
+        assert!(!html.contains("<script>") && !html.contains("<b>"), "{}", html);
+        assert!(html.contains("<h2>Today &lt;script&gt;alert(1)&lt;/script&gt;</h2>"), "{}", html);
+        assert!(html.contains("Snow &amp; &quot;blowing&quot; snow, it&#39;s cold."), "{}", html);
+        assert!(html.contains("<p>The &lt;b&gt;summits&lt;/b&gt; &amp; ridges.</p>"), "{}", html);
+    }
+}
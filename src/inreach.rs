@@ -0,0 +1,97 @@
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+use headless_chrome::Tab;
+use log::info;
+use serde::Deserialize;
+
+use crate::clock::Clock;
+use crate::config::{Config, InReachConfig};
+use crate::error::RavenError;
+use crate::fetch::{launch_browser, launch_browser_with};
+
+pub use self::account::AccountSender;
+pub use self::browser::BrowserSender;
+pub use self::http::HttpSender;
+
+mod account;
+mod browser;
+mod http;
+
+// How long `raven garmin-login` waits for someone to finish logging in
+const INTERACTIVE_LOGIN_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    // Drives the MapShare reply page in the headless browser
+    #[default]
+    Browser,
+    // Submits the reply page's form directly, experimental
+    Http,
+    // Logs in to a Garmin Explore account and sends from its inbox, no reply link needed
+    Account,
+}
+
+// Anything that can deliver prepared messages to the inReach, in order. `on_sent` is called with
+// each message's index as soon as it has gone, so a send that dies partway can be resumed
+pub trait InReachSender {
+    fn send(&self, messages: &[String], on_sent: &mut dyn FnMut(usize)) -> Result<(), Box<dyn Error>>;
+}
+
+// Sends through whichever transport is configured. The browser transport reuses the fetch's tab
+// when there is one and launches its own browser otherwise. The account transport always launches
+// its own, on the profile its login session is kept in
+pub fn send_message_to_inreach(
+    tab: Option<&Arc<Tab>>,
+    config: &Config,
+    clock: &dyn Clock,
+    messages: &[String],
+    on_sent: &mut dyn FnMut(usize),
+) -> Result<(), Box<dyn Error>> {
+    match config.inreach.transport {
+        Transport::Browser => {
+            let reply_url = reply_url(&config.inreach)?;
+            match tab {
+                Some(tab) => BrowserSender::new(tab, &config.inreach, reply_url).send(messages, on_sent),
+                None => {
+                    let browser = launch_browser(config)?;
+                    let tab = browser.wait_for_initial_tab()?;
+                    BrowserSender::new(&tab, &config.inreach, reply_url).send(messages, on_sent)
+                }
+            }
+        }
+        Transport::Http => {
+            let reply_url = reply_url(&config.inreach)?;
+            HttpSender::new(&config.inreach, &config.user_agent, reply_url, clock).send(messages, on_sent)
+        }
+        Transport::Account => {
+            let account = &config.inreach.account;
+            let browser = launch_browser_with(config, Some(&account.profile_dir), true)?;
+            let tab = browser.wait_for_initial_tab()?;
+            AccountSender::new(&tab, account).send(messages, on_sent)
+        }
+    }
+}
+
+// Opens the Garmin inbox in a visible browser on the account transport's profile and waits for
+// someone to log in, verification code and all. Later sends reuse the session
+pub fn log_in_interactively(config: &Config) -> Result<(), Box<dyn Error>> {
+    let account = &config.inreach.account;
+    let browser = launch_browser_with(config, Some(&account.profile_dir), false)?;
+    let tab = browser.wait_for_initial_tab()?;
+    tab.navigate_to(&account.messages_url)?;
+    info!(
+        "Log in to Garmin in the browser window, waiting up to {} minutes",
+        INTERACTIVE_LOGIN_TIMEOUT.as_secs() / 60
+    );
+    tab.wait_for_element_with_custom_timeout(&account.conversation_selector, INTERACTIVE_LOGIN_TIMEOUT)?;
+    Ok(())
+}
+
+fn reply_url(config: &InReachConfig) -> Result<String, RavenError> {
+    config.reply_url().ok_or_else(|| RavenError::MissingReplyUrl {
+        env: config.reply_url_env.clone(),
+    })
+}
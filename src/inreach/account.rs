@@ -0,0 +1,97 @@
+use std::env;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+use headless_chrome::Tab;
+use log::{debug, info};
+
+use crate::config::AccountConfig;
+use crate::error::RavenError;
+use crate::inreach::InReachSender;
+
+// How long Garmin gets to show the inbox, the login form or a verification code prompt
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Sends through a Garmin Explore account's inbox instead of a reply link, so it works whether or
+// not the inReach messaged first. The tab belongs to a browser on raven's own profile, a saved
+// session skips the login
+pub struct AccountSender<'a> {
+    tab: &'a Arc<Tab>,
+    config: &'a AccountConfig,
+}
+
+impl<'a> AccountSender<'a> {
+    pub fn new(tab: &'a Arc<Tab>, config: &'a AccountConfig) -> AccountSender<'a> {
+        AccountSender { tab, config }
+    }
+
+    // Fills in the login form when there's no saved session. A verification code prompt, or a login
+    // that never gets to the inbox, fails at once rather than waiting on a code nobody will type
+    fn log_in(&self) -> Result<(), Box<dyn Error>> {
+        let (tab, config) = (self.tab, self.config);
+        let inbox_or_login = format!("{}, {}", config.conversation_selector, config.username_selector);
+        tab.wait_for_element_with_custom_timeout(&inbox_or_login, LOGIN_TIMEOUT)?;
+        if tab.find_element(&config.conversation_selector).is_ok() {
+            return Ok(());
+        }
+
+        // Only needed when the session has lapsed
+        let username = credential(&config.username_env)?;
+        let password = credential(&config.password_env)?;
+        debug!("Logging in to Garmin as {}", username);
+        tab.wait_for_element(&config.username_selector)?.click()?;
+        tab.type_str(&username)?;
+        tab.wait_for_element(&config.password_selector)?.click()?;
+        tab.type_str(&password)?;
+        tab.wait_for_element(&config.login_selector)?.click()?;
+
+        let inbox_or_code = format!("{}, {}", config.conversation_selector, config.mfa_selector);
+        let reason = match tab.wait_for_element_with_custom_timeout(&inbox_or_code, LOGIN_TIMEOUT) {
+            Ok(_) if tab.find_element(&config.conversation_selector).is_ok() => return Ok(()),
+            Ok(_) => "Garmin is asking for a verification code".to_string(),
+            Err(_) => format!(
+                "the Garmin login didn't reach the inbox, check {} and {}",
+                config.username_env, config.password_env
+            ),
+        };
+        Err(Box::new(RavenError::ManualLoginRequired { reason }))
+    }
+
+    fn open_conversation(&self) -> Result<(), Box<dyn Error>> {
+        // Required by validation for this transport
+        let wanted = self.config.conversation.as_deref().unwrap_or_default();
+        for conversation in self.tab.wait_for_elements(&self.config.conversation_selector)? {
+            if conversation.get_inner_text()?.to_lowercase().contains(&wanted.to_lowercase()) {
+                conversation.click()?;
+                return Ok(());
+            }
+        }
+        Err(format!("no conversation matching {:?} in the Garmin inbox", wanted).into())
+    }
+}
+
+impl<'a> InReachSender for AccountSender<'a> {
+    // Opens the configured conversation and types each prepared message into it, in order
+    fn send(&self, messages: &[String], on_sent: &mut dyn FnMut(usize)) -> Result<(), Box<dyn Error>> {
+        let tab = self.tab;
+        tab.navigate_to(&self.config.messages_url)?.wait_until_navigated()?;
+        self.log_in()?;
+        self.open_conversation()?;
+
+        for (i, message) in messages.iter().enumerate() {
+            tab.wait_for_element(&self.config.message_selector)?.click()?;
+            tab.type_str(message)?;
+            tab.wait_for_element(&self.config.send_selector)?.click()?;
+            info!("Sent inReach message through the Garmin account ({} chars): {}", message.len(), message);
+            on_sent(i);
+        }
+
+        Ok(())
+    }
+}
+
+// From the environment, never the config file
+fn credential(env: &str) -> Result<String, RavenError> {
+    env::var(env).map_err(|_| RavenError::MissingCredentials { env: env.to_string() })
+}
@@ -0,0 +1,45 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use headless_chrome::Tab;
+use log::info;
+
+use crate::config::InReachConfig;
+use crate::inreach::InReachSender;
+
+pub struct BrowserSender<'a> {
+    tab: &'a Arc<Tab>,
+    config: &'a InReachConfig,
+    reply_url: String,
+}
+
+impl<'a> BrowserSender<'a> {
+    pub fn new(tab: &'a Arc<Tab>, config: &'a InReachConfig, reply_url: String) -> BrowserSender<'a> {
+        BrowserSender { tab, config, reply_url }
+    }
+}
+
+impl<'a> InReachSender for BrowserSender<'a> {
+    // Types each prepared message into the MapShare reply page and sends it, in order
+    fn send(&self, messages: &[String], on_sent: &mut dyn FnMut(usize)) -> Result<(), Box<dyn Error>> {
+        let tab = self.tab;
+
+        // Navigate to a verified URL
+        tab.navigate_to(&self.reply_url)?;
+
+        for (i, message) in messages.iter().enumerate() {
+            // Activate the textarea
+            tab.wait_for_element(&self.config.message_selector)?.click()?;
+            tab.press_key("Enter")?;
+
+            tab.type_str(message)?;
+
+            // Click send
+            tab.wait_for_element(&self.config.send_selector)?.click()?;
+            info!("Sent inReach message ({} chars): {}", message.len(), message);
+            on_sent(i);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+use std::error::Error;
+use std::time::Duration;
+
+use log::info;
+use ureq::{Agent, AgentBuilder};
+
+use crate::clock::Clock;
+use crate::config::InReachConfig;
+use crate::error::RavenError;
+use crate::inreach::InReachSender;
+
+// Submits the MapShare reply form directly instead of driving the page: fetch the reply page,
+// pick up its form action and hidden tokens, then post each message alongside them
+pub struct HttpSender<'a> {
+    agent: Agent,
+    config: &'a InReachConfig,
+    reply_url: String,
+    clock: &'a dyn Clock,
+}
+
+impl<'a> HttpSender<'a> {
+    pub fn new(config: &'a InReachConfig, user_agent: &str, reply_url: String, clock: &'a dyn Clock) -> HttpSender<'a> {
+        let agent = AgentBuilder::new()
+            .user_agent(user_agent)
+            .timeout(Duration::from_secs(30))
+            .build();
+
+        HttpSender {
+            agent,
+            config,
+            reply_url,
+            clock,
+        }
+    }
+
+    fn fetch_form(&self) -> Result<ReplyForm, Box<dyn Error>> {
+        let page = self.agent.get(&self.reply_url).call().map_err(map_error)?.into_string()?;
+
+        // An expired reply link renders a page without the reply form
+        let form = ReplyForm::parse(&page, &self.reply_url, &self.config.message_field)
+            .ok_or(RavenError::ReplyUrlExpired)?;
+        Ok(form)
+    }
+}
+
+impl<'a> InReachSender for HttpSender<'a> {
+    fn send(&self, messages: &[String], on_sent: &mut dyn FnMut(usize)) -> Result<(), Box<dyn Error>> {
+        let form = self.fetch_form()?;
+
+        for (i, message) in messages.iter().enumerate() {
+            if i > 0 {
+                self.clock.sleep(Duration::from_secs(self.config.http_delay_seconds));
+            }
+
+            let mut fields = form
+                .hidden_fields
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str()))
+                .collect::<Vec<_>>();
+            fields.push((self.config.message_field.as_str(), message.as_str()));
+
+            self.agent.post(&form.action).send_form(&fields).map_err(map_error)?;
+            info!("Sent inReach message over HTTP ({} chars): {}", message.len(), message);
+            on_sent(i);
+        }
+
+        Ok(())
+    }
+}
+
+fn map_error(error: ureq::Error) -> Box<dyn Error> {
+    match error {
+        ureq::Error::Status(404, _) | ureq::Error::Status(410, _) => Box::new(RavenError::ReplyUrlExpired),
+        ureq::Error::Status(429, response) => Box::new(RavenError::RateLimited {
+            retry_after_seconds: response.header("Retry-After").and_then(|value| value.parse().ok()),
+        }),
+        ureq::Error::Status(status, _) => Box::new(RavenError::InReachHttp { status }),
+        transport => Box::new(transport),
+    }
+}
+
+struct ReplyForm {
+    action: String,
+    hidden_fields: Vec<(String, String)>,
+}
+
+impl ReplyForm {
+    // Deliberately simple tag scanning, the reply page is small and its form is flat
+    fn parse(page: &str, page_url: &str, message_field: &str) -> Option<ReplyForm> {
+        let form_start = page.find("<form")?;
+        let form = &page[form_start..];
+        let form = &form[..form.find("</form>").unwrap_or(form.len())];
+
+        let mut inputs = tags(form, "input");
+        inputs.extend(tags(form, "textarea"));
+        if !inputs.iter().any(|tag| attribute(tag, "name").as_deref() == Some(message_field)) {
+            return None;
+        }
+
+        let hidden_fields = inputs
+            .iter()
+            .filter(|tag| attribute(tag, "type").is_some_and(|t| t.eq_ignore_ascii_case("hidden")))
+            .filter_map(|tag| Some((attribute(tag, "name")?, attribute(tag, "value").unwrap_or_default())))
+            .collect();
+
+        let action = tags(form, "form")
+            .first()
+            .and_then(|tag| attribute(tag, "action"))
+            .map(|action| resolve(page_url, &action))
+            .unwrap_or_else(|| page_url.to_string());
+
+        Some(ReplyForm { action, hidden_fields })
+    }
+}
+
+// The opening tags named `name`, e.g. `<input type="hidden" name="Guid" value="...">`
+fn tags<'a>(html: &'a str, name: &str) -> Vec<&'a str> {
+    let open = format!("<{}", name);
+    html.match_indices(open.as_str())
+        .filter_map(|(start, _)| {
+            let tag = &html[start..];
+            tag.find('>').map(|end| &tag[..=end])
+        })
+        .collect()
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let pattern = format!(" {}=", name);
+    let start = tag.find(&pattern)? + pattern.len();
+    let rest = &tag[start..];
+    let quote = rest.chars().next()?;
+    let value = if quote == '"' || quote == '\'' {
+        let rest = &rest[1..];
+        &rest[..rest.find(quote)?]
+    } else {
+        &rest[..rest.find(|c: char| c.is_whitespace() || c == '>').unwrap_or(rest.len())]
+    };
+    Some(value.replace("&amp;", "&"))
+}
+
+fn resolve(page_url: &str, action: &str) -> String {
+    if action.starts_with("http://") || action.starts_with("https://") {
+        return action.to_string();
+    }
+
+    let scheme_end = page_url.find("://").map(|i| i + 3).unwrap_or(0);
+    let origin_end = page_url[scheme_end..].find('/').map(|i| i + scheme_end).unwrap_or(page_url.len());
+    if action.starts_with('/') {
+        format!("{}{}", &page_url[..origin_end], action)
+    } else {
+        let base_end = page_url.rfind('/').filter(|i| *i >= origin_end).unwrap_or(origin_end);
+        format!("{}/{}", &page_url[..base_end], action)
+    }
+}
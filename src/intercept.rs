@@ -0,0 +1,335 @@
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use headless_chrome::Tab;
+use log::debug;
+
+use crate::clock::Clock;
+use crate::config::InterceptConfig;
+use crate::fetch::join_paragraphs;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Elements that never have a closing tag
+const VOID_ELEMENTS: &[&str] = &["area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "wbr"];
+
+// Elements rendered on their own line, as the page's innerText would
+const BLOCK_ELEMENTS: &[&str] = &["br", "p", "div", "h1", "h2", "h3", "h4", "h5", "h6", "li", "tr"];
+
+#[derive(Default)]
+struct Captured {
+    body: Option<String>,
+    // Set once the fetch has stopped waiting, later responses on the tab are ignored
+    done: bool,
+}
+
+// Remembers the first response body matching the config seen on the tab, installed before navigating
+pub struct Interceptor {
+    captured: Arc<Mutex<Captured>>,
+    timeout: Duration,
+}
+
+impl Interceptor {
+    pub fn install(tab: &Arc<Tab>, config: &InterceptConfig) -> Result<Interceptor, Box<dyn Error>> {
+        let captured = Arc::new(Mutex::new(Captured::default()));
+        let handler_captured = captured.clone();
+        let timeout = Duration::from_secs(config.timeout_seconds);
+        let config = config.clone();
+
+        tab.enable_response_handling(Box::new(move |params, fetch_body| {
+            {
+                let captured = handler_captured.lock().unwrap();
+                if captured.done || captured.body.is_some() {
+                    return;
+                }
+            }
+            let url = &params.response.url;
+            if !config.url_contains.iter().any(|part| url.contains(part.as_str())) {
+                return;
+            }
+
+            let body = match fetch_body() {
+                Ok(body) if body.base_64_encoded => base64::decode(&body.body)
+                    .ok()
+                    .and_then(|bytes| String::from_utf8(bytes).ok()),
+                Ok(body) => Some(body.body),
+                Err(e) => {
+                    debug!("Could not read the body of {}: {}", url, e);
+                    None
+                }
+            };
+            let body = match body {
+                Some(body) if config.body_contains.iter().all(|needle| body.contains(needle.as_str())) => body,
+                _ => {
+                    debug!("Skipping {}, it doesn't look like the outlook", url);
+                    return;
+                }
+            };
+
+            debug!("Intercepted the outlook from {}", url);
+            handler_captured.lock().unwrap().body = Some(body);
+        }))?;
+
+        Ok(Interceptor { captured, timeout })
+    }
+
+    // The matched body, waiting up to the configured timeout for the page to request it
+    pub fn wait(&self, clock: &dyn Clock) -> Option<String> {
+        let timeout = chrono::Duration::from_std(self.timeout).unwrap_or_else(|_| chrono::Duration::zero());
+        let deadline = clock.now() + timeout;
+        loop {
+            {
+                let mut captured = self.captured.lock().unwrap();
+                if captured.body.is_some() || clock.now() >= deadline {
+                    captured.done = true;
+                    return captured.body.take();
+                }
+            }
+            clock.sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+impl Drop for Interceptor {
+    fn drop(&mut self) {
+        self.captured.lock().unwrap().done = true;
+    }
+}
+
+// The full forecast (the container's paragraphs) and day blocks (its divs) from an intercepted
+// body, laid out like the DOM scrape's. None when the body doesn't have both
+pub fn extract_forecast(body: &str, config: &InterceptConfig) -> Option<(String, Vec<String>)> {
+    let html = match &config.json_pointer {
+        Some(pointer) => serde_json::from_str::<serde_json::Value>(body)
+            .ok()?
+            .pointer(pointer)?
+            .as_str()?
+            .to_string(),
+        None => body.to_string(),
+    };
+    let container = match &config.container {
+        Some(marker) => inner_html(&html, marker)?,
+        None => html.as_str(),
+    };
+
+    let full = join_paragraphs(children(container, "p").into_iter().map(text));
+    let day_blocks = children(container, "div")
+        .into_iter()
+        .map(text)
+        .filter(|block| !block.is_empty())
+        .collect::<Vec<_>>();
+
+    if full.is_empty() || day_blocks.is_empty() {
+        return None;
+    }
+    Some((full, day_blocks))
+}
+
+struct Tag<'a> {
+    name: &'a str,
+    closing: bool,
+    // No matching closing tag to wait for
+    empty: bool,
+    start: usize,
+    // Just past the '>'
+    end: usize,
+}
+
+// Deliberately simple tag scanning, comments are skipped and attributes aren't parsed
+fn tags(html: &str) -> Vec<Tag<'_>> {
+    let mut tags = Vec::new();
+    let mut offset = 0;
+    while let Some(found) = html[offset..].find('<') {
+        let start = offset + found;
+        let rest = &html[start..];
+        if rest.starts_with("<!--") {
+            offset = rest.find("-->").map(|end| start + end + 3).unwrap_or(html.len());
+            continue;
+        }
+        let end = match rest.find('>') {
+            Some(end) => start + end + 1,
+            None => break,
+        };
+
+        let closing = rest.starts_with("</");
+        let name_start = if closing { 2 } else { 1 };
+        let name_length = rest[name_start..]
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len() - name_start);
+        let name = &rest[name_start..name_start + name_length];
+        if !name.is_empty() {
+            let empty = !closing
+                && (html[..end].ends_with("/>") || VOID_ELEMENTS.iter().any(|void| void.eq_ignore_ascii_case(name)));
+            tags.push(Tag {
+                name,
+                closing,
+                empty,
+                start,
+                end,
+            });
+        }
+        offset = end;
+    }
+    tags
+}
+
+// Inner HTML of the element whose opening tag contains `marker`, e.g. `id="SummitOutlook"`
+fn inner_html<'a>(html: &'a str, marker: &str) -> Option<&'a str> {
+    let open = html[..html.find(marker)?].rfind('<')?;
+    let element = &html[open..];
+
+    let mut depth = 0;
+    let mut inner_start = None;
+    for tag in tags(element) {
+        if tag.empty {
+            continue;
+        }
+        if tag.closing {
+            depth -= 1;
+            if depth == 0 {
+                return Some(&element[inner_start?..tag.start]);
+            }
+        } else {
+            if depth == 0 {
+                inner_start = Some(tag.end);
+            }
+            depth += 1;
+        }
+    }
+    None
+}
+
+// Inner HTML of the elements named `name` directly inside `html`
+fn children<'a>(html: &'a str, name: &str) -> Vec<&'a str> {
+    let mut children = Vec::new();
+    let mut depth = 0usize;
+    let mut inner_start = None;
+    for tag in tags(html) {
+        if tag.empty {
+            continue;
+        }
+        if tag.closing {
+            depth = depth.saturating_sub(1);
+            if depth == 0 {
+                if let Some(start) = inner_start.take() {
+                    children.push(&html[start..tag.start]);
+                }
+            }
+        } else {
+            if depth == 0 && tag.name.eq_ignore_ascii_case(name) {
+                inner_start = Some(tag.end);
+            }
+            depth += 1;
+        }
+    }
+    children
+}
+
+// Roughly what innerText gives for a fragment: tags dropped, block elements on their own lines,
+// other whitespace collapsed. Buttons are left out, as the scrape leaves out abbreviation.exclude
+fn text(html: &str) -> String {
+    let mut text = String::new();
+    let mut offset = 0;
+    let mut buttons = 0usize;
+    for tag in tags(html) {
+        if buttons == 0 {
+            text.push_str(&collapse(&html[offset..tag.start]));
+        }
+        if tag.name.eq_ignore_ascii_case("button") && !tag.empty {
+            buttons = if tag.closing { buttons.saturating_sub(1) } else { buttons + 1 };
+        }
+        if BLOCK_ELEMENTS.iter().any(|block| block.eq_ignore_ascii_case(tag.name)) {
+            text.push('\n');
+        }
+        offset = tag.end;
+    }
+    text.push_str(&collapse(&html[offset..]));
+
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Runs of whitespace become one space, kept at the edges so words either side of a tag stay apart
+fn collapse(text: &str) -> String {
+    let decoded = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+
+    let mut collapsed = String::new();
+    let mut space = false;
+    for c in decoded.chars() {
+        if c.is_whitespace() {
+            space = true;
+        } else {
+            if space {
+                collapsed.push(' ');
+                space = false;
+            }
+            collapsed.push(c);
+        }
+    }
+    if space {
+        collapsed.push(' ');
+    }
+    collapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../fixtures/higher_summits.html");
+
+    fn config() -> InterceptConfig {
+        toml::from_str(r#"url_contains = ["Outlook"]"#).unwrap()
+    }
+
+    #[test]
+    fn keeps_every_paragraph_of_a_multi_paragraph_forecast() {
+        let (full, day_blocks) = extract_forecast(FIXTURE, &config()).unwrap();
+        let paragraphs = full.split("\n\n").collect::<Vec<_>>();
+        assert_eq!(paragraphs.len(), 3, "{}", full);
+        assert_eq!(paragraphs[0], "Issued: 5:07 AM Saturday, February 10, 2024");
+        assert!(paragraphs[1].starts_with("Arctic air settles over the higher summits today"));
+        assert!(paragraphs[1].ends_with("Wind chills will fall to 40 below zero by tonight."));
+        assert!(paragraphs[2].starts_with("High pressure builds in Sunday"));
+        assert!(paragraphs[2].ends_with("well below zero through the day."));
+        let labels = day_blocks.iter().filter_map(|block| block.lines().next()).collect::<Vec<_>>();
+        assert!(labels.ends_with(&["Today:", "Tonight:", "Sunday:"]), "{:?}", labels);
+    }
+
+    #[test]
+    fn reads_the_forecast_out_of_json() {
+        let body = serde_json::json!({ "d": { "html": FIXTURE } }).to_string();
+        let config = InterceptConfig {
+            json_pointer: Some("/d/html".to_string()),
+            ..config()
+        };
+        let (full, _) = extract_forecast(&body, &config).unwrap();
+        assert_eq!(full.split("\n\n").count(), 3);
+        assert_eq!(extract_forecast("<p>no outlook here</p>", &config), None);
+    }
+
+    #[test]
+    fn leaves_out_stray_buttons() {
+        let html = r#"<div id="SummitOutlook">
+<div class="outlook-controls"><button type="button">Expand All</button></div>
+<p>Issued: 5:07 AM Saturday, February 10, 2024</p>
+<p>Skies will show more sun by Sunday.</p>
+<div>Today:<br>Highs around 5 below zero. <button type="button" class="more">Read More</button></div>
+<div>Tonight:<br>Lows around 20 below zero.</div>
+</div>"#;
+        let (full, day_blocks) = extract_forecast(html, &config()).unwrap();
+        assert!(full.contains("Skies will show more sun by Sunday."), "{}", full);
+        assert!(!full.contains("Expand All") && !full.contains("Read More"), "{}", full);
+        assert_eq!(day_blocks, ["Today:\nHighs around 5 below zero.", "Tonight:\nLows around 20 below zero."]);
+    }
+}
@@ -0,0 +1,52 @@
+pub mod abbreviation;
+pub mod archive;
+pub mod backfill;
+pub mod capture;
+pub mod change;
+pub mod clock;
+pub mod conditions;
+pub mod config;
+pub mod cycle;
+pub mod delivery;
+pub mod digest;
+pub mod drift;
+pub mod effects;
+pub mod error;
+pub mod estimate;
+pub mod export;
+pub mod fetch;
+pub mod forecast;
+pub mod format;
+pub mod html;
+pub mod inreach;
+pub mod intercept;
+pub mod locale;
+pub mod note;
+pub mod notify;
+pub mod nws;
+pub mod ocr;
+pub mod output;
+pub mod pool;
+pub mod preview;
+pub mod reload;
+pub mod replay;
+pub mod retry;
+pub mod routing;
+pub mod schedule;
+pub mod selector_check;
+pub mod self_test;
+pub mod sms;
+pub mod source;
+pub mod state;
+pub mod storage;
+pub mod summary;
+pub mod systemd;
+pub mod trend;
+pub mod validation;
+pub mod verify;
+pub mod version;
+pub mod watch;
+pub mod wind;
+pub mod windows;
+
+pub use crate::watch::{spawn_watch, watch, ForecastEvent, ShutdownHandle};
@@ -0,0 +1,182 @@
+use std::str::FromStr;
+
+use crate::error::RavenError;
+use crate::wind::Direction;
+
+pub const SUPPORTED_LANGUAGES: &[&str] = &["en", "fr"];
+
+// Language of raven's own compact renderings, the scraped narrative is always left as published
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    En,
+    Fr,
+}
+
+// Everything raven writes itself in the compact and structured renderings. Kept to ASCII since
+// the inReach drops anything else
+pub struct Strings {
+    pub high: &'static str,
+    pub low: &'static str,
+    pub wind: &'static str,
+    // Long forms, for the per-day files
+    pub high_label: &'static str,
+    pub low_label: &'static str,
+    pub wind_label: &'static str,
+    pub issued: &'static str,
+    pub parts: &'static str,
+    pub was: &'static str,
+    pub wind_direction_now: &'static str,
+    pub not_available: &'static str,
+    pub summit_now: &'static str,
+    pub at: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+    pub steady: &'static str,
+    // Words of the page's period labels ("Saturday Night") and their translation
+    pub label_words: &'static [(&'static str, &'static str)],
+    // In `Direction` order, N first, clockwise
+    pub directions: [&'static str; 16],
+    // The compact rendering's fixed phrases by what they stand for, e.g. ("in the clouds", "in clds")
+    pub abbreviations: &'static [(&'static str, &'static str)],
+}
+
+const EN: Strings = Strings {
+    high: "hi",
+    low: "lo",
+    wind: "wind",
+    high_label: "High",
+    low_label: "Low",
+    wind_label: "Wind",
+    issued: "Issued",
+    parts: "parts",
+    was: "was",
+    wind_direction_now: "wind direction now",
+    not_available: "n/a",
+    summit_now: "Summit now",
+    at: "at",
+    up: "up",
+    down: "down",
+    steady: "steady",
+    label_words: &[],
+    directions: [
+        "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW", "NW", "NNW",
+    ],
+    abbreviations: &[("gusts", "G"), ("wind chill", "wc"), ("in the clouds", "in clds")],
+};
+
+const FR: Strings = Strings {
+    high: "max",
+    low: "min",
+    wind: "vent",
+    high_label: "Max",
+    low_label: "Min",
+    wind_label: "Vent",
+    issued: "Emis",
+    parts: "parties",
+    was: "avant",
+    wind_direction_now: "direction du vent maintenant",
+    not_available: "n/d",
+    summit_now: "Sommet maintenant",
+    at: "a",
+    up: "hausse",
+    down: "baisse",
+    steady: "stable",
+    label_words: &[
+        ("today", "Aujourd'hui"),
+        ("tonight", "Ce soir"),
+        ("tomorrow", "Demain"),
+        ("night", "soir"),
+        ("monday", "Lundi"),
+        ("tuesday", "Mardi"),
+        ("wednesday", "Mercredi"),
+        ("thursday", "Jeudi"),
+        ("friday", "Vendredi"),
+        ("saturday", "Samedi"),
+        ("sunday", "Dimanche"),
+    ],
+    directions: [
+        "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSO", "SO", "OSO", "O", "ONO", "NO", "NNO",
+    ],
+    abbreviations: &[("gusts", "raf"), ("wind chill", "refr"), ("in the clouds", "ds nuages")],
+};
+
+impl Language {
+    pub fn strings(self) -> &'static Strings {
+        match self {
+            Language::En => &EN,
+            Language::Fr => &FR,
+        }
+    }
+
+    // Translates a period label word by word, e.g. "Saturday Night" -> "Samedi soir"
+    pub fn label(self, label: &str) -> String {
+        let words = self.strings().label_words;
+        if words.is_empty() {
+            return label.to_string();
+        }
+
+        label
+            .split_whitespace()
+            .map(|word| {
+                let lower = word.to_lowercase();
+                words
+                    .iter()
+                    .find(|(english, _)| *english == lower)
+                    .map(|(_, translated)| translated.to_string())
+                    .unwrap_or_else(|| word.to_string())
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // The phrase itself when the language has no abbreviation for it
+    pub fn abbreviation(self, phrase: &'static str) -> &'static str {
+        self.strings()
+            .abbreviations
+            .iter()
+            .find(|(long, _)| *long == phrase)
+            .map_or(phrase, |(_, short)| short)
+    }
+
+    pub fn direction(self, direction: Direction) -> &'static str {
+        self.strings().directions[direction.index()]
+    }
+}
+
+impl FromStr for Language {
+    type Err = RavenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "en" => Ok(Language::En),
+            "fr" => Ok(Language::Fr),
+            _ => Err(RavenError::InvalidConfig(format!(
+                "unknown language {}, supported languages are {}",
+                s,
+                SUPPORTED_LANGUAGES.join(", ")
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_language_abbreviates_the_same_phrases() {
+        let phrases = |lang: Language| lang.strings().abbreviations.iter().map(|(long, _)| *long).collect::<Vec<_>>();
+        assert_eq!(phrases(Language::Fr), phrases(Language::En));
+        assert_eq!(Language::En.abbreviation("in the clouds"), "in clds");
+        assert_eq!(Language::Fr.abbreviation("in the clouds"), "ds nuages");
+        assert_eq!(Language::Fr.abbreviation("sunshine"), "sunshine");
+    }
+
+    #[test]
+    fn unknown_languages_list_the_supported_ones() {
+        let error = "de".parse::<Language>().unwrap_err().to_string();
+        assert!(error.contains("en, fr"), "{}", error);
+        assert_eq!(Language::Fr.label("Saturday Night"), "Samedi soir");
+    }
+}
@@ -1,135 +1,562 @@
-use std::collections::hash_map::DefaultHasher;
 use std::error::Error;
-use std::{env, fs};
-use std::fs::File;
-use std::hash::{Hash, Hasher};
-use std::io::Write;
-use std::path::Path;
-use std::sync::Arc;
-use std::thread::sleep;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
-use headless_chrome::{Browser, LaunchOptions, Tab};
+use std::{env, fs, process};
+use log::info;
+use structopt::StructOpt;
 
-const FORECAST_FULL_PATH: &str = "forecast_full.txt";
-const FORECAST_ABBREVIATED_PATH: &str = "forecast_abbreviated.txt";
+use raven::archive::{load_revisions, verify_archive};
+use raven::backfill::{backfill, render_report, BackfillOptions};
+use raven::conditions::load_observations;
+use raven::config::{Config, Overrides};
+use raven::cycle::{run_cycle, STATE_PATH};
+use raven::digest::{parse_since, render_digest, DigestFormat};
+use raven::effects::Effects;
+use raven::error::RavenError;
+use raven::estimate::render_expected;
+use raven::export::{parse_columns, render_export, ExportFormat};
+use raven::fetch::launch_browser;
+use raven::inreach::log_in_interactively;
+use raven::note::{active_note, clear_note, reset_note, set_note};
+use raven::output::{render_provenance, OutputFiles};
+use raven::preview::{load_stored, preview, render_preview, PreviewStyle, Stored};
+use raven::reload::ConfigReloader;
+use raven::replay::replay;
+use raven::retry::Operation;
+use raven::selector_check::validate_selectors;
+use raven::self_test::self_test;
+use raven::sms::{prepare_messages, sanitize};
+use raven::source::BrowserSource;
+use raven::systemd::{render_unit, Systemd, UnitOptions};
+use raven::verify::{parse_day, render_verification, VerifyFormat};
+use raven::version::{record_run, render_version};
+use raven::watch::shutdown_channel;
+use raven::windows::{default_data_dir, render_task, TaskOptions};
+use raven::ForecastEvent;
 
-fn main() -> Result<(), Box<dyn Error>> {
-    loop {
-        let browser = Browser::new(LaunchOptions {
-            headless: true, // For debugging
-            ..Default::default()
-        })?;
+const DEFAULT_CONFIG_PATH: &str = "raven.toml";
 
-        let tab = browser.wait_for_initial_tab()?;
+#[derive(StructOpt)]
+#[structopt(about = "Watches the Mount Washington higher summits forecast for changes")]
+struct Opt {
+    /// The config file, relative to the directory raven was started in. Defaults to raven.toml in the data directory
+    #[structopt(long, global = true, parse(from_os_str))]
+    config: Option<PathBuf>,
 
-        // Navigate to higher summits forecast
-        tab.navigate_to("https://www.mountwashington.org/experience-the-weather/higher-summit-forecast.aspx")?;
+    /// Keep outputs, the archive and state here, relative paths in the config are read from it too. Defaults to the
+    /// working directory, or %LOCALAPPDATA%\raven on Windows
+    #[structopt(long, global = true, parse(from_os_str))]
+    data_dir: Option<PathBuf>,
 
-        // Wait for network/javascript/dom to load forecast
-        tab.wait_for_element("div#SummitOutlook")?.click()?;
+    /// Print the whole forecast body when it changes, not just a headline
+    #[structopt(long, global = true)]
+    show_forecast: bool,
 
-        // Fetch forecasts
-        let full_forecast = fetch_higher_summits_forecast(&tab).expect("Could not fetch forecast");
-        let abbreviated_forecast = fetch_abbreviated_forecast(&tab)?;
+    /// Fetch and compare as usual, but only log the files that would be written and messages that would be sent
+    #[structopt(long, global = true)]
+    dry_run: bool,
 
-        // Setup if no files exist and it's the first run
-        setup(&full_forecast, &abbreviated_forecast);
+    /// Save the page, a screenshot and the parsed output here whenever a fetched forecast is rejected
+    #[structopt(long, global = true, parse(from_os_str))]
+    debug_capture_dir: Option<PathBuf>,
 
-        // Only update files + print out forecasts if they have changed
-        if hash(&full_forecast) != hash(&fs::read_to_string(FORECAST_FULL_PATH)?) {
-            persist_forecast(&full_forecast, FORECAST_FULL_PATH)?;
-        }
+    /// Language of the compact renderings, en or fr (overrides the config file)
+    #[structopt(long, global = true)]
+    lang: Option<String>,
 
-        if hash(&abbreviated_forecast) != hash(&fs::read_to_string(FORECAST_ABBREVIATED_PATH)?) {
-            persist_forecast(&abbreviated_forecast, FORECAST_ABBREVIATED_PATH)?;
+    /// Only send the first N days, e.g. 3 for tonight and the next two (overrides the config file)
+    #[structopt(long, global = true)]
+    days: Option<usize>,
 
-            // TODO: Add CLI toggle flag for actually sending sat messages
-            //send_message_to_inreach(tab, abbreviated_forecast).expect("Could not send message to inreach");
-        }
+    /// Append this line to every forecast sent (overrides the config file, `raven note` overrides both)
+    #[structopt(long, global = true)]
+    note: Option<String>,
 
-        // Check again for updates in 1 minute
-        sleep(Duration::from_secs(60));
-    }
-}
+    /// Allow polling intervals below the 30 second minimum
+    #[structopt(long = "i-know-what-im-doing", global = true)]
+    allow_fast_polling: bool,
 
-fn setup(full_forecast: &String, abbreviated_forecast: &String) {
-    // Base condition, no forecasts exists. TODO: Cleanup
-    if !Path::new(FORECAST_FULL_PATH).exists() {
-        persist_forecast(&full_forecast, FORECAST_FULL_PATH).expect("Could not write full forecast");
-    }
-    if !Path::new(FORECAST_ABBREVIATED_PATH).exists() {
-        persist_forecast(&abbreviated_forecast, FORECAST_ABBREVIATED_PATH).expect("Could not write abbreviated forecast");
-    }
+    #[structopt(subcommand)]
+    command: Option<Command>,
 }
 
-// This full, detailed summary is often around ~2k characters
-fn fetch_higher_summits_forecast(tab: &Arc<Tab>) -> Result<String, Box<dyn Error>> {
-    let elem = tab.wait_for_element("#SummitOutlook > p")?;
-
-    // Snag larger forecast
-    let forecast = elem.get_inner_text().unwrap();
-
-    Ok(forecast)
+#[derive(StructOpt)]
+enum Command {
+    /// Poll the forecast (every minute by default) and persist changes (the default). Changes to the config file
+    /// are picked up before the next cycle, except to user_agent, browser_path, output, archive and retry
+    Watch {
+        /// Actually send changed forecasts to the inReach, spending satellite messages. Off by default: without
+        /// it raven only writes the files and notifies the other configured channels
+        #[structopt(long)]
+        send: bool,
+    },
+    /// Fetch the forecast once and persist any changes
+    Fetch,
+    /// Check that every configured selector still resolves on the live site, without sending anything
+    Validate,
+    /// Check this deployment offline against a bundled forecast: config, parsing, change detection, message
+    /// rendering and writable paths, then connect to any configured notifiers without sending. Exits non-zero
+    /// on any failure
+    SelfTest,
+    /// Summarize how the archived forecast evolved, entirely offline
+    Digest {
+        /// How far back to look, e.g. 7d, 12h or 2w
+        #[structopt(long, default_value = "7d")]
+        since: String,
+        /// text or markdown
+        #[structopt(long, default_value = "text")]
+        format: DigestFormat,
+        /// Write the digest here instead of stdout
+        #[structopt(long, parse(from_os_str))]
+        out: Option<PathBuf>,
+    },
+    /// Compare each archived revision's high, low and wind for a day with the sampled summit conditions, entirely
+    /// offline
+    Verify {
+        /// The day to verify, e.g. 2024-02-10
+        #[structopt(long)]
+        day: String,
+        /// text or csv
+        #[structopt(long, default_value = "text")]
+        format: VerifyFormat,
+        /// Write the report here instead of stdout
+        #[structopt(long, parse(from_os_str))]
+        out: Option<PathBuf>,
+    },
+    /// Export every archived revision's days as a table, one row per revision per day, entirely offline. Safe to
+    /// run while raven watch is archiving
+    Export {
+        /// csv
+        #[structopt(long, default_value = "csv")]
+        format: ExportFormat,
+        /// How far back to look, e.g. 30d, 12h or 2w, defaults to the whole archive
+        #[structopt(long)]
+        since: Option<String>,
+        /// Comma separated, from archived, issued, day, label, high_f, low_f, wind_min_mph, wind_max_mph,
+        /// gust_mph, wind_chill_f, precip and in_clouds. Defaults to all of them in that order
+        #[structopt(long)]
+        columns: Option<String>,
+        /// Write the export here instead of stdout
+        #[structopt(long, parse(from_os_str))]
+        out: Option<PathBuf>,
+    },
+    /// Print the inReach messages a stored forecast would be sent as, through the same parsing and message
+    /// preparation as a live send, without a browser
+    Render {
+        /// An archived revision's JSON, or forecast_abbreviated.txt
+        #[structopt(long, parse(from_os_str), required_unless = "archived", conflicts_with = "archived")]
+        from: Option<PathBuf>,
+        /// The archived revision with this timestamp, or the start of it, e.g. 20240210T1007
+        #[structopt(long)]
+        archived: Option<String>,
+        /// abbreviated (what is sent), compact or full
+        #[structopt(long, default_value = "abbreviated")]
+        style: PreviewStyle,
+        /// Flag the messages past this many and exit non-zero when there are more
+        #[structopt(long)]
+        max_messages: Option<usize>,
+    },
+    /// Archive the Wayback Machine's snapshots of the forecast page between two dates, for the digest and
+    /// verification. Plain HTTP, resumes where an interrupted run stopped
+    Backfill {
+        /// The first day, e.g. 2023-12-01
+        #[structopt(long)]
+        from: String,
+        /// The last day, included
+        #[structopt(long)]
+        to: String,
+        /// Seconds between requests to the Wayback Machine
+        #[structopt(long, default_value = "5")]
+        delay_seconds: u64,
+    },
+    /// Feed archived revisions through change detection, alerting and message preparation, sending nothing
+    Replay {
+        #[structopt(long, default_value = "archive", parse(from_os_str))]
+        from: PathBuf,
+    },
+    /// Show where each output file's content came from: when and where it was fetched, its issue time, the raven
+    /// version that wrote it and whether it went out to the inReach. Also when the next update is expected, going by
+    /// when updates have been seen
+    Status,
+    /// Change the note appended to every forecast sent, a running raven picks it up on its next send
+    Note(NoteCommand),
+    /// Maintain the forecast archive
+    Archive(ArchiveCommand),
+    /// Log in to Garmin in a visible browser for inreach.transport = "account", e.g. when Garmin wants a verification
+    /// code. Later sends reuse the session
+    GarminLogin,
+    /// Print raven's version and the commit it was built from
+    Version {
+        /// Also the effective config's hash, per section, and what the last run recorded
+        #[structopt(long)]
+        verbose: bool,
+    },
+    /// Print a systemd unit running `raven watch` with the current config and working directory
+    SystemdUnit {
+        /// User to run as, defaults to the current user
+        #[structopt(long)]
+        user: Option<String>,
+        #[structopt(long, default_value = "on-failure")]
+        restart: String,
+        /// Have the unit send changed forecasts to the inReach
+        #[structopt(long)]
+        send: bool,
+    },
+    /// Print a Windows Task Scheduler task running `raven watch` from boot, import it with schtasks /create /xml
+    ScheduledTask {
+        /// DOMAIN\user to run as, defaults to the current user
+        #[structopt(long)]
+        user: Option<String>,
+        /// Have the task send changed forecasts to the inReach
+        #[structopt(long)]
+        send: bool,
+    },
 }
 
-// This abbreviated forecast is typically around ~700 characters
-fn fetch_abbreviated_forecast(tab: &Arc<Tab>) -> Result<String, Box<dyn Error>> {
-    // Collect information for all days into one string
-    let abbreviated_forecast = tab.wait_for_elements("#SummitOutlook > div")?
-        .into_iter()
-        .map(|e| e.get_inner_text().unwrap().replace("\n", ""))
-        .collect::<Vec<_>>()
-        .join(" ");
-
-    Ok(abbreviated_forecast)
+#[derive(StructOpt)]
+enum NoteCommand {
+    /// Append this line to every forecast sent from now on
+    Set { note: String },
+    /// Send no note, the configured one included, until the next set or reset
+    Clear,
+    /// Go back to the note from the config file or --note
+    Reset,
+    /// Print the note the next send will carry
+    Show,
 }
 
-fn persist_forecast(forecast: &String, filename: &str) -> Result<(), Box<dyn Error>> {
-    // Dump the forecast to console
-    println!("{}", forecast);
-
-    // Write forecast to disk
-    let mut output = File::create(filename)?;
-    write!(output, "{}", forecast)?;
-    Ok(())
+#[derive(StructOpt)]
+enum ArchiveCommand {
+    /// Check that the index and bundles agree and every entry reads back, exits non-zero on problems
+    Verify,
 }
 
-fn send_message_to_inreach(tab: Arc<Tab>, forecast: String) -> Result<(), Box<dyn Error>> {
-    // Navigate to a verified URL
-    tab.navigate_to(&env::var("GARMIN_MESSAGE_REPLY_URL").expect("Could not fetch value for envvar GARMIN_MESSAGE_REPLY_URL"))?;
+fn main() -> Result<(), Box<dyn Error>> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
-    // Activate the textarea
-    tab.wait_for_element("#ReplyMessage")?.click()?;
-    tab.press_key("Enter")?;
+    let opt = Opt::from_args();
+    // Before moving to the data directory, which the default is in
+    let config_path = match &opt.config {
+        Some(config) => env::current_dir()?.join(config),
+        None => PathBuf::from(DEFAULT_CONFIG_PATH),
+    };
+    if let Some(data_dir) = opt.data_dir.clone().or_else(default_data_dir) {
+        fs::create_dir_all(&data_dir)?;
+        env::set_current_dir(&data_dir)?;
+    }
+    let overrides = Overrides {
+        debug_capture_dir: opt.debug_capture_dir,
+        lang: opt.lang,
+        days: opt.days,
+        note: opt.note,
+    };
+    let mut config = Config::load(&config_path)?;
+    overrides.apply(&mut config);
+    // The self-test reports an invalid config as one of its stages
+    if !matches!(opt.command, Some(Command::SelfTest)) {
+        config.validate(opt.allow_fast_polling)?;
+    }
+    let effects = Effects::new(opt.dry_run).with_persist_retry(config.retry.policy(Operation::Persist));
 
-    // Split into SMS message size
-    let sub_string = split_string_into_sms_message_lengths(&forecast);
+    let command = opt.command.unwrap_or(Command::Watch { send: false });
+    if matches!(command, Command::Watch { .. } | Command::Fetch) {
+        record_run(&config, &effects)?;
+    }
 
-    println!("Safe: {:?}", sub_string.len());
-    println!("Safe: {:?}", sub_string);
+    match command {
+        Command::Watch { send } => {
+            let reloader = ConfigReloader::new(&config_path, overrides, opt.allow_fast_polling, &effects);
+            watch(&config, &effects, send, opt.show_forecast, reloader)
+        }
+        Command::Fetch => {
+            let outcome = run_cycle(&config, &effects, &mut BrowserSource::new(), false)?;
+            for event in ForecastEvent::from_outcome(&outcome) {
+                report(&event, &effects, opt.show_forecast);
+            }
+            outcome.notifications.wait(&effects, Path::new(STATE_PATH))
+        }
+        Command::Validate => {
+            let browser = launch_browser(&config)?;
+            let tab = browser.wait_for_initial_tab()?;
+            if !validate_selectors(&tab, &config)? {
+                process::exit(1);
+            }
+            Ok(())
+        }
+        Command::SelfTest => {
+            let stages = self_test(&config, opt.allow_fast_polling);
+            for stage in &stages {
+                match &stage.result {
+                    Ok(detail) => println!("ok   {}: {}", stage.name, detail),
+                    Err(e) => println!("FAIL {}: {}", stage.name, e),
+                }
+            }
+            if stages.iter().any(|stage| stage.result.is_err()) {
+                process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Digest { since, format, out } => {
+            let since = effects.now() - parse_since(&since)?;
+            let revisions = load_revisions(&effects, &config.archive.dir, Some(since))?;
+            let digest = render_digest(&revisions, since, format);
+            match out {
+                Some(path) => effects.write(&path, &digest)?,
+                None => print!("{}", digest),
+            }
+            Ok(())
+        }
+        Command::Export {
+            format,
+            since,
+            columns,
+            out,
+        } => {
+            let columns = parse_columns(columns.as_deref())?;
+            let since = since.map(|since| parse_since(&since)).transpose()?.map(|since| effects.now() - since);
+            let revisions = load_revisions(&effects, &config.archive.dir, since)?;
+            let export = render_export(&revisions, &columns, format);
+            match out {
+                // Never a half-written file for a spreadsheet to pick up
+                Some(path) => effects.write_atomic(&path, &export)?,
+                None => print!("{}", export),
+            }
+            Ok(())
+        }
+        Command::Verify { day, format, out } => {
+            let day = parse_day(&day)?;
+            let revisions = load_revisions(&effects, &config.archive.dir, None)?;
+            let report = render_verification(&revisions, &load_observations(&effects), day, format);
+            match out {
+                Some(path) => effects.write(&path, &report)?,
+                None => print!("{}", report),
+            }
+            Ok(())
+        }
+        Command::Render {
+            from,
+            archived,
+            style,
+            max_messages,
+        } => {
+            let stored = match (&from, &archived) {
+                (Some(from), _) => Stored::File(from),
+                (None, Some(archived)) => Stored::Archived(archived),
+                (None, None) => return Err(RavenError::InvalidArgument("give --from or --archived".to_string()).into()),
+            };
+            let fetched = load_stored(&effects, &config, stored)?;
+            let messages = preview(&config, &effects, &fetched, style);
+            print!("{}", render_preview(&messages, max_messages));
+            if max_messages.is_some_and(|max| messages.len() > max) {
+                process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Backfill {
+            from,
+            to,
+            delay_seconds,
+        } => {
+            let options = BackfillOptions {
+                from: parse_day(&from)?,
+                to: parse_day(&to)?,
+                delay: Duration::from_secs(delay_seconds),
+            };
+            print!("{}", render_report(&backfill(&config, &effects, &options)?));
+            Ok(())
+        }
+        Command::Replay { from } => {
+            print!("{}", replay(&config, &effects, &from)?);
+            Ok(())
+        }
+        Command::Status => {
+            print!("{}", render_version(&config, &effects, false));
+            print!("{}", render_expected(&config, &effects));
+            print!("{}", render_provenance(&effects, &OutputFiles::default()));
+            for product in &config.products {
+                println!("[{}]", product.name);
+                print!("{}", render_provenance(&effects, &OutputFiles::with_prefix(&product.prefix)));
+            }
+            Ok(())
+        }
+        Command::Note(command) => {
+            match command {
+                NoteCommand::Set { note } => {
+                    if note.trim().is_empty() {
+                        let message = "the note is empty, use raven note clear".to_string();
+                        return Err(RavenError::InvalidArgument(message).into());
+                    }
+                    set_note(&effects, &note)?;
+                }
+                NoteCommand::Clear => clear_note(&effects)?,
+                NoteCommand::Reset => reset_note(&effects)?,
+                NoteCommand::Show => {}
+            }
+            match active_note(&effects, config.inreach.note.as_deref()) {
+                Some(note) => println!("{} ({} chars as sent)", note, sanitize(&note).chars().count()),
+                None => println!("No note"),
+            }
+            Ok(())
+        }
+        Command::GarminLogin => {
+            log_in_interactively(&config)?;
+            println!("Logged in, sends will reuse the session in {}", config.inreach.account.profile_dir.display());
+            Ok(())
+        }
+        Command::Version { verbose } => {
+            print!("{}", render_version(&config, &effects, verbose));
+            Ok(())
+        }
+        Command::Archive(ArchiveCommand::Verify) => {
+            let report = verify_archive(&effects, &config.archive.dir)?;
+            for note in &report.notes {
+                println!("NOTE {}", note);
+            }
+            for problem in &report.problems {
+                println!("FAIL {}", problem);
+            }
+            println!("Checked {} entries, {} problems", report.checked, report.problems.len());
+            if !report.problems.is_empty() {
+                process::exit(1);
+            }
+            Ok(())
+        }
+        Command::SystemdUnit { user, restart, send } => {
+            let working_directory = env::current_dir()?;
+            let user = user.or_else(|| env::var("USER").ok());
+            let unit = render_unit(
+                &config,
+                &UnitOptions {
+                    executable: &env::current_exe()?,
+                    config_path: &working_directory.join(&config_path),
+                    working_directory: &working_directory,
+                    user: user.as_deref(),
+                    restart: &restart,
+                    send,
+                },
+            );
+            print!("{}", unit);
+            Ok(())
+        }
+        Command::ScheduledTask { user, send } => {
+            let data_dir = env::current_dir()?;
+            let user = user.unwrap_or_else(|| {
+                let name = env::var("USERNAME").unwrap_or_default();
+                match env::var("USERDOMAIN") {
+                    Ok(domain) => format!("{}\\{}", domain, name),
+                    Err(_) => name,
+                }
+            });
+            let task = render_task(
+                &config,
+                &TaskOptions {
+                    executable: &env::current_exe()?,
+                    config_path: &data_dir.join(&config_path),
+                    data_dir: &data_dir,
+                    user: &user,
+                    send,
+                },
+            );
+            print!("{}", task);
+            Ok(())
+        }
+    }
+}
 
-    tab.type_str(forecast.as_str())?;
+fn watch(
+    config: &Config,
+    effects: &Effects,
+    send: bool,
+    show_forecast: bool,
+    mut reloader: ConfigReloader,
+) -> Result<(), Box<dyn Error>> {
+    let systemd = Systemd::from_env();
+    let (handle, shutdown) = shutdown_channel();
+    let stopping = systemd.clone();
+    #[cfg(windows)]
+    let closing = handle.clone();
+    ctrlc::set_handler(move || {
+        info!("Shutting down");
+        stopping.stopping();
+        handle.shutdown();
+    })?;
+    // Registered after ctrlc's handler so Windows asks it first, held until the loop has returned
+    #[cfg(windows)]
+    let _console = raven::windows::on_console_close(move || {
+        info!("Console closing, shutting down");
+        closing.shutdown();
+    })?;
 
-    // Click send
-    //tab.wait_for_element("#sendBtn")?.click()?;
+    let mut source = BrowserSource::new();
+    let mut ready = false;
+    raven::watch(config, effects, &mut source, send, &shutdown, Some(&mut reloader), |event| {
+        if !ready && !matches!(event, ForecastEvent::FetchFailed { .. }) {
+            systemd.ready();
+            ready = true;
+        }
+        systemd.watchdog();
+        report(&event, effects, show_forecast);
+    })
+}
 
-    Ok(())
+fn report(event: &ForecastEvent, effects: &Effects, show_forecast: bool) {
+    match event {
+        ForecastEvent::Changed {
+            new,
+            full,
+            abbreviated,
+            alerts,
+            ..
+        } => {
+            let issued = new.issued.as_deref().unwrap_or("n/a");
+            if let Some(full) = full {
+                print_forecast("Full forecast", issued, full, show_forecast);
+            }
+            if let Some(abbreviated) = abbreviated {
+                print_forecast("Abbreviated forecast", issued, abbreviated, show_forecast);
+            }
+            for alert in alerts {
+                println!("Trend alert: {}", alert);
+            }
+        }
+        ForecastEvent::Sent { chunks } if !effects.is_dry_run() => {
+            println!("Sent {} inReach messages", chunks.len());
+        }
+        ForecastEvent::Delivery { check } => {
+            println!("inReach delivery: {}", check.summary());
+        }
+        ForecastEvent::Product { name, event } => {
+            if let ForecastEvent::Changed { .. } = **event {
+                println!("[{}]", name);
+            }
+            report(event, effects, show_forecast);
+        }
+        _ => {}
+    }
 }
 
+// A labelled headline, e.g. "== Full forecast: issued 5:07 AM, 2113 chars, 14 SMS ==", then the body if asked for
+fn print_forecast(label: &str, issued: &str, forecast: &str, show_forecast: bool) {
+    println!(
+        "== {}: issued {}, {} chars, {} SMS ==",
+        label,
+        issued,
+        forecast.chars().count(),
+        prepare_messages(forecast).len()
+    );
 
-fn hash<T: Hash>(t: &T) -> u64 {
-    let mut s = DefaultHasher::new();
-    t.hash(&mut s);
-    s.finish()
+    if show_forecast {
+        println!("{}", forecast);
+    } else {
+        println!("{}", first_sentence(forecast));
+    }
 }
 
-fn split_string_into_sms_message_lengths(forecast: &String) -> Vec<String> {
-    const TEXT_MESSAGE_LENGTH: usize = 160;
-    let mut chars = forecast.chars();
-    let sub_string = (0..)
-        .map(|_| chars.by_ref().take(TEXT_MESSAGE_LENGTH).collect::<String>())
-        .take_while(|s| !s.is_empty())
-        .collect::<Vec<_>>();
-    sub_string
+fn first_sentence(text: &str) -> &str {
+    let text = text.trim();
+    let end = text
+        .find(['.', '\n'])
+        .map(|i| i + 1)
+        .unwrap_or_else(|| text.len());
+    text[..end].trim()
 }
-
@@ -0,0 +1,65 @@
+use std::error::Error;
+use std::path::Path;
+
+use crate::effects::Effects;
+
+// Written by `raven note`, apart from the state file so a running raven saving its state can't undo
+// it. Empty once cleared, missing when the configured note applies
+pub const NOTE_PATH: &str = "raven_note.txt";
+
+// The note every forecast send carries, None when there isn't one
+pub fn active_note(effects: &Effects, configured: Option<&str>) -> Option<String> {
+    effects
+        .read(Path::new(NOTE_PATH))
+        .as_deref()
+        .or(configured)
+        .map(str::trim)
+        .filter(|note| !note.is_empty())
+        .map(str::to_string)
+}
+
+pub fn set_note(effects: &Effects, note: &str) -> Result<(), Box<dyn Error>> {
+    effects.write_atomic(Path::new(NOTE_PATH), note)
+}
+
+// No note at all, the configured one included
+pub fn clear_note(effects: &Effects) -> Result<(), Box<dyn Error>> {
+    effects.write_atomic(Path::new(NOTE_PATH), "")
+}
+
+// Back to the configured note
+pub fn reset_note(effects: &Effects) -> Result<(), Box<dyn Error>> {
+    if effects.exists(Path::new(NOTE_PATH)) {
+        effects.remove(Path::new(NOTE_PATH))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cycle::STATE_PATH;
+    use crate::state::State;
+    use crate::storage::MemoryStorage;
+    use std::sync::Arc;
+
+    #[test]
+    fn a_running_raven_saving_its_state_keeps_the_note() {
+        let effects = Effects::with_storage(false, Arc::new(MemoryStorage::new()));
+        let state = State::load(&effects, Path::new(STATE_PATH));
+        set_note(&effects, "Bail via Lion Head if winds >80").unwrap();
+        state.save(&effects, Path::new(STATE_PATH)).unwrap();
+        assert_eq!(active_note(&effects, Some("configured")).as_deref(), Some("Bail via Lion Head if winds >80"));
+    }
+
+    #[test]
+    fn clear_drops_the_configured_note_until_reset() {
+        let effects = Effects::with_storage(false, Arc::new(MemoryStorage::new()));
+        assert_eq!(active_note(&effects, Some("configured")).as_deref(), Some("configured"));
+        clear_note(&effects).unwrap();
+        assert_eq!(active_note(&effects, Some("configured")), None);
+        reset_note(&effects).unwrap();
+        assert_eq!(active_note(&effects, Some("configured")).as_deref(), Some("configured"));
+        reset_note(&effects).unwrap();
+    }
+}
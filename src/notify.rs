@@ -0,0 +1,271 @@
+use std::error::Error;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use log::{error, info, warn};
+
+use crate::clock::Clock;
+use crate::config::Config;
+use crate::effects::Effects;
+use crate::format::{render, template, Constraints, Content};
+use crate::retry::Operation;
+use crate::state::{DailyCount, State};
+
+pub use self::twilio::TwilioNotifier;
+
+mod twilio;
+
+// A channel other than the inReach that rendered messages can be delivered to
+pub trait Notifier: Send + Sync {
+    // Unique per recipient, used in logs and to key the daily caps in the state file
+    fn name(&self) -> String;
+    // The names routing rules can select this notifier by
+    fn channels(&self) -> Vec<String>;
+    // None means no cap
+    fn max_messages_per_day(&self) -> Option<u32>;
+    // What the messages have to fit, they're rendered to suit
+    fn constraints(&self) -> Constraints;
+    fn notify(&self, messages: &[String]) -> Result<(), Box<dyn Error>>;
+    // Reaches the channel's endpoint without sending anything, for `raven self-test`
+    fn check(&self) -> Result<(), Box<dyn Error>>;
+}
+
+// Every enabled channel from the config, channels that can't be set up are logged and skipped
+pub fn configured_notifiers(config: &Config, clock: Arc<dyn Clock>) -> Vec<Arc<dyn Notifier>> {
+    let mut notifiers: Vec<Arc<dyn Notifier>> = Vec::new();
+
+    if let Some(twilio) = &config.twilio {
+        let retry = config.retry.policy(Operation::Notify);
+        match TwilioNotifier::for_recipients(twilio, &config.user_agent, &retry, clock) {
+            Ok(twilio_notifiers) => notifiers.extend(
+                twilio_notifiers
+                    .into_iter()
+                    .map(|notifier| Arc::new(notifier) as Arc<dyn Notifier>),
+            ),
+            Err(e) => error!("Twilio is configured but can't be used: {}", e),
+        }
+    }
+
+    notifiers
+}
+
+// Notifications still being delivered in the background, the inReach send never waits on these
+pub struct PendingNotifications {
+    batches: Vec<Batch>,
+}
+
+// One `notify_all`'s channels
+struct Batch {
+    results: Receiver<(String, u32, Result<(), String>)>,
+    expected: usize,
+    // How long it's waited on for, counted from when the waiting starts
+    timeout: chrono::Duration,
+    effects: Effects,
+    // What each channel's messages reserved against its cap, released once waited on or given up
+    reserved: Vec<(String, u32)>,
+}
+
+impl Drop for Batch {
+    fn drop(&mut self) {
+        for (name, count) in &self.reserved {
+            self.effects.release_messages(name, *count);
+        }
+    }
+}
+
+impl PendingNotifications {
+    pub fn none() -> PendingNotifications {
+        PendingNotifications { batches: Vec::new() }
+    }
+
+    // Waits on `other` along with these, e.g. another product's notifications from the same cycle
+    pub fn merge(&mut self, other: PendingNotifications) {
+        self.batches.extend(other.batches);
+    }
+
+    // Collects and logs every channel's result, giving up on channels still running once the
+    // timeout is up, then records what was sent against the daily caps
+    pub fn wait(self, effects: &Effects, state_path: &Path) -> Result<(), Box<dyn Error>> {
+        if self.batches.iter().all(|batch| batch.expected == 0) {
+            return Ok(());
+        }
+
+        let today = effects.clock().local_now().date_naive();
+        let started = effects.now();
+        let mut state = State::load(effects, state_path);
+        for batch in self.batches {
+            let deadline = started + batch.timeout;
+            let mut received = 0;
+            while received < batch.expected {
+                let remaining = (deadline - effects.now()).to_std().unwrap_or(Duration::ZERO);
+                let (name, count, result) = match batch.results.recv_timeout(remaining) {
+                    Ok(result) => result,
+                    Err(_) => break,
+                };
+                received += 1;
+
+                match result {
+                    Ok(()) => {
+                        info!("Notified {}", name);
+                        record(&mut state, &name, today, count);
+                    }
+                    Err(e) => error!("Could not notify {}: {}", name, e),
+                }
+            }
+
+            if received < batch.expected {
+                error!("{} notification channels timed out", batch.expected - received);
+            }
+        }
+
+        state.save(effects, state_path)
+    }
+}
+
+// Renders `content` for every notifier and starts sending to those that still have room in today's
+// cap, each on its own thread so a slow or panicking channel never holds up or takes down the others
+pub fn notify_all(
+    notifiers: Vec<Arc<dyn Notifier>>,
+    config: &Config,
+    effects: &Effects,
+    state: &State,
+    content: &Content,
+) -> PendingNotifications {
+    let today = effects.clock().local_now().date_naive();
+    let (sender, results) = mpsc::channel();
+    let mut expected = 0;
+    let mut reserved = Vec::new();
+
+    for notifier in notifiers {
+        let name = notifier.name();
+        let messages = render(
+            content,
+            notifier.constraints(),
+            template(config, &notifier.channels()),
+            config.language(),
+        );
+        let count = messages.len() as u32;
+        // Other batches this cycle may not have been recorded yet, what they queued counts too
+        if let Some(cap) = notifier.max_messages_per_day() {
+            let sent = state.sent_today(&name, today) + effects.reserved_messages(&name);
+            if sent + count > cap {
+                warn!("Skipping {}, {} of {} messages already sent or queued today", name, sent, cap);
+                continue;
+            }
+        }
+        effects.reserve_messages(&name, count);
+        reserved.push((name.clone(), count));
+
+        let sender = sender.clone();
+        let effects = effects.clone();
+        expected += 1;
+        thread::spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                effects.notify(notifier.as_ref(), &messages).map_err(|e| e.to_string())
+            }))
+            .unwrap_or_else(|_| Err("notifier panicked".to_string()));
+            // The receiver is gone if the channel outlived its timeout
+            let _ = sender.send((name, count, result));
+        });
+    }
+
+    PendingNotifications {
+        batches: vec![Batch {
+            results,
+            expected,
+            timeout: chrono::Duration::seconds(config.notify.timeout_seconds as i64),
+            effects: effects.clone(),
+            reserved,
+        }],
+    }
+}
+
+fn record(state: &mut State, name: &str, today: NaiveDate, count: u32) {
+    let sent = state.sent_today(name, today);
+    state.daily_counts.insert(
+        name.to_string(),
+        DailyCount {
+            date: today,
+            count: sent + count,
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{Charset, Length, Markup};
+    use crate::storage::MemoryStorage;
+    use std::sync::Mutex;
+
+    // One message per notice, three a day
+    #[derive(Default)]
+    struct Phone {
+        received: Mutex<Vec<String>>,
+    }
+
+    impl Notifier for Phone {
+        fn name(&self) -> String {
+            "phone".to_string()
+        }
+
+        fn channels(&self) -> Vec<String> {
+            vec!["twilio".to_string()]
+        }
+
+        fn max_messages_per_day(&self) -> Option<u32> {
+            Some(3)
+        }
+
+        fn constraints(&self) -> Constraints {
+            Constraints {
+                length: Length::Unlimited,
+                charset: Charset::Ascii,
+                markup: Markup::Plain,
+            }
+        }
+
+        fn notify(&self, messages: &[String]) -> Result<(), Box<dyn Error>> {
+            self.received.lock().unwrap().extend(messages.iter().cloned());
+            Ok(())
+        }
+
+        fn check(&self) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn batches_queued_together_share_the_cap() {
+        let config = Config::default();
+        let effects = Effects::with_storage(false, Arc::new(MemoryStorage::new()));
+        let phone = Arc::new(Phone::default());
+        let notifiers = || vec![phone.clone() as Arc<dyn Notifier>];
+        let state = State::default();
+
+        // Nothing is recorded until the wait, the third and fourth still see the first two
+        let mut pending = PendingNotifications::none();
+        for text in ["one", "two", "three", "four"] {
+            pending.merge(notify_all(notifiers(), &config, &effects, &state, &Content::text(text)));
+        }
+        pending.wait(&effects, Path::new("state.json")).unwrap();
+        // Each channel sends on its own thread, in no particular order
+        let mut received = phone.received.lock().unwrap().clone();
+        received.sort();
+        assert_eq!(received, ["one", "three", "two"]);
+        assert_eq!(effects.reserved_messages("phone"), 0);
+
+        let state = State::load(&effects, Path::new("state.json"));
+        let today = effects.clock().local_now().date_naive();
+        assert_eq!(state.sent_today("phone", today), 3);
+        notify_all(notifiers(), &config, &effects, &state, &Content::text("five"))
+            .wait(&effects, Path::new("state.json"))
+            .unwrap();
+        assert_eq!(phone.received.lock().unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,169 @@
+use std::env;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::info;
+use serde::Deserialize;
+use ureq::{Agent, AgentBuilder};
+
+use crate::clock::Clock;
+use crate::config::{TwilioConfig, TwilioRecipient};
+use crate::error::RavenError;
+use crate::format::Constraints;
+use crate::notify::Notifier;
+use crate::retry::{retry_with_clock, RetryPolicy};
+use crate::routing::TWILIO_CHANNEL;
+
+const ACCOUNT_SID_ENV: &str = "TWILIO_ACCOUNT_SID";
+const AUTH_TOKEN_ENV: &str = "TWILIO_AUTH_TOKEN";
+
+// Regular SMS to a phone, one notifier per recipient so caps and failures are tracked separately
+pub struct TwilioNotifier {
+    agent: Agent,
+    account_sid: String,
+    authorization: String,
+    from: String,
+    recipient: TwilioRecipient,
+    max_messages_per_day: Option<u32>,
+    retry: RetryPolicy,
+    // What retries back off on
+    clock: Arc<dyn Clock>,
+}
+
+// Twilio's JSON error body, e.g. {"code": 21211, "message": "The 'To' number is not a valid phone number."}
+#[derive(Deserialize)]
+struct TwilioError {
+    code: Option<u32>,
+    message: String,
+}
+
+impl TwilioNotifier {
+    pub fn for_recipients(
+        config: &TwilioConfig,
+        user_agent: &str,
+        retry: &RetryPolicy,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Vec<TwilioNotifier>, RavenError> {
+        let account_sid = env::var(ACCOUNT_SID_ENV).map_err(|_| RavenError::MissingCredentials {
+            env: ACCOUNT_SID_ENV.to_string(),
+        })?;
+        let auth_token = env::var(AUTH_TOKEN_ENV).map_err(|_| RavenError::MissingCredentials {
+            env: AUTH_TOKEN_ENV.to_string(),
+        })?;
+        let authorization = format!("Basic {}", base64::encode(format!("{}:{}", account_sid, auth_token)));
+
+        let agent = AgentBuilder::new()
+            .user_agent(user_agent)
+            .timeout(Duration::from_secs(30))
+            .build();
+
+        let notifiers = config
+            .recipients
+            .iter()
+            .filter(|recipient| recipient.enabled)
+            .map(|recipient| TwilioNotifier {
+                agent: agent.clone(),
+                account_sid: account_sid.clone(),
+                authorization: authorization.clone(),
+                from: config.from.clone(),
+                recipient: recipient.clone(),
+                max_messages_per_day: config.max_messages_per_day,
+                retry: retry.clone(),
+                clock: clock.clone(),
+            })
+            .collect();
+
+        Ok(notifiers)
+    }
+
+    fn send(&self, url: &str, message: &str) -> Result<(), Box<dyn Error>> {
+        let result = self
+            .agent
+            .post(url)
+            .set("Authorization", &self.authorization)
+            .send_form(&[
+                ("From", self.from.as_str()),
+                ("To", self.recipient.number.as_str()),
+                ("Body", message),
+            ]);
+
+        match result {
+            Ok(_) => {
+                info!("Sent SMS to {} ({} chars)", self.recipient.number, message.len());
+                Ok(())
+            }
+            // Rate limits and Twilio's own outages are worth retrying, unlike a rejected message
+            Err(ureq::Error::Status(status, _)) if status == 429 || status >= 500 => {
+                Err(format!("Twilio returned HTTP {}", status).into())
+            }
+            Err(ureq::Error::Status(status, response)) => {
+                // Invalid numbers, insufficient funds etc. come back as a JSON body worth logging
+                let detail = response
+                    .into_json::<TwilioError>()
+                    .map(|e| match e.code {
+                        Some(code) => format!("{} (code {})", e.message, code),
+                        None => e.message,
+                    })
+                    .unwrap_or_else(|_| format!("HTTP {}", status));
+                Err(Box::new(RavenError::Notifier {
+                    channel: self.name(),
+                    message: detail,
+                }))
+            }
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+}
+
+impl Notifier for TwilioNotifier {
+    fn name(&self) -> String {
+        match &self.recipient.name {
+            Some(name) => format!("twilio:{} ({})", name, self.recipient.number),
+            None => format!("twilio:{}", self.recipient.number),
+        }
+    }
+
+    fn channels(&self) -> Vec<String> {
+        let mut channels = vec![TWILIO_CHANNEL.to_string()];
+        if let Some(name) = &self.recipient.name {
+            channels.push(format!("{}:{}", TWILIO_CHANNEL, name));
+        }
+        channels
+    }
+
+    fn max_messages_per_day(&self) -> Option<u32> {
+        self.max_messages_per_day
+    }
+
+    fn constraints(&self) -> Constraints {
+        Constraints::SMS
+    }
+
+    fn notify(&self, messages: &[String]) -> Result<(), Box<dyn Error>> {
+        let url = format!(
+            "https://api.twilio.com/2010-04-01/Accounts/{}/Messages.json",
+            self.account_sid
+        );
+
+        for message in messages {
+            let operation = format!("Sending SMS to {}", self.recipient.number);
+            retry_with_clock(&self.retry, self.clock.as_ref(), &operation, || self.send(&url, message))?;
+        }
+
+        Ok(())
+    }
+
+    // Fetching the account checks the credentials along with the connection
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        let url = format!("https://api.twilio.com/2010-04-01/Accounts/{}.json", self.account_sid);
+        match self.agent.get(&url).set("Authorization", &self.authorization).call() {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(status, _)) => Err(Box::new(RavenError::Notifier {
+                channel: self.name(),
+                message: format!("Twilio returned HTTP {} for the account", status),
+            })),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::error::Error;
+use std::time::Duration;
+
+use ureq::AgentBuilder;
+
+use crate::config::{AbbreviationConfig, Config, NwsConfig, ProductConfig, Source};
+use crate::effects::Effects;
+use crate::fetch::FetchedForecast;
+
+// What the zone forecast is archived and provenanced as coming from
+pub const NWS_SOURCE: &str = "nws";
+// Leads every message of it, so it's never mistaken for the higher summits forecast
+const HEADING: &str = "NWS FALLBACK";
+
+// The zone forecast as an extra product, for its own files and the heading
+pub fn nws_product(nws: &NwsConfig) -> ProductConfig {
+    ProductConfig {
+        name: HEADING.to_string(),
+        source: Source {
+            name: NWS_SOURCE.to_string(),
+            url: nws.url.clone(),
+            abbreviation: AbbreviationConfig {
+                days: nws.days,
+                ..AbbreviationConfig::default()
+            },
+            ..Source::default()
+        },
+        prefix: nws.prefix.clone(),
+        inreach: true,
+        notify: true,
+    }
+}
+
+// The zone forecast text product over plain HTTP, one day block per period
+pub fn fetch_zone_forecast(
+    config: &Config,
+    nws: &NwsConfig,
+    effects: &Effects,
+) -> Result<FetchedForecast, Box<dyn Error>> {
+    let agent = AgentBuilder::new()
+        .user_agent(&config.user_agent)
+        .timeout(Duration::from_secs(30))
+        .build();
+    let text = agent.get(&nws.url).call()?.into_string()?;
+    let (issued, day_blocks) = parse_zone_forecast(&text).ok_or_else(|| format!("no forecast periods in {}", nws.url))?;
+
+    // Written so parse_issued finds the issue time
+    let mut full = format!("Issued: {}\n", issued.unwrap_or_default());
+    full.push_str(&day_blocks.join("\n"));
+    Ok(FetchedForecast {
+        full,
+        day_blocks,
+        source: NWS_SOURCE.to_string(),
+        captured: effects.now(),
+        // The heading marks it, the fallback marker would only repeat that
+        fallback: false,
+    })
+}
+
+// Periods run from ".TODAY...Sunny. Highs in the mid 40s." over any continuation lines to the next
+// period or the "$$" ending the zone, each becomes "Today: Sunny. Highs in the mid 40s.". The issue
+// time is the line before the first period. None without any periods
+fn parse_zone_forecast(text: &str) -> Option<(Option<String>, Vec<String>)> {
+    let lines = text.lines().map(str::trim).collect::<Vec<_>>();
+    let first = lines.iter().position(|line| period(line).is_some())?;
+    let issued = lines[..first]
+        .iter()
+        .rev()
+        .find(|line| !line.is_empty())
+        .map(|line| line.to_string());
+
+    let mut blocks = Vec::new();
+    for line in &lines[first..] {
+        if line.starts_with("$$") {
+            break;
+        }
+        match period(line) {
+            Some((label, prose)) => blocks.push(format!("{}: {}", label, prose)),
+            None if line.is_empty() => {}
+            None => {
+                if let Some(block) = blocks.last_mut() {
+                    block.push(' ');
+                    block.push_str(line);
+                }
+            }
+        }
+    }
+    Some((issued, blocks))
+}
+
+// ".TUESDAY NIGHT...Clear." is ("Tuesday Night", "Clear.")
+fn period(line: &str) -> Option<(String, &str)> {
+    let (label, prose) = line.strip_prefix('.')?.split_once("...")?;
+    if label.is_empty() || !label.chars().all(|c| c.is_ascii_uppercase() || c == ' ') {
+        return None;
+    }
+    let label = label
+        .split_whitespace()
+        .map(|word| {
+            let (first, rest) = word.split_at(1);
+            format!("{}{}", first, rest.to_lowercase())
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some((label, prose.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZONE_FORECAST: &str = "\
+FPUS51 KGYX 120830
+ZFPGYX
+
+Zone Forecast Product for Maine and New Hampshire
+National Weather Service Gray ME
+330 AM EST Fri Jan 12 2024
+
+NHZ002-122100-
+Southern Coos-
+Including the cities of Berlin and Gorham
+330 AM EST Fri Jan 12 2024
+
+.TODAY...Snow showers. Highs around 20. Northwest winds 10 to
+15 mph with gusts up to 30 mph.
+.TONIGHT...Mostly cloudy. Lows around 5 above.
+.SATURDAY NIGHT...Clear. Lows 5 below to 5 above zero.
+
+$$
+
+NHZ003-122100-
+Northern Grafton-
+.TODAY...Sunny.
+";
+
+    #[test]
+    fn reads_each_period_of_the_zone() {
+        let (issued, blocks) = parse_zone_forecast(ZONE_FORECAST).unwrap();
+        assert_eq!(issued.as_deref(), Some("330 AM EST Fri Jan 12 2024"));
+        assert_eq!(
+            blocks,
+            [
+                "Today: Snow showers. Highs around 20. Northwest winds 10 to 15 mph with gusts up to 30 mph.",
+                "Tonight: Mostly cloudy. Lows around 5 above.",
+                "Saturday Night: Clear. Lows 5 below to 5 above zero.",
+            ]
+        );
+    }
+
+    #[test]
+    fn no_periods_is_none() {
+        assert_eq!(parse_zone_forecast("Zone Forecast Product\n$$\n"), None);
+    }
+}
@@ -0,0 +1,30 @@
+use std::error::Error;
+
+use headless_chrome::Element;
+
+// Text read off a screenshot of the element, for widgets drawing their numbers into an image or
+// canvas. Only digits and minus signs are recognised
+#[cfg(feature = "ocr")]
+pub fn element_text(element: &Element) -> Result<String, Box<dyn Error>> {
+    use headless_chrome::protocol::cdp::Page::CaptureScreenshotFormatOption;
+
+    let png = element.capture_screenshot(CaptureScreenshotFormatOption::Png)?;
+    image_text(&png)
+}
+
+#[cfg(feature = "ocr")]
+pub fn image_text(png: &[u8]) -> Result<String, Box<dyn Error>> {
+    use leptess::{LepTess, Variable};
+
+    let mut tesseract = LepTess::new(None, "eng")?;
+    // A single line of numbers
+    tesseract.set_variable(Variable::TesseditPagesegMode, "7")?;
+    tesseract.set_variable(Variable::TesseditCharWhitelist, "0123456789-")?;
+    tesseract.set_image_from_mem(png)?;
+    Ok(tesseract.get_utf8_text()?.trim().to_string())
+}
+
+#[cfg(not(feature = "ocr"))]
+pub fn element_text(_element: &Element) -> Result<String, Box<dyn Error>> {
+    Err("raven was built without the ocr feature".into())
+}
@@ -0,0 +1,270 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::archive::content_hash;
+use crate::effects::Effects;
+use crate::fetch::FetchedForecast;
+use crate::forecast::{DayForecast, Forecast};
+use crate::locale::Language;
+use crate::version::version;
+
+pub const FORECAST_FULL_PATH: &str = "forecast_full.txt";
+pub const FORECAST_ABBREVIATED_PATH: &str = "forecast_abbreviated.txt";
+pub const FORECAST_COMPACT_PATH: &str = "forecast_compact.txt";
+pub const FORECAST_JSON_PATH: &str = "forecast.json";
+// forecast_day_0.txt, forecast_day_0.json, forecast_day_1.txt, ...
+const DAY_FILE_PREFIX: &str = "forecast_day_";
+// forecast_abbreviated.txt.meta.json next to forecast_abbreviated.txt
+const PROVENANCE_SUFFIX: &str = ".meta.json";
+
+// The files one product's forecast is kept in, the higher summits forecast's are unprefixed
+pub struct OutputFiles {
+    pub full: PathBuf,
+    pub abbreviated: PathBuf,
+    pub compact: PathBuf,
+    pub json: PathBuf,
+}
+
+impl OutputFiles {
+    pub fn with_prefix(prefix: &str) -> OutputFiles {
+        let path = |name: &str| PathBuf::from(format!("{}{}", prefix, name));
+        OutputFiles {
+            full: path(FORECAST_FULL_PATH),
+            abbreviated: path(FORECAST_ABBREVIATED_PATH),
+            compact: path(FORECAST_COMPACT_PATH),
+            json: path(FORECAST_JSON_PATH),
+        }
+    }
+
+    pub fn all(&self) -> [&Path; 4] {
+        [&self.full, &self.abbreviated, &self.compact, &self.json]
+    }
+}
+
+impl Default for OutputFiles {
+    fn default() -> Self {
+        OutputFiles::with_prefix("")
+    }
+}
+
+// Where an output file's content came from, kept in a sidecar next to it. The sidecar is written
+// after the content, so one that doesn't match the file's hash is from before an interrupted write
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Provenance {
+    // When the source captured it, the fetch time for a live page
+    pub fetched: DateTime<Utc>,
+    pub source: String,
+    pub url: Option<String>,
+    pub fallback: bool,
+    pub issued: Option<String>,
+    // e.g. 0.1.0 (3f2a9c81d0e4), older sidecars have just the version
+    pub raven_version: String,
+    // Of the effective config that wrote it
+    #[serde(default)]
+    pub config_hash: Option<String>,
+    // Of the file the sidecar belongs to
+    #[serde(default)]
+    pub content_hash: String,
+    // When this revision finished going out to the inReach, None if it hasn't
+    #[serde(default)]
+    pub sent: Option<DateTime<Utc>>,
+}
+
+impl Provenance {
+    pub fn new(fetched: &FetchedForecast, url: Option<&str>, forecast: &Forecast, config_hash: &str) -> Provenance {
+        Provenance {
+            fetched: fetched.captured,
+            source: fetched.source.clone(),
+            url: url.map(str::to_string),
+            fallback: fetched.fallback,
+            issued: forecast.issued.clone(),
+            raven_version: version(),
+            config_hash: Some(config_hash.to_string()),
+            content_hash: String::new(),
+            sent: None,
+        }
+    }
+}
+
+pub fn provenance_path(path: &Path) -> PathBuf {
+    let mut meta = path.as_os_str().to_owned();
+    meta.push(PROVENANCE_SUFFIX);
+    PathBuf::from(meta)
+}
+
+pub fn read_provenance(effects: &Effects, path: &Path) -> Option<Provenance> {
+    serde_json::from_str(&effects.read(&provenance_path(path))?).ok()
+}
+
+// Whether the file still has the content its sidecar describes
+pub fn provenance_matches(effects: &Effects, path: &Path, provenance: &Provenance) -> bool {
+    effects
+        .read(path)
+        .is_some_and(|contents| content_hash(&contents) == provenance.content_hash)
+}
+
+// Notes the send on the sidecars of `files` written for `forecast`, as long as they still hold it
+pub fn mark_sent(effects: &Effects, files: &OutputFiles, forecast: &Forecast, sent: DateTime<Utc>) {
+    if read_structured_forecast(effects, files).as_ref() != Some(forecast) {
+        return;
+    }
+    for path in files.all() {
+        let provenance = read_provenance(effects, path).filter(|provenance| provenance.issued == forecast.issued);
+        if let Some(mut provenance) = provenance {
+            provenance.sent = Some(sent);
+            if let Err(e) = write_provenance(effects, path, &provenance) {
+                warn!("Could not record the send in {}: {}", provenance_path(path).display(), e);
+            }
+        }
+    }
+}
+
+// Each of `files` with where its content came from, for `raven status`
+pub fn render_provenance(effects: &Effects, files: &OutputFiles) -> String {
+    let mut out = String::new();
+    for path in files.all() {
+        out.push_str(&format!("{}\n", path.display()));
+        if !effects.exists(path) {
+            out.push_str("  not written yet\n");
+            continue;
+        }
+        let provenance = match read_provenance(effects, path) {
+            Some(provenance) => provenance,
+            None => {
+                out.push_str("  no metadata, written before raven recorded it\n");
+                continue;
+            }
+        };
+
+        out.push_str(&format!(
+            "  fetched  {} from {}{}{}\n",
+            provenance.fetched.format("%Y-%m-%d %H:%M:%S UTC"),
+            provenance.source,
+            provenance.url.as_deref().map(|url| format!(" ({})", url)).unwrap_or_default(),
+            if provenance.fallback { ", a fallback" } else { "" }
+        ));
+        out.push_str(&format!("  issued   {}\n", provenance.issued.as_deref().unwrap_or("n/a")));
+        out.push_str(&format!(
+            "  raven    {}{}\n",
+            provenance.raven_version,
+            provenance
+                .config_hash
+                .as_deref()
+                .map(|hash| format!(", config {}", hash))
+                .unwrap_or_default()
+        ));
+        out.push_str(&format!(
+            "  content  {}{}\n",
+            provenance.content_hash,
+            if provenance_matches(effects, path, &provenance) {
+                ""
+            } else {
+                ", doesn't match the file, its write was interrupted"
+            }
+        ));
+        out.push_str(&format!(
+            "  inReach  {}\n",
+            provenance
+                .sent
+                .map(|sent| format!("sent {}", sent.format("%Y-%m-%d %H:%M:%S UTC")))
+                .unwrap_or_else(|| "not sent".to_string())
+        ));
+    }
+    out
+}
+
+pub fn persist_forecast(
+    effects: &Effects,
+    forecast: &str,
+    path: &Path,
+    provenance: &Provenance,
+) -> Result<(), Box<dyn Error>> {
+    // Write forecast to disk
+    effects.write(path, forecast)?;
+    write_provenance(
+        effects,
+        path,
+        &Provenance {
+            content_hash: content_hash(forecast),
+            ..provenance.clone()
+        },
+    )
+}
+
+fn write_provenance(effects: &Effects, path: &Path, provenance: &Provenance) -> Result<(), Box<dyn Error>> {
+    effects.write_atomic(&provenance_path(path), &serde_json::to_string_pretty(provenance)?)
+}
+
+pub fn read_structured_forecast(effects: &Effects, files: &OutputFiles) -> Option<Forecast> {
+    let json = effects.read(&files.json)?;
+    serde_json::from_str(&json).ok()
+}
+
+pub fn persist_structured_forecast(
+    effects: &Effects,
+    files: &OutputFiles,
+    forecast: &Forecast,
+    lang: Language,
+    provenance: &Provenance,
+) -> Result<(), Box<dyn Error>> {
+    persist_forecast(effects, &serde_json::to_string_pretty(forecast)?, &files.json, provenance)?;
+    persist_forecast(effects, &forecast.compact(lang), &files.compact, provenance)
+}
+
+// One small file pair per published day, rewritten only when that day changed. Index 0 is the
+// first period on the page, files past the last published day are removed as the window rolls
+pub fn persist_day_files(effects: &Effects, dir: &Path, forecast: &Forecast, lang: Language) -> Result<(), Box<dyn Error>> {
+    for (index, day) in forecast.days.iter().enumerate() {
+        let (text_path, json_path) = day_file_paths(dir, index);
+        write_if_changed(effects, &text_path, &render_day(day, lang))?;
+        write_if_changed(effects, &json_path, &serde_json::to_string_pretty(day)?)?;
+    }
+
+    for path in effects.list(dir)? {
+        let stale = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.strip_prefix(DAY_FILE_PREFIX))
+            .and_then(|index| index.parse::<usize>().ok())
+            .is_some_and(|index| index >= forecast.days.len());
+        if stale {
+            effects.remove(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn day_file_paths(dir: &Path, index: usize) -> (PathBuf, PathBuf) {
+    (
+        dir.join(format!("{}{}.txt", DAY_FILE_PREFIX, index)),
+        dir.join(format!("{}{}.json", DAY_FILE_PREFIX, index)),
+    )
+}
+
+fn write_if_changed(effects: &Effects, path: &Path, contents: &str) -> Result<(), Box<dyn Error>> {
+    if effects.read(path).as_deref() == Some(contents) {
+        return Ok(());
+    }
+    effects.write_atomic(path, contents)
+}
+
+// "Tonight", the prose, then whichever numbers were parsed, one per line
+fn render_day(day: &DayForecast, lang: Language) -> String {
+    let strings = lang.strings();
+    let mut lines = vec![lang.label(&day.label), day.summary.clone()];
+    if let Some(high) = day.high_f {
+        lines.push(format!("{}: {}F", strings.high_label, high));
+    }
+    if let Some(low) = day.low_f {
+        lines.push(format!("{}: {}F", strings.low_label, low));
+    }
+    if let Some(wind) = &day.wind {
+        lines.push(format!("{}: {}", strings.wind_label, wind.compact(lang)));
+    }
+    lines.join("\n") + "\n"
+}
@@ -0,0 +1,129 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use headless_chrome::{Browser, Tab};
+use log::debug;
+
+use crate::config::{Config, Source};
+use crate::effects::Effects;
+use crate::error::RavenError;
+use crate::fetch::{fetch_validated, FetchedForecast};
+use crate::forecast::Forecast;
+
+// A source to fetch on one of the pool's tabs, `known` is its last forecast
+pub struct Job<'a> {
+    pub source: &'a Source,
+    pub known: Option<Forecast>,
+}
+
+pub struct Fetched {
+    // Errors come back from other threads, so only raven's own keep their type, the rest are text
+    pub result: Result<FetchedForecast, Box<dyn Error + Send + Sync>>,
+    pub took: Duration,
+}
+
+// Fetches every job on up to `tabs` new tabs of `browser` at once, each source's failure its own.
+// Jobs not started by `deadline` are given up on, one already navigating is left to finish. Every
+// tab is closed by the time this returns, results are in the order of `jobs`
+pub fn fetch_all(
+    browser: &Browser,
+    tabs: usize,
+    deadline: Instant,
+    config: &Config,
+    effects: &Effects,
+    jobs: &[Job],
+) -> Vec<Fetched> {
+    let queue = Mutex::new(jobs.iter().enumerate().collect::<VecDeque<_>>());
+    let results = Mutex::new(jobs.iter().map(|_| None).collect::<Vec<Option<Fetched>>>());
+
+    thread::scope(|scope| {
+        let workers = (0..tabs.min(jobs.len()))
+            .map(|_| scope.spawn(|| work(&queue, &results, browser, deadline, config, effects)))
+            .collect::<Vec<_>>();
+        // Joined here so a panicking fetch only loses its own job, the rest of the queue goes to the
+        // other workers
+        for worker in workers {
+            let _ = worker.join();
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|fetched| {
+            fetched.unwrap_or_else(|| Fetched {
+                result: Err("the fetch panicked".into()),
+                took: Duration::default(),
+            })
+        })
+        .collect()
+}
+
+// One tab's worth, taking jobs until the queue is empty
+fn work(
+    queue: &Mutex<VecDeque<(usize, &Job)>>,
+    results: &Mutex<Vec<Option<Fetched>>>,
+    browser: &Browser,
+    deadline: Instant,
+    config: &Config,
+    effects: &Effects,
+) {
+    let mut tab = None;
+    loop {
+        let (i, job) = match queue.lock().unwrap().pop_front() {
+            Some(next) => next,
+            None => break,
+        };
+        let started = Instant::now();
+        let result = if started >= deadline {
+            Err("not started, the cycle ran past its deadline".into())
+        } else {
+            fetch_on(&mut tab, browser, config, effects, job)
+        };
+        results.lock().unwrap()[i] = Some(Fetched {
+            result,
+            took: started.elapsed(),
+        });
+    }
+    if let Some(tab) = tab {
+        close(&tab);
+    }
+}
+
+// Reuses the worker's tab while it keeps working. After a failure it's closed and the next job gets a
+// fresh one, a crashed tab fails everything after it otherwise
+fn fetch_on(
+    tab: &mut Option<Arc<Tab>>,
+    browser: &Browser,
+    config: &Config,
+    effects: &Effects,
+    job: &Job,
+) -> Result<FetchedForecast, Box<dyn Error + Send + Sync>> {
+    let current = match tab.take() {
+        Some(current) => current,
+        None => browser.new_tab().map_err(|e| format!("couldn't open a tab: {}", e))?,
+    };
+    match fetch_validated(&current, config, effects, job.source, false, job.known.as_ref()) {
+        Ok(fetched) => {
+            *tab = Some(current);
+            Ok(fetched)
+        }
+        Err(e) => {
+            close(&current);
+            match e.downcast::<RavenError>() {
+                Ok(e) => Err(e),
+                Err(e) => Err(e.to_string().into()),
+            }
+        }
+    }
+}
+
+fn close(tab: &Arc<Tab>) {
+    if let Err(e) = tab.close(false) {
+        debug!("Closing a tab failed: {}", e);
+    }
+}
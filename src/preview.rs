@@ -0,0 +1,205 @@
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::archive::{load_revisions, ArchivedRevision};
+use crate::config::Config;
+use crate::cycle::{forecast_content, STATE_PATH};
+use crate::effects::Effects;
+use crate::error::RavenError;
+use crate::fetch::FetchedForecast;
+use crate::forecast::{DayForecast, Forecast};
+use crate::format::inreach_messages;
+use crate::note::active_note;
+use crate::state::State;
+use crate::trend::trend_alerts;
+use crate::validation::DAY_NAMES;
+
+// As archive files are named
+const ARCHIVE_TIMESTAMP: &str = "%Y%m%dT%H%M%S%3fZ";
+const SECTION_PREVIEW_LENGTH: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PreviewStyle {
+    // The day blocks, what a changed forecast is sent as
+    Abbreviated,
+    // Just each day's wind, e.g. "Tonight: W>NW 50-70"
+    Compact,
+    // The narrative
+    Full,
+}
+
+impl FromStr for PreviewStyle {
+    type Err = RavenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "abbreviated" => Ok(PreviewStyle::Abbreviated),
+            "compact" => Ok(PreviewStyle::Compact),
+            "full" => Ok(PreviewStyle::Full),
+            _ => Err(RavenError::InvalidArgument(format!(
+                "unknown style {}, expected abbreviated, compact or full",
+                s
+            ))),
+        }
+    }
+}
+
+// Where a stored forecast is read from
+pub enum Stored<'a> {
+    // An archived revision's JSON, or text with the day blocks in it such as forecast_abbreviated.txt
+    File(&'a Path),
+    // The start of an archived revision's timestamp, e.g. 20240210T1007
+    Archived(&'a str),
+}
+
+// The stored forecast as if it had just been fetched, every day block checked to parse
+pub fn load_stored(effects: &Effects, config: &Config, stored: Stored) -> Result<FetchedForecast, Box<dyn Error>> {
+    let fetched = match stored {
+        Stored::File(path) => {
+            let text = effects.read(path).ok_or_else(|| format!("can't read {}", path.display()))?;
+            if path.extension().and_then(|e| e.to_str()) == Some("json") {
+                serde_json::from_str::<ArchivedRevision>(&text)
+                    .map_err(|e| format!("{} isn't an archived revision: {}", path.display(), e))?
+                    .fetched()
+            } else {
+                from_text(effects, path, &text)?
+            }
+        }
+        Stored::Archived(timestamp) => find_archived(effects, config, timestamp)?.fetched(),
+    };
+
+    for (i, block) in fetched.day_blocks.iter().enumerate() {
+        if DayForecast::parse(block).summary.is_empty() {
+            return Err(format!("day section {} ({}) has no forecast in it", i + 1, section_preview(block)).into());
+        }
+    }
+    Ok(fetched)
+}
+
+// The inReach messages a changed forecast would go out as, through the live send's own rendering.
+// Alerts and the summary are against the last forecast sent
+pub fn preview(config: &Config, effects: &Effects, fetched: &FetchedForecast, style: PreviewStyle) -> Vec<String> {
+    let lang = config.language();
+    let fetched = &fetched.with_days(config.source.abbreviation.days);
+    let forecast = Forecast::parse(&fetched.full, &fetched.day_blocks);
+    let state = State::load(effects, Path::new(STATE_PATH));
+    let note = active_note(effects, config.inreach.note.as_deref());
+    let alerts = state
+        .last_sent
+        .as_ref()
+        .map(|last_sent| trend_alerts(last_sent, &forecast, lang))
+        .unwrap_or_default();
+    let body = match style {
+        PreviewStyle::Abbreviated => fetched.abbreviated(),
+        PreviewStyle::Compact => forecast.compact(lang),
+        PreviewStyle::Full => fetched.full_text(),
+    };
+
+    inreach_messages(
+        config,
+        &forecast_content(config, None, body, alerts, state.last_sent, &forecast, note),
+    )
+}
+
+// Each message with its length, and a note when there are more than `max_messages`
+pub fn render_preview(messages: &[String], max_messages: Option<usize>) -> String {
+    let mut out = String::new();
+    for (i, message) in messages.iter().enumerate() {
+        let over = max_messages.is_some_and(|max| i >= max);
+        out.push_str(&format!(
+            "{:>3} chars{} {}\n",
+            message.chars().count(),
+            if over { " (over)" } else { "" },
+            message
+        ));
+    }
+    match max_messages {
+        Some(max) if messages.len() > max => out.push_str(&format!(
+            "{} messages, {} more than --max-messages {}\n",
+            messages.len(),
+            messages.len() - max,
+            max
+        )),
+        _ => out.push_str(&format!("{} messages\n", messages.len())),
+    }
+    out
+}
+
+// Text split into day sections at each "Tonight:" style label, the text before the first one is the
+// narrative. forecast_full.txt has only the narrative, so it's rejected for having no sections
+fn from_text(effects: &Effects, path: &Path, text: &str) -> Result<FetchedForecast, Box<dyn Error>> {
+    let starts = section_starts(text);
+    if starts.is_empty() {
+        return Err(format!(
+            "{} has no day sections (\"Tonight: ...\"), use forecast_abbreviated.txt or an archived revision",
+            path.display()
+        )
+        .into());
+    }
+
+    let day_blocks = starts
+        .iter()
+        .enumerate()
+        .map(|(i, start)| {
+            let end = starts.get(i + 1).copied().unwrap_or(text.len());
+            text[*start..end].trim().to_string()
+        })
+        .collect();
+    Ok(FetchedForecast {
+        full: text[..starts[0]].trim().to_string(),
+        day_blocks,
+        source: path.display().to_string(),
+        captured: effects.now(),
+        fallback: false,
+    })
+}
+
+// Where each day label starts, a day name at the start of a word followed by a colon, "Night" allowed
+// in between ("Sunday Night:")
+fn section_starts(text: &str) -> Vec<usize> {
+    let lower = text.to_ascii_lowercase();
+    lower
+        .char_indices()
+        .filter(|(i, _)| lower[..*i].chars().next_back().is_none_or(|c| !c.is_alphanumeric()))
+        .filter(|(i, _)| {
+            let rest = &lower[*i..];
+            DAY_NAMES
+                .iter()
+                .filter_map(|day| rest.strip_prefix(day))
+                .any(|after| after.trim_start_matches(" night").starts_with(':'))
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+fn find_archived(effects: &Effects, config: &Config, timestamp: &str) -> Result<ArchivedRevision, Box<dyn Error>> {
+    let timestamp = timestamp.trim();
+    let mut matching = load_revisions(effects, &config.archive.dir, None)?
+        .into_iter()
+        .filter(|revision| revision.archived.format(ARCHIVE_TIMESTAMP).to_string().starts_with(timestamp))
+        .collect::<Vec<_>>();
+    match matching.len() {
+        0 => Err(format!("no revision archived at {} in {}", timestamp, config.archive.dir.display()).into()),
+        1 => Ok(matching.remove(0)),
+        _ => Err(format!(
+            "{} matches {} revisions, give more of the timestamp: {}",
+            timestamp,
+            matching.len(),
+            matching
+                .iter()
+                .map(|revision| revision.archived.format(ARCHIVE_TIMESTAMP).to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+        .into()),
+    }
+}
+
+fn section_preview(block: &str) -> String {
+    let preview = block.split_whitespace().collect::<Vec<_>>().join(" ");
+    if preview.chars().count() <= SECTION_PREVIEW_LENGTH {
+        return format!("{:?}", preview);
+    }
+    format!("{:?}...", preview.chars().take(SECTION_PREVIEW_LENGTH).collect::<String>())
+}
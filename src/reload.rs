@@ -0,0 +1,160 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use log::{error, info, warn};
+
+use crate::config::{Config, Overrides};
+use crate::effects::Effects;
+use crate::version::{record_config, ConfigFingerprint};
+
+// Sections only read when the browser is launched or the effects are set up, or where a change mid-run
+// would split the outputs or the archive across two places. A reload keeps their running values
+const RESTART_ONLY: [&str; 5] = ["user_agent", "browser_path", "output", "archive", "retry"];
+
+// Re-reads the config file between watch cycles, so most settings change without a restart
+pub struct ConfigReloader {
+    path: PathBuf,
+    overrides: Overrides,
+    allow_fast_polling: bool,
+    // The file as last read, only a change to it is reloaded. None when it couldn't be read
+    contents: Option<String>,
+}
+
+impl ConfigReloader {
+    pub fn new(path: &Path, overrides: Overrides, allow_fast_polling: bool, effects: &Effects) -> ConfigReloader {
+        ConfigReloader {
+            path: path.to_path_buf(),
+            overrides,
+            allow_fast_polling,
+            contents: effects.read(path),
+        }
+    }
+
+    // The config to run the next cycle with when the file changed to a valid one, None when it didn't
+    // change or doesn't parse or validate, `running` stays in force then. A broken file is reported
+    // once, not every cycle until it's fixed
+    pub fn reload(&mut self, running: &Config, effects: &Effects) -> Option<Config> {
+        let contents = match effects.read(&self.path) {
+            Some(contents) => contents,
+            None => {
+                if self.contents.take().is_some() {
+                    warn!("Can't read {}, keeping the running config", self.path.display());
+                }
+                return None;
+            }
+        };
+        if self.contents.as_deref() == Some(contents.as_str()) {
+            return None;
+        }
+        self.contents = Some(contents.clone());
+
+        let mut config = match self.parse(&contents) {
+            Ok(config) => config,
+            Err(e) => {
+                error!("Not reloading {}, keeping the running config: {}", self.path.display(), e);
+                return None;
+            }
+        };
+        let changed = ConfigFingerprint::of(&config).changed_since(&ConfigFingerprint::of(running));
+        for section in keep_running(&mut config, running, &changed) {
+            warn!("{} changed in {}, restart raven to apply it", section, self.path.display());
+        }
+
+        let changed = changed
+            .into_iter()
+            .filter(|section| !RESTART_ONLY.contains(&section.as_str()))
+            .collect::<Vec<_>>();
+        if changed.is_empty() {
+            info!("{} changed, nothing that can be applied live did", self.path.display());
+            return None;
+        }
+        info!("Reloaded {}, applying {}", self.path.display(), changed.join(", "));
+        if let Err(e) = record_config(effects, ConfigFingerprint::of(&config), "on reload") {
+            warn!("Could not record the reloaded config: {}", e);
+        }
+        Some(config)
+    }
+
+    fn parse(&self, contents: &str) -> Result<Config, Box<dyn Error>> {
+        let mut config = Config::parse(contents)?;
+        self.overrides.apply(&mut config);
+        config.validate(self.allow_fast_polling)?;
+        Ok(config)
+    }
+}
+
+// Puts back the running value of every restart-only section in `changed`, returning their names
+fn keep_running(config: &mut Config, running: &Config, changed: &[String]) -> Vec<&'static str> {
+    let kept = RESTART_ONLY
+        .iter()
+        .copied()
+        .filter(|section| changed.iter().any(|name| name == section))
+        .collect::<Vec<_>>();
+
+    for section in &kept {
+        match *section {
+            "user_agent" => config.user_agent = running.user_agent.clone(),
+            "browser_path" => config.browser_path = running.browser_path.clone(),
+            "output" => config.output = running.output.clone(),
+            "archive" => config.archive = running.archive.clone(),
+            "retry" => config.retry = running.retry.clone(),
+            _ => {}
+        }
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use std::sync::Arc;
+
+    const PATH: &str = "raven.toml";
+
+    // A reloader that has read `contents`, and the config running from it
+    fn running(contents: &str) -> (ConfigReloader, Config, Effects) {
+        let effects = Effects::with_storage(false, Arc::new(MemoryStorage::new()));
+        effects.write(Path::new(PATH), contents).unwrap();
+        let reloader = ConfigReloader::new(Path::new(PATH), Overrides::default(), false, &effects);
+        (reloader, Config::parse(contents).unwrap(), effects)
+    }
+
+    #[test]
+    fn a_malformed_file_keeps_the_running_config() {
+        let (mut reloader, config, effects) = running("[sms]\nsettle_seconds = 60\n");
+        effects.write(Path::new(PATH), "[sms\nsettle_seconds = 0\n").unwrap();
+        assert!(reloader.reload(&config, &effects).is_none());
+        // Still kept on the next cycle, the broken file isn't tried again until it changes
+        assert!(reloader.reload(&config, &effects).is_none());
+    }
+
+    #[test]
+    fn a_live_safe_change_is_applied() {
+        let (mut reloader, config, effects) = running("[sms]\nsettle_seconds = 60\n");
+        effects.write(Path::new(PATH), "[sms]\nsettle_seconds = 0\n").unwrap();
+        let reloaded = reloader.reload(&config, &effects).unwrap();
+        assert_eq!(reloaded.sms.settle_seconds, 0);
+        assert!(reloader.reload(&reloaded, &effects).is_none());
+    }
+
+    #[test]
+    fn restart_only_sections_keep_their_running_values() {
+        let (mut reloader, config, effects) = running("[output]\nday_files = false\n");
+        effects.write(Path::new(PATH), "[output]\nday_files = true\n").unwrap();
+        assert!(reloader.reload(&config, &effects).is_none());
+
+        // Alongside a live change, the live change alone is applied
+        let contents = "[output]\nday_files = true\n[archive]\ndir = \"revisions\"\n[sms]\nsettle_seconds = 60\n";
+        effects.write(Path::new(PATH), contents).unwrap();
+        let reloaded = reloader.reload(&config, &effects).unwrap();
+        assert_eq!(reloaded.sms.settle_seconds, 60);
+        assert!(!reloaded.output.day_files);
+        assert_eq!(reloaded.archive.dir, config.archive.dir);
+
+        // Both reported as needing a restart
+        let mut changed = Config::parse(contents).unwrap();
+        let sections = ConfigFingerprint::of(&changed).changed_since(&ConfigFingerprint::of(&config));
+        assert_eq!(keep_running(&mut changed, &config, &sections), ["output", "archive"]);
+    }
+}
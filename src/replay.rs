@@ -0,0 +1,150 @@
+use std::error::Error;
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::Utc;
+
+use crate::archive::load_revisions;
+use crate::clock::ManualClock;
+use crate::config::Config;
+use crate::cycle::{process_forecast, STATE_PATH};
+use crate::effects::Effects;
+use crate::state::State;
+use crate::storage::MemoryStorage;
+
+// Runs every archived revision in `dir`, read through `archive`, through the real cycle pipeline,
+// oldest first, against in-memory storage at the time it was archived. Nothing is sent, each change is assumed to have
+// been sent successfully. The report only depends on the archive and config, so it can be compared
+// between runs
+pub fn replay(config: &Config, archive: &Effects, dir: &Path) -> Result<String, Box<dyn Error>> {
+    let mut config = config.clone();
+    // Replayed revisions are already in the archive
+    config.archive.enabled = false;
+
+    let revisions = load_revisions(archive, dir, None)?;
+    let storage = Arc::new(MemoryStorage::new());
+    let clock = Arc::new(ManualClock::new(revisions.first().map(|revision| revision.archived).unwrap_or_else(Utc::now)));
+    let effects = Effects::with_storage(false, storage.clone()).with_clock(clock.clone());
+
+    let mut report = String::new();
+    for (i, revision) in revisions.iter().enumerate() {
+        writeln!(
+            report,
+            "== {}/{} archived {} from {}{} ==",
+            i + 1,
+            revisions.len(),
+            revision.archived.format("%Y-%m-%d %H:%M:%S UTC"),
+            revision.source,
+            if revision.fallback { " (fallback)" } else { "" }
+        )?;
+        clock.set(revision.archived);
+
+        let outcome = match process_forecast(&config, &effects, &revision.fetched()) {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                writeln!(report, "error: {}\n", e)?;
+                continue;
+            }
+        };
+
+        let written = storage.take_written();
+        if written.is_empty() {
+            writeln!(report, "unchanged")?;
+        }
+        for path in written {
+            writeln!(report, "persist {}", path.display())?;
+        }
+        for alert in &outcome.alerts {
+            writeln!(report, "alert {}", alert)?;
+        }
+        for message in &outcome.messages {
+            writeln!(report, "send ({} chars) {}", message.chars().count(), message)?;
+        }
+
+        if !outcome.messages.is_empty() {
+            let mut state = State::load(&effects, Path::new(STATE_PATH));
+            state.last_sent = Some(outcome.forecast.clone());
+            state.save(&effects, Path::new(STATE_PATH))?;
+            storage.take_written();
+        }
+
+        writeln!(report)?;
+    }
+
+    if revisions.is_empty() {
+        writeln!(report, "No archived revisions in {}", dir.display())?;
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{archive_revision, ArchiveFormat, ArchivedRevision};
+    use crate::fetch::FetchedForecast;
+    use crate::forecast::Forecast;
+
+    fn revision(archived: &str, issued: &str, today: &str) -> ArchivedRevision {
+        let day_blocks = vec![
+            format!("Today:\n{}", today),
+            "Tonight:\nIn the clouds with snow. Lows around -10F. NW winds 60 to 80 mph.".to_string(),
+        ];
+        let fetched = FetchedForecast {
+            full: format!("Issued: {} Friday, January 12, 2024\n\n{}", issued, day_blocks.join("\n\n")),
+            day_blocks,
+            source: "Higher Summits Forecast".to_string(),
+            captured: archived.parse().unwrap(),
+            fallback: false,
+        };
+        let forecast = Forecast::parse(&fetched.full, &fetched.day_blocks);
+        ArchivedRevision::new(&fetched, &forecast, fetched.captured)
+    }
+
+    #[test]
+    fn replays_the_archive_in_order() {
+        let archive = Effects::with_storage(false, Arc::new(MemoryStorage::new()));
+        let dir = Path::new("archive");
+        for revision in [
+            revision("2024-01-12T11:10:00Z", "6:07 AM", "In the clouds. Highs around 5F. NW winds 50 to 70 mph."),
+            // The same forecast fetched again, then a revision with the wind swinging round to the south
+            revision("2024-01-12T11:40:00Z", "6:07 AM", "In the clouds. Highs around 5F. NW winds 50 to 70 mph."),
+            revision("2024-01-12T17:15:00Z", "12:10 PM", "In the clouds. Highs around 8F. S winds 50 to 70 mph."),
+        ] {
+            archive_revision(&archive, dir, ArchiveFormat::Files, &revision).unwrap();
+        }
+
+        let report = replay(&Config::default(), &archive, dir).unwrap();
+        let expected = concat!(
+            "== 1/3 archived 2024-01-12 11:10:00 UTC from Higher Summits Forecast ==\n",
+            "persist forecast.json\n",
+            "persist forecast.json.meta.json\n",
+            "persist forecast_compact.txt\n",
+            "persist forecast_compact.txt.meta.json\n",
+            "persist forecast_full.txt\n",
+            "persist forecast_full.txt.meta.json\n",
+            "persist forecast_abbreviated.txt\n",
+            "persist forecast_abbreviated.txt.meta.json\n",
+            "\n",
+            "== 2/3 archived 2024-01-12 11:40:00 UTC from Higher Summits Forecast ==\n",
+            "unchanged\n",
+            "\n",
+            "== 3/3 archived 2024-01-12 17:15:00 UTC from Higher Summits Forecast ==\n",
+            "persist forecast_full.txt\n",
+            "persist forecast_full.txt.meta.json\n",
+            "persist forecast_abbreviated.txt\n",
+            "persist forecast_abbreviated.txt.meta.json\n",
+            "persist forecast.json\n",
+            "persist forecast.json.meta.json\n",
+            "persist forecast_compact.txt\n",
+            "persist forecast_compact.txt.meta.json\n",
+            "alert Today: wind direction now S 50-70, was NW 50-70\n",
+            "send (160 chars) 1/2 ALERT Today: wind direction now S 50-70, was NW 50-70. Today:In the clouds. ",
+            "Highs around 8F. S winds 50 to 70 mph. Tonight:In the clouds with snow. Lows aro\n",
+            "send (36 chars) 2/2 und -10F. NW winds 60 to 80 mph.\n",
+            "\n",
+        );
+        assert_eq!(report, expected);
+    }
+}
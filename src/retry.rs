@@ -0,0 +1,225 @@
+use std::error::Error;
+use std::thread;
+use std::time::Duration;
+
+use log::warn;
+use rand::Rng;
+
+use crate::clock::Clock;
+use crate::error::RavenError;
+
+// Whether an error is worth another attempt
+pub type Retryable = fn(&(dyn Error + 'static)) -> bool;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Navigation,
+    ElementWait,
+    Persist,
+    InReach,
+    Notify,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    // 1 means no retries
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    // Each retry waits this much longer than the one before
+    pub multiplier: f64,
+    pub max_delay: Duration,
+    // Up to this fraction of each delay is added or taken off at random
+    pub jitter: f64,
+    pub retryable: Retryable,
+}
+
+impl RetryPolicy {
+    // One attempt, the behavior of everything before retries were configurable
+    pub fn none() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            base_delay: Duration::from_secs(0),
+            multiplier: 1.0,
+            max_delay: Duration::from_secs(0),
+            jitter: 0.0,
+            retryable,
+        }
+    }
+
+    // The wait before retry number `retry` (1 for the first), before jitter
+    pub fn delay(&self, retry: u32) -> Duration {
+        let delay = self.base_delay.as_secs_f64() * self.multiplier.powi(retry.saturating_sub(1) as i32);
+        Duration::from_secs_f64(delay.min(self.max_delay.as_secs_f64()).max(0.0))
+    }
+}
+
+// Permanent failures, retrying only burns time (and with Garmin, goodwill). Everything else, timeouts,
+// connection errors and the browser's own errors included, is worth another go
+pub fn retryable(error: &(dyn Error + 'static)) -> bool {
+    match error.downcast_ref::<RavenError>() {
+        Some(RavenError::ReplyUrlExpired)
+        | Some(RavenError::MissingReplyUrl { .. })
+        | Some(RavenError::MissingCredentials { .. })
+        | Some(RavenError::ManualLoginRequired { .. })
+        | Some(RavenError::InvalidConfig(_))
+        | Some(RavenError::InvalidArgument(_))
+        | Some(RavenError::Notifier { .. }) => false,
+        Some(RavenError::InReachHttp { status }) => *status >= 500,
+        _ => true,
+    }
+}
+
+// Runs `attempt` until it succeeds, fails with an error the policy won't retry or runs out of
+// attempts, logging each failed attempt under `operation`
+pub fn retry_with<T>(
+    policy: &RetryPolicy,
+    operation: &str,
+    attempt: impl FnMut() -> Result<T, Box<dyn Error>>,
+) -> Result<T, Box<dyn Error>> {
+    retry_with_sleeper(policy, operation, &mut thread::sleep, attempt)
+}
+
+// `retry_with` waiting on `clock`, so backoff runs on whatever time the caller runs on
+pub fn retry_with_clock<T>(
+    policy: &RetryPolicy,
+    clock: &dyn Clock,
+    operation: &str,
+    attempt: impl FnMut() -> Result<T, Box<dyn Error>>,
+) -> Result<T, Box<dyn Error>> {
+    retry_with_sleeper(policy, operation, &mut |delay| clock.sleep(delay), attempt)
+}
+
+// `retry_with` with the waiting swapped out, so delays can be checked without sleeping
+pub fn retry_with_sleeper<T>(
+    policy: &RetryPolicy,
+    operation: &str,
+    sleep: &mut dyn FnMut(Duration),
+    mut attempt: impl FnMut() -> Result<T, Box<dyn Error>>,
+) -> Result<T, Box<dyn Error>> {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let error = match attempt() {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+        if attempts >= policy.max_attempts {
+            if policy.max_attempts > 1 {
+                warn!("{} failed after {} attempts: {}", operation, attempts, error);
+            }
+            return Err(error);
+        }
+        if !(policy.retryable)(error.as_ref()) {
+            warn!("{} failed and won't be retried: {}", operation, error);
+            return Err(error);
+        }
+
+        let delay = with_jitter(policy.delay(attempts), policy.jitter);
+        // Garmin says how long to back off for
+        let delay = match error.downcast_ref::<RavenError>() {
+            Some(RavenError::RateLimited {
+                retry_after_seconds: Some(seconds),
+            }) => delay.max(Duration::from_secs(*seconds)),
+            _ => delay,
+        };
+        warn!(
+            "{} failed (attempt {} of {}), retrying in {:.1}s: {}",
+            operation,
+            attempts,
+            policy.max_attempts,
+            delay.as_secs_f64(),
+            error
+        );
+        sleep(delay);
+    }
+}
+
+fn with_jitter(delay: Duration, jitter: f64) -> Duration {
+    if jitter <= 0.0 || delay == Duration::from_secs(0) {
+        return delay;
+    }
+    let factor = rand::thread_rng().gen_range(1.0 - jitter, 1.0 + jitter);
+    Duration::from_secs_f64(delay.as_secs_f64() * factor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(100),
+            multiplier: 2.0,
+            max_delay: Duration::from_millis(300),
+            jitter: 0.0,
+            retryable,
+        }
+    }
+
+    // Runs `policy` against attempts failing with `errors` in turn, then succeeding. The attempts made and
+    // the delays slept between them
+    fn run(policy: &RetryPolicy, mut errors: Vec<RavenError>) -> (Result<u32, Box<dyn Error>>, u32, Vec<Duration>) {
+        errors.reverse();
+        let mut attempts = 0;
+        let mut delays = Vec::new();
+        let result = retry_with_sleeper(policy, "Testing", &mut |delay| delays.push(delay), || {
+            attempts += 1;
+            match errors.pop() {
+                Some(error) => Err(Box::new(error)),
+                None => Ok(attempts),
+            }
+        });
+        (result, attempts, delays)
+    }
+
+    fn unavailable() -> RavenError {
+        RavenError::InReachHttp { status: 503 }
+    }
+
+    #[test]
+    fn retries_back_off_up_to_the_max_delay() {
+        let (result, attempts, delays) = run(&policy(5), vec![unavailable(), unavailable(), unavailable()]);
+        assert_eq!(result.unwrap(), 4);
+        assert_eq!(attempts, 4);
+        assert_eq!(delays, [100, 200, 300].map(Duration::from_millis));
+    }
+
+    #[test]
+    fn gives_up_after_max_attempts() {
+        let (result, attempts, delays) = run(&policy(3), vec![unavailable(), unavailable(), unavailable(), unavailable()]);
+        assert!(result.is_err());
+        assert_eq!(attempts, 3);
+        assert_eq!(delays.len(), 2);
+    }
+
+    #[test]
+    fn permanent_failures_are_not_retried() {
+        let (result, attempts, delays) = run(&policy(5), vec![RavenError::ReplyUrlExpired]);
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+        assert!(delays.is_empty());
+
+        let (_, attempts, _) = run(&policy(5), vec![RavenError::InReachHttp { status: 404 }]);
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn a_rate_limit_waits_at_least_as_long_as_asked() {
+        let limited = RavenError::RateLimited {
+            retry_after_seconds: Some(2),
+        };
+        let (result, _, delays) = run(&policy(2), vec![limited]);
+        assert!(result.is_ok());
+        assert_eq!(delays, [Duration::from_secs(2)]);
+    }
+
+    #[test]
+    fn jitter_stays_within_its_fraction() {
+        for _ in 0..100 {
+            let delay = with_jitter(Duration::from_millis(1000), 0.1);
+            assert!(delay >= Duration::from_millis(900) && delay <= Duration::from_millis(1100), "{:?}", delay);
+        }
+        assert_eq!(with_jitter(Duration::from_millis(1000), 0.0), Duration::from_millis(1000));
+    }
+}
@@ -0,0 +1,441 @@
+use std::sync::Arc;
+
+use chrono::NaiveTime;
+use serde::Deserialize;
+
+use crate::config::{Config, RoutingConfig, RoutingRule};
+use crate::error::RavenError;
+use crate::notify::Notifier;
+
+pub const INREACH_CHANNEL: &str = "inreach";
+pub const TWILIO_CHANNEL: &str = "twilio";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    // A changed forecast without trend alerts
+    Changed,
+    // A changed forecast leading with trend alerts
+    Alert,
+    // A run of failed cycles, once it's long enough and then at most once per repeat period
+    Error,
+    // The first good cycle after a run of failures that was notified
+    Recovery,
+    // No new forecast by an expected-update deadline, its reminders and the all-clear
+    MissedUpdate,
+    // The forecast page's structure drifting from its baseline, for maintainers
+    Drift,
+    // Sent parts still missing from the MapShare conversation after the second delivery check
+    Undelivered,
+}
+
+// Changed and recoveries are info, alerts, errors, missed updates, drift and undelivered sends are
+// warnings, an error where every source failed is critical
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutingMode {
+    // Only the first matching rule's channels, rules read top to bottom
+    #[default]
+    FirstMatch,
+    // Every matching rule's channels together
+    AllMatches,
+}
+
+// Where an event goes
+pub struct Destinations {
+    pub inreach: bool,
+    pub notifiers: Vec<Arc<dyn Notifier>>,
+}
+
+impl RoutingRule {
+    fn matches(&self, kind: EventKind, severity: Severity, now: NaiveTime) -> bool {
+        (self.events.is_empty() || self.events.contains(&kind))
+            && severity >= self.min_severity
+            && self
+                .between
+                .as_deref()
+                .is_none_or(|window| parse_window(window).is_ok_and(|(start, end)| in_window(now, start, end)))
+    }
+}
+
+// The channels the rules pick for an event at `now`, None when no rule matched
+pub fn route(config: &RoutingConfig, kind: EventKind, severity: Severity, now: NaiveTime) -> Option<Vec<String>> {
+    let mut matched = config.rules.iter().filter(|rule| rule.matches(kind, severity, now)).peekable();
+    matched.peek()?;
+
+    match config.mode {
+        RoutingMode::FirstMatch => matched.next().map(|rule| rule.channels.clone()),
+        RoutingMode::AllMatches => {
+            let mut channels = Vec::<String>::new();
+            for channel in matched.flat_map(|rule| &rule.channels) {
+                if !channels.contains(channel) {
+                    channels.push(channel.clone());
+                }
+            }
+            Some(channels)
+        }
+    }
+}
+
+// Whether the rules hold a forecast at `now` instead of sending it anywhere
+pub fn holds(config: &RoutingConfig, kind: EventKind, severity: Severity, now: NaiveTime) -> bool {
+    let mut matched = config.rules.iter().filter(|rule| rule.matches(kind, severity, now));
+    match config.mode {
+        RoutingMode::FirstMatch => matched.next().is_some_and(|rule| rule.hold),
+        RoutingMode::AllMatches => matched.any(|rule| rule.hold),
+    }
+}
+
+// A changed forecast's destinations. Without rules, or when none match, it goes everywhere as before
+pub fn forecast_destinations(
+    config: &Config,
+    notifiers: Vec<Arc<dyn Notifier>>,
+    kind: EventKind,
+    severity: Severity,
+    now: NaiveTime,
+) -> Destinations {
+    match config
+        .routing
+        .as_ref()
+        .and_then(|routing| route(routing, kind, severity, now))
+    {
+        Some(channels) => select(&channels, notifiers),
+        None => Destinations {
+            inreach: true,
+            notifiers,
+        },
+    }
+}
+
+// An error's destinations. Without a matching rule it goes to every channel but the inReach, so a
+// broken raven is heard about without it spending inReach messages
+pub fn error_destinations(
+    config: &Config,
+    notifiers: Vec<Arc<dyn Notifier>>,
+    severity: Severity,
+    now: NaiveTime,
+) -> Destinations {
+    match config
+        .routing
+        .as_ref()
+        .and_then(|routing| route(routing, EventKind::Error, severity, now))
+    {
+        Some(channels) => select(&channels, notifiers),
+        None => Destinations {
+            inreach: false,
+            notifiers,
+        },
+    }
+}
+
+// A recovery's destinations. Without a rule for recoveries it follows wherever a failure would have
+// gone, so every channel that heard about the failure hears it's over
+pub fn recovery_destinations(config: &Config, notifiers: Vec<Arc<dyn Notifier>>, now: NaiveTime) -> Destinations {
+    match config
+        .routing
+        .as_ref()
+        .and_then(|routing| route(routing, EventKind::Recovery, Severity::Info, now))
+    {
+        Some(channels) => select(&channels, notifiers),
+        None => error_destinations(config, notifiers, Severity::Warning, now),
+    }
+}
+
+// A missed update's destinations. Without a matching rule it goes to every channel but the inReach,
+// which is kept for forecasts
+pub fn missed_update_destinations(
+    config: &Config,
+    notifiers: Vec<Arc<dyn Notifier>>,
+    severity: Severity,
+    now: NaiveTime,
+) -> Destinations {
+    match config
+        .routing
+        .as_ref()
+        .and_then(|routing| route(routing, EventKind::MissedUpdate, severity, now))
+    {
+        Some(channels) => select(&channels, notifiers),
+        None => Destinations {
+            inreach: false,
+            notifiers,
+        },
+    }
+}
+
+// A structure drift's destinations. It's only for whoever maintains the selectors, so only rules
+// that list drift among their events send it anywhere
+pub fn drift_destinations(config: &Config, notifiers: Vec<Arc<dyn Notifier>>, now: NaiveTime) -> Destinations {
+    let channels = config.routing.as_ref().and_then(|routing| {
+        let explicit = RoutingConfig {
+            mode: routing.mode,
+            rules: routing
+                .rules
+                .iter()
+                .filter(|rule| rule.events.contains(&EventKind::Drift))
+                .cloned()
+                .collect(),
+        };
+        route(&explicit, EventKind::Drift, Severity::Warning, now)
+    });
+    match channels {
+        Some(channels) => select(&channels, notifiers),
+        None => Destinations {
+            inreach: false,
+            notifiers: Vec::new(),
+        },
+    }
+}
+
+// An undelivered send's destinations. Without a matching rule it goes to every channel but the inReach,
+// which is what it's about
+pub fn undelivered_destinations(config: &Config, notifiers: Vec<Arc<dyn Notifier>>, now: NaiveTime) -> Destinations {
+    match config
+        .routing
+        .as_ref()
+        .and_then(|routing| route(routing, EventKind::Undelivered, Severity::Warning, now))
+    {
+        Some(channels) => select(&channels, notifiers),
+        None => Destinations {
+            inreach: false,
+            notifiers,
+        },
+    }
+}
+
+fn select(channels: &[String], notifiers: Vec<Arc<dyn Notifier>>) -> Destinations {
+    Destinations {
+        inreach: channels.iter().any(|channel| channel == INREACH_CHANNEL),
+        notifiers: notifiers
+            .into_iter()
+            .filter(|notifier| notifier.channels().iter().any(|name| channels.contains(name)))
+            .collect(),
+    }
+}
+
+// Every name a rule may use: inreach, twilio for all recipients and twilio:<name> for a named one.
+// Worked out from the config alone so validation doesn't need credentials
+pub fn known_channels(config: &Config) -> Vec<String> {
+    let mut channels = vec![INREACH_CHANNEL.to_string()];
+    if let Some(twilio) = &config.twilio {
+        channels.push(TWILIO_CHANNEL.to_string());
+        channels.extend(
+            twilio
+                .recipients
+                .iter()
+                .filter_map(|recipient| recipient.name.as_ref())
+                .map(|name| format!("{}:{}", TWILIO_CHANNEL, name)),
+        );
+    }
+    channels
+}
+
+// Unknown channels and unparseable windows fail startup rather than quietly dropping events
+pub fn validate_routing(config: &Config) -> Result<(), RavenError> {
+    let routing = match &config.routing {
+        Some(routing) => routing,
+        None => return Ok(()),
+    };
+
+    let known = known_channels(config);
+    for (i, rule) in routing.rules.iter().enumerate() {
+        if let Some(unknown) = rule.channels.iter().find(|channel| !known.contains(channel)) {
+            return Err(RavenError::InvalidConfig(format!(
+                "routing rule {} sends to unknown channel {}, configured channels are {}",
+                i + 1,
+                unknown,
+                known.join(", ")
+            )));
+        }
+        if let Some(window) = &rule.between {
+            parse_window(window).map_err(|e| {
+                RavenError::InvalidConfig(format!("routing rule {} has an invalid window: {}", i + 1, e))
+            })?;
+        }
+    }
+    Ok(())
+}
+
+// "21:00-05:00", local time, wrapping past midnight when the end is before the start
+pub fn parse_window(window: &str) -> Result<(NaiveTime, NaiveTime), String> {
+    let (start, end) = window
+        .split_once('-')
+        .ok_or_else(|| format!("{} should look like 21:00-05:00", window))?;
+    let parse = |time: &str| {
+        NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|e| format!("{}: {}", time.trim(), e))
+    };
+    Ok((parse(start)?, parse(end)?))
+}
+
+pub fn in_window(now: NaiveTime, start: NaiveTime, end: NaiveTime) -> bool {
+    if start <= end {
+        now >= start && now < end
+    } else {
+        now >= start || now < end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Constraints;
+
+    struct Recipient(&'static str);
+
+    impl Notifier for Recipient {
+        fn name(&self) -> String {
+            self.0.to_string()
+        }
+
+        fn channels(&self) -> Vec<String> {
+            vec![TWILIO_CHANNEL.to_string(), format!("{}:{}", TWILIO_CHANNEL, self.0)]
+        }
+
+        fn max_messages_per_day(&self) -> Option<u32> {
+            None
+        }
+
+        fn constraints(&self) -> Constraints {
+            Constraints::SMS
+        }
+
+        fn notify(&self, _: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+            Ok(())
+        }
+
+        fn check(&self) -> Result<(), Box<dyn std::error::Error>> {
+            Ok(())
+        }
+    }
+
+    fn recipients() -> Vec<Arc<dyn Notifier>> {
+        vec![Arc::new(Recipient("alice")), Arc::new(Recipient("bob"))]
+    }
+
+    fn names(destinations: &Destinations) -> Vec<String> {
+        destinations.notifiers.iter().map(|notifier| notifier.name()).collect()
+    }
+
+    // Alerts to the inReach and every Twilio recipient, anything overnight to alice only, the rest to Twilio
+    const RULES: &str = r#"
+        [[rules]]
+        events = ["alert"]
+        channels = ["inreach", "twilio"]
+
+        [[rules]]
+        between = "21:00-05:00"
+        channels = ["twilio:alice"]
+
+        [[rules]]
+        events = ["changed", "error"]
+        channels = ["twilio"]
+    "#;
+
+    fn routing(mode: &str) -> RoutingConfig {
+        toml::from_str(&format!("mode = \"{}\"\n{}", mode, RULES)).unwrap()
+    }
+
+    fn at(time: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(time, "%H:%M").unwrap()
+    }
+
+    #[test]
+    fn first_match_takes_the_first_rule_that_matches() {
+        let routing = routing("first_match");
+        assert_eq!(route(&routing, EventKind::Alert, Severity::Warning, at("22:00")).unwrap(), ["inreach", "twilio"]);
+        assert_eq!(route(&routing, EventKind::Changed, Severity::Info, at("22:00")).unwrap(), ["twilio:alice"]);
+        assert_eq!(route(&routing, EventKind::Changed, Severity::Info, at("12:00")).unwrap(), ["twilio"]);
+    }
+
+    #[test]
+    fn all_matches_combines_every_matching_rule() {
+        let routing = routing("all_matches");
+        assert_eq!(
+            route(&routing, EventKind::Alert, Severity::Warning, at("22:00")).unwrap(),
+            ["inreach", "twilio", "twilio:alice"]
+        );
+        assert_eq!(
+            route(&routing, EventKind::Changed, Severity::Info, at("04:59")).unwrap(),
+            ["twilio:alice", "twilio"]
+        );
+        assert_eq!(route(&routing, EventKind::Changed, Severity::Info, at("05:00")).unwrap(), ["twilio"]);
+    }
+
+    #[test]
+    fn no_matching_rule_is_none() {
+        assert!(route(&routing("first_match"), EventKind::MissedUpdate, Severity::Warning, at("12:00")).is_none());
+        assert!(route(&routing("all_matches"), EventKind::MissedUpdate, Severity::Warning, at("12:00")).is_none());
+    }
+
+    #[test]
+    fn a_rule_needs_at_least_its_severity() {
+        let routing: RoutingConfig = toml::from_str(
+            r#"
+            [[rules]]
+            min_severity = "critical"
+            channels = ["inreach"]
+            "#,
+        )
+        .unwrap();
+        assert!(route(&routing, EventKind::Error, Severity::Warning, at("12:00")).is_none());
+        assert_eq!(route(&routing, EventKind::Error, Severity::Critical, at("12:00")).unwrap(), ["inreach"]);
+    }
+
+    #[test]
+    fn held_forecasts_wait_for_the_window_to_end() {
+        let quiet: RoutingConfig = toml::from_str(
+            r#"
+            [[rules]]
+            events = ["changed"]
+            between = "22:00-06:00"
+            hold = true
+            "#,
+        )
+        .unwrap();
+        assert!(holds(&quiet, EventKind::Changed, Severity::Info, at("05:59")));
+        assert!(!holds(&quiet, EventKind::Changed, Severity::Info, at("06:00")));
+        assert!(!holds(&quiet, EventKind::Alert, Severity::Warning, at("05:59")));
+        assert!(!holds(&routing("first_match"), EventKind::Changed, Severity::Info, at("22:00")));
+    }
+
+    #[test]
+    fn unknown_channels_fail_validation() {
+        let config = Config::parse("[routing]\n[[routing.rules]]\nchannels = [\"pushover\"]").unwrap();
+        let error = validate_routing(&config).unwrap_err().to_string();
+        assert!(error.contains("unknown channel pushover"), "{}", error);
+    }
+
+    #[test]
+    fn errors_and_recoveries_go_everywhere_but_the_inreach_by_default() {
+        let config = Config::parse("").unwrap();
+        let error = error_destinations(&config, recipients(), Severity::Warning, at("12:00"));
+        assert!(!error.inreach);
+        assert_eq!(names(&error), ["alice", "bob"]);
+        let recovery = recovery_destinations(&config, recipients(), at("12:00"));
+        assert!(!recovery.inreach);
+        assert_eq!(names(&recovery), ["alice", "bob"]);
+
+        // Rules that don't cover errors leave them on the default too
+        let config =
+            Config::parse("[routing]\n[[routing.rules]]\nevents = [\"alert\"]\nchannels = [\"inreach\"]").unwrap();
+        let error = error_destinations(&config, recipients(), Severity::Warning, at("12:00"));
+        assert!(!error.inreach);
+        assert_eq!(names(&error), ["alice", "bob"]);
+    }
+
+    #[test]
+    fn recoveries_follow_errors_without_a_rule_of_their_own() {
+        let config =
+            Config::parse("[routing]\n[[routing.rules]]\nevents = [\"error\"]\nchannels = [\"twilio:bob\"]").unwrap();
+        let recovery = recovery_destinations(&config, recipients(), at("12:00"));
+        assert_eq!(names(&recovery), ["bob"]);
+    }
+}
@@ -0,0 +1,88 @@
+use chrono::{Duration, NaiveDateTime, NaiveTime};
+use chrono_tz::Tz;
+
+use crate::config::{Config, ScheduleConfig};
+use crate::error::RavenError;
+
+// What the expected-update check wants to say this cycle
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScheduleNotice {
+    // A deadline passed without a new issue time
+    Missed { deadline: NaiveDateTime },
+    // Still missing, another reminder is due
+    Reminder { deadline: NaiveDateTime },
+    // The update arrived after a missed deadline
+    AllClear,
+}
+
+// The latest deadline at or before `now` that passed with no update since the deadline before it.
+// Times are in the schedule's timezone, `last_update` None means nothing has been seen yet so nothing can be late
+pub fn missed_deadline(
+    deadlines: &[NaiveTime],
+    last_update: Option<NaiveDateTime>,
+    now: NaiveDateTime,
+) -> Option<NaiveDateTime> {
+    let last_update = last_update?;
+    // Every deadline from the day before yesterday on, enough to find the last one passed and the
+    // one before it whatever the schedule
+    let mut candidates = (0..3)
+        .rev()
+        .flat_map(|days_ago| {
+            let day = now.date() - Duration::days(days_ago);
+            deadlines.iter().map(move |time| day.and_time(*time))
+        })
+        .filter(|deadline| *deadline <= now)
+        .collect::<Vec<_>>();
+    candidates.sort();
+    candidates.dedup();
+
+    let (deadline, previous) = match candidates.as_slice() {
+        [.., previous, deadline] => (*deadline, *previous),
+        _ => return None,
+    };
+    if last_update <= previous {
+        Some(deadline)
+    } else {
+        None
+    }
+}
+
+// Whether to notify this cycle, given the deadline that's missed now (if any), the one already
+// notified and when its last reminder went out. `reminder` None means only the first notice
+pub fn notice(
+    missed: Option<NaiveDateTime>,
+    notified: Option<(NaiveDateTime, NaiveDateTime)>,
+    reminder: Option<Duration>,
+    now: NaiveDateTime,
+) -> Option<ScheduleNotice> {
+    match (missed, notified) {
+        (Some(deadline), None) => Some(ScheduleNotice::Missed { deadline }),
+        (Some(_), Some((deadline, reminded))) => {
+            let due = reminder.is_some_and(|reminder| now.signed_duration_since(reminded) >= reminder);
+            due.then_some(ScheduleNotice::Reminder { deadline })
+        }
+        (None, Some(_)) => Some(ScheduleNotice::AllClear),
+        (None, None) => None,
+    }
+}
+
+pub fn deadlines(config: &ScheduleConfig) -> Result<Vec<NaiveTime>, String> {
+    config
+        .deadlines
+        .iter()
+        .map(|time| NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|e| format!("{}: {}", time, e)))
+        .collect()
+}
+
+pub fn timezone(config: &ScheduleConfig) -> Result<Tz, String> {
+    config.timezone.parse()
+}
+
+pub fn validate_schedule(config: &Config) -> Result<(), RavenError> {
+    if let Some(schedule) = &config.schedule {
+        deadlines(schedule)
+            .map_err(|e| RavenError::InvalidConfig(format!("schedule has an invalid deadline: {}", e)))?;
+        timezone(schedule).map_err(|e| RavenError::InvalidConfig(format!("schedule has an invalid timezone: {}", e)))?;
+    }
+    Ok(())
+}
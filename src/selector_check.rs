@@ -0,0 +1,121 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use headless_chrome::Tab;
+
+use crate::config::{CaptureTime, Config, Source};
+
+const PREVIEW_LENGTH: usize = 80;
+
+struct SelectorCheck {
+    name: &'static str,
+    selector: String,
+    // Fallback sources and extra products are reported but don't fail the run, the daemon copes without them
+    required: bool,
+}
+
+// Navigates to every configured page and reports whether each selector resolves to non-empty text,
+// nothing is ever clicked or sent. Returns whether all required selectors passed.
+pub fn validate_selectors(tab: &Arc<Tab>, config: &Config) -> Result<bool, Box<dyn Error>> {
+    let mut passed = check_source(tab, &config.source, true);
+
+    for source in &config.fallback.sources {
+        check_source(tab, source, false);
+    }
+    // Extra products fail on their own at runtime too
+    for product in &config.products {
+        check_source(tab, &product.source, false);
+    }
+
+    match config.inreach.reply_url() {
+        Some(url) => {
+            let checks = vec![
+                SelectorCheck {
+                    name: "message",
+                    selector: config.inreach.message_selector.clone(),
+                    required: true,
+                },
+                SelectorCheck {
+                    name: "send",
+                    selector: config.inreach.send_selector.clone(),
+                    required: true,
+                },
+            ];
+            passed &= check_page(tab, "inReach reply page", &url, &checks);
+        }
+        None => println!("Skipping inReach reply page, {} is not set", config.inreach.reply_url_env),
+    }
+
+    Ok(passed)
+}
+
+fn check_source(tab: &Arc<Tab>, source: &Source, required: bool) -> bool {
+    let selectors = &source.selectors;
+    let mut checks = vec![
+        SelectorCheck {
+            name: "outlook",
+            selector: selectors.outlook.clone(),
+            required,
+        },
+        SelectorCheck {
+            name: "full",
+            selector: selectors.full.clone(),
+            required,
+        },
+        SelectorCheck {
+            name: "days",
+            selector: selectors.days.clone(),
+            required,
+        },
+    ];
+    if let Some(CaptureTime::Element { selector, .. }) = &source.capture_time {
+        checks.push(SelectorCheck {
+            name: "capture time",
+            selector: selector.clone(),
+            required,
+        });
+    }
+
+    check_page(tab, &source.name, &source.url, &checks)
+}
+
+fn check_page(tab: &Arc<Tab>, name: &str, url: &str, checks: &[SelectorCheck]) -> bool {
+    println!("{} ({})", name, url);
+
+    if let Err(e) = tab.navigate_to(url) {
+        println!("  FAIL could not navigate: {}", e);
+        return checks.iter().all(|check| !check.required);
+    }
+
+    let mut passed = true;
+    for check in checks {
+        match resolve_text(tab, &check.selector) {
+            Ok(text) => {
+                let preview = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                let preview = preview.chars().take(PREVIEW_LENGTH).collect::<String>();
+                println!("  PASS {} `{}`: {}", check.name, check.selector, preview);
+            }
+            Err(e) => {
+                let label = if check.required { "FAIL" } else { "WARN" };
+                println!("  {} {} `{}`: {}", label, check.name, check.selector, e);
+                passed &= !check.required;
+            }
+        }
+    }
+
+    passed
+}
+
+// Joins the text of every matching element, a selector only passes if that isn't blank
+fn resolve_text(tab: &Arc<Tab>, selector: &str) -> Result<String, Box<dyn Error>> {
+    let mut texts = Vec::new();
+    for element in tab.wait_for_elements(selector)? {
+        texts.push(element.get_inner_text()?);
+    }
+
+    let text = texts.join(" ");
+    if text.trim().is_empty() {
+        return Err("matched only empty elements".into());
+    }
+    Ok(text)
+}
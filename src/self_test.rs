@@ -0,0 +1,372 @@
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::process;
+use std::sync::Arc;
+
+use chrono::Utc;
+use log::warn;
+
+use crate::abbreviation::{clean_block, UI_LABELS};
+use crate::change::{ChangeDetector, ChangeResult, Revision};
+use crate::clock::SystemClock;
+use crate::config::{Config, InterceptConfig};
+use crate::effects::Effects;
+use crate::error::RavenError;
+use crate::fetch::FetchedForecast;
+use crate::forecast::Forecast;
+use crate::format::{inreach_messages, render, template, Constraints, Content, Summary};
+use crate::intercept::extract_forecast;
+use crate::notify::{Notifier, TwilioNotifier};
+use crate::output::{
+    persist_forecast, persist_structured_forecast, provenance_matches, read_provenance, read_structured_forecast,
+    OutputFiles, Provenance,
+};
+use crate::retry::RetryPolicy;
+use crate::routing::known_channels;
+use crate::sms::TEXT_MESSAGE_LENGTH;
+use crate::trend::trend_alerts;
+use crate::validation::validate_forecast;
+use crate::version::ConfigFingerprint;
+
+// A saved copy of the higher summits page, so the forecast stages never need the network
+const FIXTURE: &str = include_str!("../fixtures/higher_summits.html");
+
+pub struct Stage {
+    pub name: &'static str,
+    // What was checked, or why it failed
+    pub result: Result<String, String>,
+}
+
+// Checks a deployment stage by stage, the forecast ones against the bundled fixture. Only the
+// notifier stage reaches out, and only to connect
+pub fn self_test(config: &Config, allow_fast_polling: bool) -> Vec<Stage> {
+    let mut stages = vec![stage("config", check_config(config, allow_fast_polling))];
+
+    let parsed = parse_fixture(config).map(|fetched| {
+        let forecast = Forecast::parse(&fetched.full, &fetched.day_blocks);
+        (fetched, forecast)
+    });
+    stages.push(stage(
+        "parse",
+        match &parsed {
+            Ok((_, forecast)) => check_parsed(forecast),
+            Err(e) => Err(e.to_string().into()),
+        },
+    ));
+
+    match &parsed {
+        Ok((fetched, forecast)) => {
+            let previous = synthetic_previous(fetched);
+            stages.push(stage("change detection", check_change(config, fetched, forecast, &previous)));
+            stages.push(stage("render", check_render(config, fetched, forecast, &previous)));
+            stages.push(stage("storage", check_storage(config, fetched, forecast)));
+        }
+        Err(_) => {
+            for name in ["change detection", "render", "storage"] {
+                stages.push(stage(name, Err("skipped, the fixture didn't parse".into())));
+            }
+        }
+    }
+
+    stages.push(stage("notifiers", check_notifiers(config)));
+    stages
+}
+
+fn stage(name: &'static str, result: Result<String, Box<dyn Error>>) -> Stage {
+    Stage {
+        name,
+        result: result.map_err(|e| e.to_string()),
+    }
+}
+
+fn check_config(config: &Config, allow_fast_polling: bool) -> Result<String, Box<dyn Error>> {
+    config.validate(allow_fast_polling)?;
+    Ok(format!(
+        "valid, {} products, OCR {}",
+        config.products.len(),
+        if cfg!(feature = "ocr") { "built in" } else { "not built in" }
+    ))
+}
+
+// The fixture as an intercepted response would be read, then validated like any fetch, and a padded
+// copy of it rejected
+fn parse_fixture(config: &Config) -> Result<FetchedForecast, Box<dyn Error>> {
+    let intercept = InterceptConfig {
+        url_contains: Vec::new(),
+        body_contains: Vec::new(),
+        json_pointer: None,
+        container: Some("id=\"SummitOutlook\"".to_string()),
+        timeout_seconds: 0,
+    };
+    let (full, day_blocks) = extract_forecast(FIXTURE, &intercept).ok_or("no outlook in the fixture")?;
+    let fetched = FetchedForecast {
+        full,
+        day_blocks: day_blocks
+            .iter()
+            .filter_map(|block| clean_block(block, None, &config.source.abbreviation.strip, &[]))
+            .collect(),
+        source: "self-test".to_string(),
+        captured: Utc::now(),
+        fallback: false,
+    };
+    validate_forecast(&fetched, &config.validation, Utc::now())?;
+
+    // The outlook padded past validation.max_bytes, as a site bug once served it
+    let oversized = FetchedForecast {
+        full: fetched.full.repeat(config.validation.max_bytes / fetched.full.len().max(1) + 1),
+        ..fetched.clone()
+    };
+    if !matches!(
+        validate_forecast(&oversized, &config.validation, Utc::now()),
+        Err(RavenError::ForecastTooLong { .. })
+    ) {
+        return Err(format!("a {} byte forecast wasn't rejected as too long", oversized.full.len()).into());
+    }
+
+    let abbreviated = fetched.abbreviated().to_lowercase();
+    if let Some(label) = UI_LABELS.iter().find(|label| abbreviated.contains(&label.to_lowercase())) {
+        return Err(format!("{:?} was left in the abbreviated forecast", label).into());
+    }
+    Ok(fetched)
+}
+
+fn check_parsed(forecast: &Forecast) -> Result<String, Box<dyn Error>> {
+    let issued = forecast.issued_at().ok_or("no issue time parsed")?;
+    if forecast.days.len() != 3 {
+        return Err(format!("{} days parsed, the fixture has 3", forecast.days.len()).into());
+    }
+    if let Some(day) = forecast
+        .days
+        .iter()
+        .find(|day| day.wind.is_none() || (day.high_f.is_none() && day.low_f.is_none()))
+    {
+        return Err(format!("{} parsed without its temperature or wind", day.label).into());
+    }
+    Ok(format!("{} days, issued {}", forecast.days.len(), issued.format("%Y-%m-%d %H:%M")))
+}
+
+// The fixture with tonight's low a few degrees warmer, as an earlier revision might have had it
+fn synthetic_previous(fetched: &FetchedForecast) -> FetchedForecast {
+    FetchedForecast {
+        day_blocks: fetched
+            .day_blocks
+            .iter()
+            .map(|block| block.replace("20 below", "15 below"))
+            .collect(),
+        ..fetched.clone()
+    }
+}
+
+fn check_change(
+    config: &Config,
+    fetched: &FetchedForecast,
+    forecast: &Forecast,
+    previous: &FetchedForecast,
+) -> Result<String, Box<dyn Error>> {
+    let detector = ChangeDetector::new(config.change.comparator);
+    let abbreviated = fetched.abbreviated();
+    let candidate = Revision {
+        text: &abbreviated,
+        forecast: Some(forecast),
+    };
+
+    if !matches!(detector.detect(None, candidate, &[]), ChangeResult::FirstRun) {
+        return Err("no previous revision wasn't treated as a first run".into());
+    }
+    if !matches!(detector.detect(Some(candidate), candidate, &[]), ChangeResult::Unchanged) {
+        return Err("an identical revision was seen as a change".into());
+    }
+
+    let previous_abbreviated = previous.abbreviated();
+    let previous_forecast = Forecast::parse(&previous.full, &previous.day_blocks);
+    let previous = Revision {
+        text: &previous_abbreviated,
+        forecast: Some(&previous_forecast),
+    };
+    match detector.detect(Some(previous), candidate, &[]) {
+        ChangeResult::Changed { diff } => Ok(format!(
+            "{:?} comparator, the synthetic revision changed {} numbers",
+            config.change.comparator,
+            diff.len()
+        )),
+        other => Err(format!("the synthetic revision wasn't seen as a change ({:?})", other).into()),
+    }
+}
+
+// The compact messages for the inReach and every channel with a template, each part checked
+// against what an SMS can carry
+fn check_render(
+    config: &Config,
+    fetched: &FetchedForecast,
+    forecast: &Forecast,
+    previous: &FetchedForecast,
+) -> Result<String, Box<dyn Error>> {
+    let lang = config.language();
+    let previous_forecast = Forecast::parse(&previous.full, &previous.day_blocks);
+    let content = Content {
+        alerts: trend_alerts(&previous_forecast, forecast, lang),
+        body: fetched.abbreviated(),
+        summary: config.sms.summary_message.then(|| Summary {
+            last_sent: Some(previous_forecast.clone()),
+            forecast: forecast.clone(),
+        }),
+        ..Content::default()
+    };
+
+    let messages = inreach_messages(config, &content);
+    check_parts("inreach", &messages)?;
+    for channel in known_channels(config) {
+        if let Some(template) = template(config, std::slice::from_ref(&channel)) {
+            check_parts(&channel, &render(&content, Constraints::SMS, Some(template), lang))?;
+        }
+    }
+    Ok(format!("{} inReach parts, each within {} ASCII characters", messages.len(), TEXT_MESSAGE_LENGTH))
+}
+
+fn check_parts(channel: &str, messages: &[String]) -> Result<(), Box<dyn Error>> {
+    if messages.is_empty() {
+        return Err(format!("nothing rendered for {}", channel).into());
+    }
+    for (i, message) in messages.iter().enumerate() {
+        let numbering = format!("{}/{} ", i + 1, messages.len());
+        if message.chars().count() > TEXT_MESSAGE_LENGTH {
+            return Err(format!("{} part {} is {} characters", channel, i + 1, message.chars().count()).into());
+        }
+        if !message.is_ascii() {
+            return Err(format!("{} part {} isn't plain ASCII", channel, i + 1).into());
+        }
+        if !message.starts_with(&numbering) {
+            return Err(format!("{} part {} isn't numbered {:?}", channel, i + 1, numbering.trim()).into());
+        }
+    }
+    Ok(())
+}
+
+// Writes the fixture's outputs to a temporary directory and reads them back, then checks every
+// directory the config writes to can be written
+fn check_storage(config: &Config, fetched: &FetchedForecast, forecast: &Forecast) -> Result<String, Box<dyn Error>> {
+    let effects = Effects::new(false);
+    let dir = env::temp_dir().join(format!("raven-self-test-{}", process::id()));
+    effects.create_dir_all(&dir)?;
+    let round_trip = round_trip(&effects, &dir, fetched, forecast, config);
+    if let Err(e) = fs::remove_dir_all(&dir) {
+        warn!("Could not remove {}: {}", dir.display(), e);
+    }
+    round_trip?;
+
+    let dirs = writable_dirs(config);
+    for dir in &dirs {
+        let probe = dir.join(".raven-self-test");
+        effects
+            .create_dir_all(dir)
+            .and_then(|_| effects.write(&probe, "raven self-test"))
+            .and_then(|_| effects.remove(&probe))
+            .map_err(|e| format!("{} isn't writable: {}", dir.display(), e))?;
+    }
+    Ok(format!(
+        "wrote and read back the outputs, {} writable",
+        dirs.iter().map(|dir| dir.display().to_string()).collect::<Vec<_>>().join(", ")
+    ))
+}
+
+fn round_trip(
+    effects: &Effects,
+    dir: &Path,
+    fetched: &FetchedForecast,
+    forecast: &Forecast,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let files = OutputFiles::with_prefix(&format!("{}{}", dir.display(), MAIN_SEPARATOR));
+    let full = fetched.full_text();
+    let provenance = Provenance::new(fetched, None, forecast, &ConfigFingerprint::of(config).hash);
+    persist_forecast(effects, &full, &files.full, &provenance)?;
+    persist_structured_forecast(effects, &files, forecast, config.language(), &provenance)?;
+
+    if effects.read(&files.full).as_deref() != Some(full.as_str()) {
+        return Err(format!("{} read back differently", files.full.display()).into());
+    }
+    if read_structured_forecast(effects, &files).as_ref() != Some(forecast) {
+        return Err(format!("{} read back differently", files.json.display()).into());
+    }
+    let provenance = read_provenance(effects, &files.full);
+    if !provenance.is_some_and(|provenance| provenance_matches(effects, &files.full, &provenance)) {
+        return Err(format!("{} has no matching metadata", files.full.display()).into());
+    }
+    Ok(())
+}
+
+fn writable_dirs(config: &Config) -> Vec<PathBuf> {
+    let mut dirs = vec![PathBuf::from(".")];
+    if config.archive.enabled {
+        dirs.push(config.archive.dir.clone());
+    }
+    if config.html.enabled {
+        if let Some(parent) = config.html.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            dirs.push(parent.to_path_buf());
+        }
+    }
+    if config.output.day_files {
+        dirs.push(config.output.day_files_dir.clone());
+    }
+    dirs.extend(config.debug.capture_dir.clone());
+    dirs.dedup();
+    dirs
+}
+
+// Connects to every configured notifier without sending, retries are left out so a failure shows at once
+fn check_notifiers(config: &Config) -> Result<String, Box<dyn Error>> {
+    let twilio = match &config.twilio {
+        Some(twilio) => twilio,
+        None => return Ok("none configured".to_string()),
+    };
+    let retry = RetryPolicy::none();
+    let notifiers = TwilioNotifier::for_recipients(twilio, &config.user_agent, &retry, Arc::new(SystemClock))?;
+    for notifier in &notifiers {
+        notifier.check()?;
+    }
+    Ok(format!(
+        "reached {}",
+        notifiers.iter().map(|notifier| notifier.name()).collect::<Vec<_>>().join(", ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cycle::run_cycle;
+    use crate::source::FixtureSource;
+    use crate::storage::MemoryStorage;
+    use crate::validation::check_size;
+    use std::sync::Arc;
+
+    #[test]
+    fn an_oversized_forecast_leaves_the_last_good_one_alone() {
+        let config = Config::default();
+        let fetched = parse_fixture(&config).unwrap();
+        // Past the default 64 KiB
+        let oversized = FetchedForecast {
+            full: fetched.full.repeat(64 * 1024 / fetched.full.len() + 1),
+            ..fetched.clone()
+        };
+        assert!(matches!(
+            check_size(&oversized, &config.validation),
+            Err(RavenError::ForecastTooLong { max_bytes: 65536, .. })
+        ));
+
+        let effects = Effects::with_storage(false, Arc::new(MemoryStorage::new()));
+        let mut source = FixtureSource::new(vec![fetched, oversized]);
+        run_cycle(&config, &effects, &mut source, false).unwrap();
+        let files = OutputFiles::default();
+        let written = [&files.full, &files.abbreviated, &files.json].map(|path| effects.read(path));
+        assert!(written.iter().all(Option::is_some));
+
+        let error = match run_cycle(&config, &effects, &mut source, false) {
+            Ok(_) => panic!("the oversized forecast was accepted"),
+            Err(e) => e.to_string(),
+        };
+        assert!(error.contains("expected at most 65536"), "{}", error);
+        assert_eq!([&files.full, &files.abbreviated, &files.json].map(|path| effects.read(path)), written);
+    }
+}
@@ -0,0 +1,158 @@
+pub const TEXT_MESSAGE_LENGTH: usize = 160;
+
+// inReach messages are plain ASCII, fold the characters the forecast and our renderings use
+pub fn sanitize(text: &str) -> String {
+    let folded = text
+        .chars()
+        .filter_map(|c| match c {
+            '→' => Some('>'),
+            '‘' | '’' => Some('\''),
+            '“' | '”' => Some('"'),
+            '–' | '—' => Some('-'),
+            // Minus signs and the other hyphens, dropping them would turn -20F into 20F
+            '−' | '‒' | '‐' | '‑' | '⁃' | '﹣' | '－' => Some('-'),
+            '°' => None,
+            c if c.is_ascii() => Some(c),
+            _ => None,
+        })
+        .collect::<String>();
+
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+pub fn split_string_into_sms_message_lengths(forecast: &str, length: usize) -> Vec<String> {
+    let mut chars = forecast.chars();
+    (0..)
+        .map(|_| chars.by_ref().take(length).collect::<String>())
+        .take_while(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+}
+
+// Sanitizes, splits and numbers ("1/3 ...") the text so every part fits in one message
+pub fn prepare_messages(text: &str) -> Vec<String> {
+    prepare(text, 0, |_, _| String::new())
+}
+
+// Same as `prepare_messages`, but part 1 is a summary line built by `summary(total_parts, max_length)`
+pub fn prepare_messages_with_summary<F>(text: &str, summary: F) -> Vec<String>
+where
+    F: FnOnce(usize, usize) -> String,
+{
+    prepare(text, 1, summary)
+}
+
+fn prepare<F>(text: &str, leading_parts: usize, summary: F) -> Vec<String>
+where
+    F: FnOnce(usize, usize) -> String,
+{
+    let text = sanitize(text);
+
+    // The numbering prefix grows with the part count, so re-split until the count settles
+    let mut width = 1;
+    let chunks = loop {
+        let chunks = split_string_into_sms_message_lengths(&text, TEXT_MESSAGE_LENGTH - prefix_length(width));
+        let digits = (chunks.len() + leading_parts).to_string().len();
+        if digits <= width {
+            break chunks;
+        }
+        width = digits;
+    };
+
+    let total = chunks.len() + leading_parts;
+    let max_length = TEXT_MESSAGE_LENGTH - prefix_length(width);
+
+    let mut parts = Vec::with_capacity(total);
+    if leading_parts > 0 {
+        let summary = sanitize(&summary(total, max_length));
+        parts.push(summary.chars().take(max_length).collect::<String>());
+    }
+    parts.extend(chunks);
+
+    parts
+        .into_iter()
+        .enumerate()
+        .map(|(i, part)| format!("{}/{} {}", i + 1, total, part))
+        .collect()
+}
+
+// Adds a line to the last prepared message if it still fits there, otherwise as one more
+// unnumbered message when `allow_extra`, otherwise not at all
+pub fn append_line(messages: &[String], line: &str, allow_extra: bool) -> Vec<String> {
+    let line = sanitize(line);
+    let mut messages = messages.to_vec();
+    match messages.last_mut() {
+        Some(last) if last.chars().count() + 1 + line.chars().count() <= TEXT_MESSAGE_LENGTH => {
+            last.push(' ');
+            last.push_str(&line);
+        }
+        _ if allow_extra => messages.extend(split_string_into_sms_message_lengths(&line, TEXT_MESSAGE_LENGTH)),
+        _ => {}
+    }
+    messages
+}
+
+// "12/12 " for a two digit part count
+fn prefix_length(width: usize) -> usize {
+    width * 2 + 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The parts without their "2/7 " numbering, joined back up
+    fn unnumbered(parts: &[String]) -> String {
+        parts.iter().map(|part| part.split_once(' ').unwrap().1).collect()
+    }
+
+    #[test]
+    fn keeps_the_sign_of_a_temperature() {
+        assert_eq!(sanitize("−20°F"), "-20F");
+        assert_eq!(sanitize("‒5 to ‑10°F"), "-5 to -10F");
+        assert_eq!(prepare_messages("Lows around −20°F."), ["1/1 Lows around -20F."]);
+    }
+
+    #[test]
+    fn every_part_fits_in_one_message() {
+        let text = "Summit in the clouds with snow showers. ".repeat(25);
+        let parts = prepare_messages(&text);
+        assert_eq!(parts.len(), 7);
+        assert!(parts.iter().all(|part| part.chars().count() <= TEXT_MESSAGE_LENGTH));
+        assert!(parts[0].starts_with("1/7 "));
+        assert_eq!(unnumbered(&parts), sanitize(&text));
+    }
+
+    #[test]
+    fn a_two_digit_part_count_still_fits() {
+        let text = "NW winds 70-90 mph with higher gusts. ".repeat(60);
+        let parts = prepare_messages(&text);
+        assert!(parts.len() >= 10);
+        assert!(parts.iter().all(|part| part.chars().count() <= TEXT_MESSAGE_LENGTH));
+        assert!(parts.last().unwrap().starts_with(&format!("{0}/{0} ", parts.len())));
+        assert_eq!(unnumbered(&parts), sanitize(&text));
+    }
+
+    #[test]
+    fn the_summary_part_is_cut_to_one_message() {
+        let text = "Highs around 5F. ".repeat(20);
+        let parts = prepare_messages_with_summary(&text, |total, _| format!("{} parts {}", total, "x".repeat(400)));
+        assert!(parts.iter().all(|part| part.chars().count() <= TEXT_MESSAGE_LENGTH));
+        assert!(parts[0].starts_with(&format!("1/{0} {0} parts", parts.len())));
+        assert_eq!(unnumbered(&parts[1..]), sanitize(&text));
+    }
+
+    #[test]
+    fn sanitizes_to_ascii() {
+        assert_eq!(sanitize("W→NW  50–70, “gusty”\n -5°F"), "W>NW 50-70, \"gusty\" -5F");
+    }
+
+    #[test]
+    fn appends_where_it_fits() {
+        let parts = vec!["1/1 short".to_string()];
+        assert_eq!(append_line(&parts, "Summit now 3F", false), vec!["1/1 short Summit now 3F"]);
+
+        let full = vec!["x".repeat(TEXT_MESSAGE_LENGTH - 5)];
+        assert_eq!(append_line(&full, "Summit now 3F", false), full);
+        assert_eq!(append_line(&full, "Summit now 3F", true).len(), 2);
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use headless_chrome::{Browser, Tab};
+use log::info;
+
+use crate::config::{Config, Source};
+use crate::effects::Effects;
+use crate::fetch::{fetch_validated, fetch_with_fallback, launch_browser, FetchedForecast};
+use crate::forecast::Forecast;
+use crate::output::{read_structured_forecast, OutputFiles};
+use crate::pool::{self, Fetched, Job};
+use crate::validation::validate_forecast;
+
+// Where each cycle's forecast comes from, the live site in production and fixtures elsewhere
+pub trait ForecastSource {
+    fn fetch(&mut self, config: &Config, effects: &Effects) -> Result<FetchedForecast, Box<dyn Error>>;
+
+    // One of the configured extra products, fetched after `fetch` in the same cycle. `known` is the
+    // product's last forecast
+    fn fetch_product(
+        &mut self,
+        _config: &Config,
+        _effects: &Effects,
+        source: &Source,
+        _known: Option<&Forecast>,
+    ) -> Result<FetchedForecast, Box<dyn Error>> {
+        Err(format!("{} can't be fetched from this source", source.name).into())
+    }
+
+    // The tab the last fetch used, reused by the browser inReach transport
+    fn tab(&self) -> Option<&Arc<Tab>> {
+        None
+    }
+}
+
+// A fresh browser per fetch, kept until the next one (or until dropped) so the send can reuse it.
+// The products are fetched on other tabs of it while the primary forecast loads
+#[derive(Default)]
+pub struct BrowserSource {
+    browser: Option<(Browser, Arc<Tab>)>,
+    // Consecutive cycles the primary source has failed, drives the fallback chain
+    primary_failures: u32,
+    // This cycle's products by source name and url, each taken by its `fetch_product`
+    prefetched: BTreeMap<(String, String), Result<FetchedForecast, Box<dyn Error + Send + Sync>>>,
+}
+
+impl BrowserSource {
+    pub fn new() -> BrowserSource {
+        BrowserSource::default()
+    }
+}
+
+impl ForecastSource for BrowserSource {
+    fn fetch(&mut self, config: &Config, effects: &Effects) -> Result<FetchedForecast, Box<dyn Error>> {
+        // Close the previous browser before starting another
+        self.browser = None;
+        self.prefetched.clear();
+
+        let started = Instant::now();
+        let deadline = started + Duration::from_secs(config.polling.cycle_deadline_seconds);
+        let browser = launch_browser(config)?;
+        let tab = browser.wait_for_initial_tab()?;
+
+        // With a single tab the products are fetched one after another on the primary's, by `fetch_product`
+        let jobs = match config.polling.tabs {
+            1 => Vec::new(),
+            _ => config
+                .products
+                .iter()
+                .map(|product| Job {
+                    source: &product.source,
+                    known: read_structured_forecast(effects, &OutputFiles::with_prefix(&product.prefix)),
+                })
+                .collect::<Vec<_>>(),
+        };
+        let primary_failures = &mut self.primary_failures;
+        let (fetched, primary_took, products) = thread::scope(|scope| {
+            let products = scope.spawn(|| {
+                pool::fetch_all(&browser, config.polling.tabs - 1, deadline, config, effects, &jobs)
+            });
+            let primary_started = Instant::now();
+            let fetched = fetch_with_fallback(&tab, config, effects, primary_failures);
+            let primary_took = primary_started.elapsed();
+            (fetched, primary_took, products.join().unwrap_or_default())
+        });
+
+        if !jobs.is_empty() {
+            log_durations(started.elapsed(), primary_took, &products, config.polling.tabs);
+        }
+        for (job, product) in jobs.iter().zip(products) {
+            self.prefetched
+                .insert((job.source.name.clone(), job.source.url.clone()), product.result);
+        }
+        self.browser = Some((browser, tab));
+        fetched
+    }
+
+    // Fetched alongside the primary forecast when there are tabs for it, otherwise navigates the
+    // cycle's tab rather than starting another browser
+    fn fetch_product(
+        &mut self,
+        config: &Config,
+        effects: &Effects,
+        source: &Source,
+        known: Option<&Forecast>,
+    ) -> Result<FetchedForecast, Box<dyn Error>> {
+        if let Some(fetched) = self.prefetched.remove(&(source.name.clone(), source.url.clone())) {
+            return fetched.map_err(|e| e as Box<dyn Error>);
+        }
+        let (_, tab) = self.browser.as_ref().ok_or("the browser failed to start this cycle")?;
+        fetch_validated(tab, config, effects, source, false, known)
+    }
+
+    fn tab(&self) -> Option<&Arc<Tab>> {
+        self.browser.as_ref().map(|(_, tab)| tab)
+    }
+}
+
+// The cycle's fetch time against what the same fetches would have taken one after another, to
+// size polling.tabs by
+fn log_durations(took: Duration, primary_took: Duration, products: &[Fetched], tabs: usize) {
+    let sequential = primary_took + products.iter().map(|product| product.took).sum::<Duration>();
+    info!(
+        "Fetched {} sources in {:.1}s on {} tabs, {:.1}s one after another",
+        products.len() + 1,
+        took.as_secs_f64(),
+        tabs.min(products.len() + 1),
+        sequential.as_secs_f64()
+    );
+}
+
+// Hands out prepared forecasts in order, e.g. fixtures or revisions read back from the archive
+pub struct FixtureSource {
+    forecasts: std::vec::IntoIter<FetchedForecast>,
+}
+
+impl FixtureSource {
+    pub fn new(forecasts: Vec<FetchedForecast>) -> FixtureSource {
+        FixtureSource {
+            forecasts: forecasts.into_iter(),
+        }
+    }
+}
+
+impl ForecastSource for FixtureSource {
+    fn fetch(&mut self, config: &Config, effects: &Effects) -> Result<FetchedForecast, Box<dyn Error>> {
+        let forecast = self.forecasts.next().ok_or("no more fixture forecasts")?;
+        // Held to the same checks as a fetched page
+        validate_forecast(&forecast, &config.validation, effects.now())?;
+        Ok(forecast)
+    }
+}
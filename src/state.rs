@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::conditions::Observation;
+use crate::delivery::DeliveryCheck;
+use crate::drift::StructureBaseline;
+use crate::effects::Effects;
+use crate::estimate::UpdateTimes;
+use crate::forecast::Forecast;
+use crate::format::Content;
+use crate::version::ConfigFingerprint;
+
+// Everything raven needs to remember between runs
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    // The last forecast successfully sent to the inReach, what the next send is diffed against
+    pub last_sent: Option<Forecast>,
+    // `content_hash` of the messages that send went out as, a send has to differ from it
+    pub last_sent_content: Option<u64>,
+    // Messages sent per notification channel today, for the per-day caps
+    pub daily_counts: BTreeMap<String, DailyCount>,
+    // A changed forecast waiting out the settle period before it's sent
+    pub pending_send: Option<PendingSend>,
+    // Rolling history of sampled summit conditions, oldest first
+    pub observations: Vec<Observation>,
+    // An inReach send in progress, kept until every chunk has gone so a failed send resumes
+    pub outbound: Option<OutboundSend>,
+    // Set by the first failed cycle and cleared by the next good one, so notices about the same outage
+    // can say how long it's gone on
+    pub failing_since: Option<DateTime<Utc>>,
+    // Cycles failed in a row since `failing_since`
+    pub consecutive_failures: u32,
+    // When the current run of failures was last notified, None when it hasn't been yet
+    pub failure_notified: Option<DateTime<Utc>>,
+    // Fingerprints of the abbreviated revisions before the current one, newest last, for flap suppression
+    pub recent_revisions: Vec<u64>,
+    // The latest issue time seen and when it first was, for the expected-update schedule
+    pub issue: Option<SeenIssue>,
+    // A missed update deadline that's been notified, until the update arrives
+    pub missed_update: Option<MissedUpdate>,
+    // The page structure seen over recent cycles, for drift detection
+    pub structure: StructureBaseline,
+    // The last inReach send's parts as seen on the MapShare conversation, with delivery.enabled
+    pub delivery: Option<DeliveryCheck>,
+    // The build and effective config of the last run, so a changed config is noticed at startup
+    pub build: Option<String>,
+    pub config: Option<ConfigFingerprint>,
+    // When the Garmin account transport first needed someone to log in, cleared by the next send that gets through
+    pub login_required: Option<DateTime<Utc>>,
+    // When new issue times have been seen over past weeks, for the expected update and polling.schedule = "auto"
+    pub update_times: UpdateTimes,
+    // The NWS zone forecast last sent while every MWOBS source was failing, cleared once one recovers
+    pub nws_last_sent: Option<Forecast>,
+    // The extra products' inReach sends in progress by product name, kept like `outbound`
+    pub product_outbound: BTreeMap<String, OutboundSend>,
+    // `last_sent_content` for each extra product and the NWS fallback, by product name
+    pub product_sent_content: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeenIssue {
+    pub issued: String,
+    pub seen: DateTime<Utc>,
+}
+
+// In the schedule's timezone, as the deadlines are
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MissedUpdate {
+    pub deadline: NaiveDateTime,
+    pub reminded: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboundSend {
+    // Hash of the structured forecast being sent, a partial send is only resumed for the same forecast
+    pub forecast_hash: u64,
+    pub forecast: Forecast,
+    pub chunks: Vec<OutboundChunk>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboundChunk {
+    pub text: String,
+    pub hash: u64,
+    pub sent: bool,
+}
+
+impl OutboundSend {
+    pub fn sent_count(&self) -> usize {
+        self.chunks.iter().filter(|chunk| chunk.sent).count()
+    }
+
+    // Indices of the chunks still to go, from the first unsent one
+    pub fn unsent(&self) -> Vec<usize> {
+        let first = self
+            .chunks
+            .iter()
+            .position(|chunk| !chunk.sent)
+            .unwrap_or(self.chunks.len());
+        (first..self.chunks.len()).collect()
+    }
+
+    // Carries over which chunks `previous` already sent, for a re-prepared send of the same forecast.
+    // Chunks are matched by position and hash, so one whose text changed (e.g. a new trend line) still goes out
+    pub fn resume(mut self, previous: &OutboundSend) -> OutboundSend {
+        for (chunk, old) in self.chunks.iter_mut().zip(&previous.chunks) {
+            if !old.sent || old.hash != chunk.hash {
+                break;
+            }
+            chunk.sent = true;
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingSend {
+    // Rendered for the inReach
+    pub messages: Vec<String>,
+    // What the other channels render their own messages from
+    #[serde(default)]
+    pub content: Content,
+    pub forecast: Forecast,
+    // The latest revision's time, the settle period counts from here
+    pub changed: DateTime<Utc>,
+    // `content_hash` of the messages the inReach refused, set once the other channels have had it. Kept
+    // for a later cycle, when e.g. a new note makes it a send worth making
+    #[serde(default)]
+    pub refused: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyCount {
+    pub date: NaiveDate,
+    pub count: u32,
+}
+
+impl State {
+    // A missing or unreadable state file starts fresh rather than stopping the watch loop
+    pub fn load(effects: &Effects, path: &Path) -> State {
+        let json = match effects.read(path) {
+            Some(json) => json,
+            None => return State::default(),
+        };
+
+        serde_json::from_str(&json).unwrap_or_else(|e| {
+            warn!("Ignoring unreadable state file {}: {}", path.display(), e);
+            State::default()
+        })
+    }
+
+    pub fn sent_today(&self, channel: &str, today: NaiveDate) -> u32 {
+        match self.daily_counts.get(channel) {
+            Some(daily) if daily.date == today => daily.count,
+            _ => 0,
+        }
+    }
+
+    pub fn save(&self, effects: &Effects, path: &Path) -> Result<(), Box<dyn Error>> {
+        effects.write_atomic(path, &serde_json::to_string_pretty(self)?)
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// Where the cycle keeps its outputs and state, the real filesystem or memory for replays
+pub trait Storage: Send + Sync {
+    fn read(&self, path: &Path) -> Option<String>;
+    // `length` bytes from `offset`, fewer at the end of the file
+    fn read_bytes(&self, path: &Path, offset: u64, length: usize) -> io::Result<Vec<u8>>;
+    fn size(&self, path: &Path) -> Option<u64>;
+    fn exists(&self, path: &Path) -> bool;
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()>;
+    fn write_bytes(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+    fn write_atomic(&self, path: &Path, contents: &str) -> io::Result<()>;
+    // Appends and syncs, returning the offset the bytes start at
+    fn append(&self, path: &Path, bytes: &[u8]) -> io::Result<u64>;
+    fn remove(&self, path: &Path) -> io::Result<()>;
+    fn remove_dir_all(&self, dir: &Path) -> io::Result<()>;
+    fn create_dir_all(&self, dir: &Path) -> io::Result<()>;
+    // Paths of the files and directories directly inside `dir`
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>>;
+}
+
+pub struct FileStorage;
+
+impl Storage for FileStorage {
+    fn read(&self, path: &Path) -> Option<String> {
+        fs::read_to_string(path).ok()
+    }
+
+    fn read_bytes(&self, path: &Path, offset: u64, length: usize) -> io::Result<Vec<u8>> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut bytes = Vec::with_capacity(length);
+        file.take(length as u64).read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn size(&self, path: &Path) -> Option<u64> {
+        fs::metadata(path).ok().map(|metadata| metadata.len())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        fs::write(path, contents)
+    }
+
+    fn write_bytes(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        fs::write(path, contents)
+    }
+
+    fn write_atomic(&self, path: &Path, contents: &str) -> io::Result<()> {
+        write_atomic(path, contents)
+    }
+
+    fn append(&self, path: &Path, bytes: &[u8]) -> io::Result<u64> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let offset = file.metadata()?.len();
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        Ok(offset)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir_all(&self, dir: &Path) -> io::Result<()> {
+        fs::remove_dir_all(dir)
+    }
+
+    fn create_dir_all(&self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(dir)?.map(|entry| entry.map(|entry| entry.path())).collect()
+    }
+}
+
+// Keeps everything in memory and remembers what was written, so a replay can report it
+#[derive(Default)]
+pub struct MemoryStorage {
+    files: Mutex<BTreeMap<PathBuf, String>>,
+    // Appended-to files, kept apart since they needn't be text
+    appended: Mutex<BTreeMap<PathBuf, Vec<u8>>>,
+    written: Mutex<Vec<PathBuf>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::default()
+    }
+
+    // Paths written or removed since the last call, in order
+    pub fn take_written(&self) -> Vec<PathBuf> {
+        std::mem::take(&mut *self.written.lock().unwrap())
+    }
+
+    fn insert(&self, path: &Path, contents: &str) {
+        self.files.lock().unwrap().insert(path.to_path_buf(), contents.to_string());
+        self.written.lock().unwrap().push(path.to_path_buf());
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn read(&self, path: &Path) -> Option<String> {
+        self.files.lock().unwrap().get(path).cloned()
+    }
+
+    fn read_bytes(&self, path: &Path, offset: u64, length: usize) -> io::Result<Vec<u8>> {
+        let bytes = match self.appended.lock().unwrap().get(path) {
+            Some(bytes) => bytes.clone(),
+            None => self
+                .read(path)
+                .map(String::into_bytes)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.display().to_string()))?,
+        };
+        let start = (offset as usize).min(bytes.len());
+        let end = start.saturating_add(length).min(bytes.len());
+        Ok(bytes[start..end].to_vec())
+    }
+
+    fn size(&self, path: &Path) -> Option<u64> {
+        match self.appended.lock().unwrap().get(path) {
+            Some(bytes) => Some(bytes.len() as u64),
+            None => self.read(path).map(|contents| contents.len() as u64),
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        let files = self.files.lock().unwrap();
+        let appended = self.appended.lock().unwrap();
+        files.keys().chain(appended.keys()).any(|file| file.starts_with(path))
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        self.insert(path, contents);
+        Ok(())
+    }
+
+    fn write_bytes(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.appended.lock().unwrap().insert(path.to_path_buf(), contents.to_vec());
+        self.written.lock().unwrap().push(path.to_path_buf());
+        Ok(())
+    }
+
+    fn write_atomic(&self, path: &Path, contents: &str) -> io::Result<()> {
+        self.insert(path, contents);
+        Ok(())
+    }
+
+    fn append(&self, path: &Path, bytes: &[u8]) -> io::Result<u64> {
+        let mut appended = self.appended.lock().unwrap();
+        let file = appended.entry(path.to_path_buf()).or_default();
+        let offset = file.len() as u64;
+        file.extend_from_slice(bytes);
+        self.written.lock().unwrap().push(path.to_path_buf());
+        Ok(offset)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        match self.files.lock().unwrap().remove(path) {
+            Some(_) => {
+                self.written.lock().unwrap().push(path.to_path_buf());
+                Ok(())
+            }
+            None => Err(io::Error::new(io::ErrorKind::NotFound, path.display().to_string())),
+        }
+    }
+
+    fn remove_dir_all(&self, dir: &Path) -> io::Result<()> {
+        self.files.lock().unwrap().retain(|file, _| !file.starts_with(dir));
+        self.appended.lock().unwrap().retain(|file, _| !file.starts_with(dir));
+        self.written.lock().unwrap().push(dir.to_path_buf());
+        Ok(())
+    }
+
+    fn create_dir_all(&self, _dir: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let files = self.files.lock().unwrap();
+        let appended = self.appended.lock().unwrap();
+        // Directories only exist as the files under them
+        let mut children = files
+            .keys()
+            .chain(appended.keys())
+            .filter_map(|file| file.strip_prefix(dir).ok()?.components().next())
+            .map(|child| dir.join(child))
+            .collect::<Vec<_>>();
+        children.sort();
+        children.dedup();
+        Ok(children)
+    }
+}
+
+// Writes to a temporary sibling then renames over the target, so readers never see a partial file
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+
+    let mut temp = File::create(&temp_path)?;
+    temp.write_all(contents.as_bytes())?;
+    temp.sync_all()?;
+
+    fs::rename(&temp_path, path)
+}
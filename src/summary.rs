@@ -0,0 +1,110 @@
+use crate::forecast::Forecast;
+use crate::locale::Language;
+use crate::sms::sanitize;
+use crate::trend::numeric_changes;
+
+// First message of a multi-part send: issue time, the biggest changes since the last send
+// (or the headline numbers if nothing was sent before) and the part count, e.g.
+// "Issued 5:07 AM. Tonight lo -8F (was -2F), Sat wind 90 (was 70). 4 parts"
+pub fn summary_line(
+    previous: Option<&Forecast>,
+    current: &Forecast,
+    total_parts: usize,
+    max_length: usize,
+    lang: Language,
+) -> String {
+    let strings = lang.strings();
+    let head = format!(
+        "{} {}.",
+        strings.issued,
+        current.issued.as_deref().unwrap_or(strings.not_available)
+    );
+    let tail = format!("{} {}", total_parts, strings.parts);
+
+    let items = match previous {
+        Some(previous) => numeric_changes(previous, current)
+            .iter()
+            .map(|change| change.render(lang))
+            .collect::<Vec<_>>(),
+        None => current.days.iter().take(1).map(|day| day.headline(lang)).collect(),
+    };
+
+    // Add as many items as still fit in one message after sanitization
+    let mut included: Vec<String> = Vec::new();
+    for item in items {
+        included.push(item);
+        if sanitize(&assemble(&head, &included, &tail)).chars().count() > max_length {
+            included.pop();
+            break;
+        }
+    }
+
+    assemble(&head, &included, &tail)
+}
+
+fn assemble(head: &str, items: &[String], tail: &str) -> String {
+    if items.is_empty() {
+        format!("{} {}", head, tail)
+    } else {
+        format!("{} {}. {}", head, items.join(", "), tail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sms::{prepare_messages_with_summary, TEXT_MESSAGE_LENGTH};
+
+    const ISSUED: &str = "Issued: 5:07 AM Friday, January 12, 2024";
+
+    fn forecast(low: i32, high: i32, wind: u32) -> Forecast {
+        let blocks = ["Tonight", "Saturday", "Saturday Night", "Sunday", "Sunday Night", "Monday"]
+            .iter()
+            .map(|label| {
+                format!(
+                    "{}: Highs around {}F. Lows around {}F. NW winds {}-{} mph with higher gusts.",
+                    label,
+                    high,
+                    low,
+                    wind,
+                    wind + 20
+                )
+            })
+            .collect::<Vec<_>>();
+        Forecast::parse(ISSUED, &blocks)
+    }
+
+    #[test]
+    fn many_changes_still_fit_one_message_after_sanitizing() {
+        let line = summary_line(Some(&forecast(-2, 10, 50)), &forecast(-18, 1, 80), 4, 156, Language::En);
+        assert!(sanitize(&line).chars().count() <= 156);
+        assert!(line.starts_with("Issued 5:07 AM Friday, January 12, 2024. "));
+        assert!(line.contains("(was"));
+        assert!(line.ends_with("4 parts"));
+    }
+
+    #[test]
+    fn drops_every_change_when_only_the_issue_time_fits() {
+        let line = summary_line(Some(&forecast(-2, 10, 50)), &forecast(-18, 1, 80), 4, 60, Language::En);
+        assert_eq!(line, "Issued 5:07 AM Friday, January 12, 2024. 4 parts");
+    }
+
+    #[test]
+    fn without_a_previous_send_it_carries_the_headline() {
+        let line = summary_line(None, &forecast(-8, 3, 60), 3, 156, Language::En);
+        assert!(line.contains("Tonight hi 3F, lo -8F, NW 60-80"), "{}", line);
+        assert!(line.ends_with("3 parts"));
+    }
+
+    #[test]
+    fn the_first_part_of_a_send_is_at_most_160_chars() {
+        let (previous, current) = (forecast(-2, 10, 50), forecast(-18, 1, 80));
+        let body = current.days.iter().map(|day| day.summary.clone()).collect::<Vec<_>>().join(" ");
+        let parts = prepare_messages_with_summary(&body, |total, max_length| {
+            summary_line(Some(&previous), &current, total, max_length, Language::En)
+        });
+        assert!(parts.len() > 2);
+        assert!(parts.iter().all(|part| part.chars().count() <= TEXT_MESSAGE_LENGTH));
+        assert!(parts[0].ends_with(&format!("{} parts", parts.len())));
+    }
+}
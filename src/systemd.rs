@@ -0,0 +1,123 @@
+use std::env;
+use std::path::Path;
+
+use log::warn;
+
+use crate::config::Config;
+
+// How long a single cycle (browser launch, fetch, sends) may take before systemd considers raven hung
+const CYCLE_ALLOWANCE_SECONDS: u64 = 300;
+
+// sd_notify over $NOTIFY_SOCKET, every call is a no-op when not started by systemd
+#[derive(Clone)]
+pub struct Systemd {
+    socket: Option<String>,
+}
+
+impl Systemd {
+    pub fn from_env() -> Systemd {
+        Systemd {
+            socket: env::var("NOTIFY_SOCKET").ok().filter(|socket| !socket.is_empty()),
+        }
+    }
+
+    // Type=notify units are only considered started once this is sent
+    pub fn ready(&self) {
+        self.notify("READY=1");
+    }
+
+    pub fn watchdog(&self) {
+        self.notify("WATCHDOG=1");
+    }
+
+    pub fn stopping(&self) {
+        self.notify("STOPPING=1");
+    }
+
+    fn notify(&self, state: &str) {
+        if let Some(socket) = &self.socket {
+            if let Err(e) = send(socket, state) {
+                warn!("Could not notify systemd ({}): {}", state, e);
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn send(socket: &str, state: &str) -> std::io::Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let datagram = UnixDatagram::unbound()?;
+    match socket.strip_prefix('@') {
+        // Abstract namespace socket
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            use std::os::unix::net::SocketAddr;
+
+            let address = SocketAddr::from_abstract_name(name.as_bytes())?;
+            datagram.send_to_addr(state.as_bytes(), &address)?;
+        }
+        _ => {
+            datagram.send_to(state.as_bytes(), socket)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send(_socket: &str, _state: &str) -> std::io::Result<()> {
+    Ok(())
+}
+
+pub struct UnitOptions<'a> {
+    pub executable: &'a Path,
+    pub config_path: &'a Path,
+    pub working_directory: &'a Path,
+    pub user: Option<&'a str>,
+    pub restart: &'a str,
+    pub send: bool,
+}
+
+// A Type=notify unit for `raven watch` with the watchdog sized from the polling config
+pub fn render_unit(config: &Config, options: &UnitOptions) -> String {
+    let watchdog_seconds = config.polling.interval_seconds + config.polling.jitter_seconds + CYCLE_ALLOWANCE_SECONDS;
+
+    let mut service = vec![
+        "Type=notify".to_string(),
+        "NotifyAccess=main".to_string(),
+        format!(
+            "ExecStart={} --config {} watch{}",
+            options.executable.display(),
+            options.config_path.display(),
+            if options.send { " --send" } else { "" }
+        ),
+        // Outputs and the state file are written relative to the working directory
+        format!("WorkingDirectory={}", options.working_directory.display()),
+    ];
+    if let Some(user) = options.user {
+        service.push(format!("User={}", user));
+    }
+    service.push(format!("Restart={}", options.restart));
+    service.push("RestartSec=30".to_string());
+    service.push(format!("WatchdogSec={}", watchdog_seconds));
+    service.push("Environment=RUST_LOG=info".to_string());
+    if config.inreach.reply_url().is_some() {
+        service.push(format!("# {} is set in this shell, the unit needs it too", config.inreach.reply_url_env));
+        service.push(format!("# Environment={}=...", config.inreach.reply_url_env));
+    }
+
+    format!(
+        "[Unit]\n\
+         Description=raven Mount Washington higher summits forecast watcher\n\
+         Wants=network-online.target\n\
+         After=network-online.target\n\
+         \n\
+         [Service]\n\
+         {}\n\
+         \n\
+         [Install]\n\
+         WantedBy=multi-user.target\n",
+        service.join("\n")
+    )
+}
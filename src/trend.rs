@@ -0,0 +1,114 @@
+use crate::forecast::{DayForecast, Forecast};
+use crate::locale::Language;
+
+// Alert when the forecast wind direction swings more than this between revisions
+const WIND_SWING_ALERT_DEGREES: f64 = 90.0;
+
+// Compares two forecast revisions day by day and describes any changes worth alerting on
+pub fn trend_alerts(previous: &Forecast, current: &Forecast, lang: Language) -> Vec<String> {
+    let mut alerts = Vec::new();
+
+    for day in &current.days {
+        let previous_day = match previous.day(&day.label) {
+            Some(previous_day) => previous_day,
+            None => continue,
+        };
+
+        if let (Some(old), Some(new)) = (&previous_day.wind, &day.wind) {
+            let initial_swing = old.direction.swing(new.direction);
+            let final_swing = old.final_direction().swing(new.final_direction());
+            if initial_swing > WIND_SWING_ALERT_DEGREES || final_swing > WIND_SWING_ALERT_DEGREES {
+                let strings = lang.strings();
+                alerts.push(format!(
+                    "{}: {} {}, {} {}",
+                    lang.label(&day.label),
+                    strings.wind_direction_now,
+                    new.compact(lang),
+                    strings.was,
+                    old.compact(lang)
+                ));
+            }
+        }
+    }
+
+    alerts
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    High,
+    Low,
+    Wind,
+}
+
+// Field, unit and how to read one number from a day
+type NumericField = (Field, &'static str, fn(&DayForecast) -> Option<i32>);
+
+#[derive(Debug, Clone)]
+pub struct NumericChange {
+    pub label: String,
+    pub field: Field,
+    pub unit: &'static str,
+    pub old: i32,
+    pub new: i32,
+}
+
+impl NumericChange {
+    pub fn magnitude(&self) -> i32 {
+        (self.new - self.old).abs()
+    }
+
+    // "Tonight lo -8F (was -2F)"
+    pub fn render(&self, lang: Language) -> String {
+        let strings = lang.strings();
+        let field = match self.field {
+            Field::High => strings.high,
+            Field::Low => strings.low,
+            Field::Wind => strings.wind,
+        };
+        format!(
+            "{} {} {}{} ({} {}{})",
+            lang.label(&self.label),
+            field,
+            self.new,
+            self.unit,
+            strings.was,
+            self.old,
+            self.unit
+        )
+    }
+}
+
+// Every changed number between two revisions, biggest change first
+pub fn numeric_changes(previous: &Forecast, current: &Forecast) -> Vec<NumericChange> {
+    let fields: [NumericField; 3] = [
+        (Field::High, "F", |day| day.high_f),
+        (Field::Low, "F", |day| day.low_f),
+        (Field::Wind, "", |day| day.wind.as_ref().and_then(|w| w.max_mph).map(|mph| mph as i32)),
+    ];
+
+    let mut changes = Vec::new();
+    for day in &current.days {
+        let previous_day = match previous.day(&day.label) {
+            Some(previous_day) => previous_day,
+            None => continue,
+        };
+
+        for (field, unit, value) in fields.iter() {
+            if let (Some(old), Some(new)) = (value(previous_day), value(day)) {
+                if old != new {
+                    changes.push(NumericChange {
+                        label: day.label.clone(),
+                        field: *field,
+                        unit,
+                        old,
+                        new,
+                    });
+                }
+            }
+        }
+    }
+
+    changes.sort_by_key(|change| -change.magnitude());
+    changes
+}
@@ -0,0 +1,197 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::change::stable_hash;
+use crate::config::ValidationConfig;
+use crate::error::{RavenError, SendRefusal};
+use crate::fetch::FetchedForecast;
+use crate::sms::sanitize;
+
+// Lowercase, what day block labels start with
+pub const DAY_NAMES: &[&str] = &[
+    "today", "tonight", "tomorrow", "monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday",
+];
+
+// Sanity checks applied to every fetched forecast, live or fallback, before anything is persisted.
+// Its age is taken as of `now`
+pub fn validate_forecast(
+    forecast: &FetchedForecast,
+    config: &ValidationConfig,
+    now: DateTime<Utc>,
+) -> Result<(), RavenError> {
+    check_size(forecast, config)?;
+
+    let length = forecast.full.trim().chars().count();
+    if length < config.min_length {
+        return Err(RavenError::ForecastTooShort {
+            length,
+            min_length: config.min_length,
+        });
+    }
+
+    if config.require_day_names {
+        let has_day_name = forecast.day_blocks.iter().any(|block| {
+            let block = block.to_lowercase();
+            DAY_NAMES.iter().any(|day| block.contains(day))
+        });
+        if !has_day_name {
+            return Err(RavenError::MissingDayNames);
+        }
+    }
+
+    if now.signed_duration_since(forecast.captured) > Duration::hours(config.max_age_hours) {
+        return Err(RavenError::StaleForecast {
+            source: forecast.source.clone(),
+            captured: forecast.captured,
+        });
+    }
+
+    Ok(())
+}
+
+// The narrative and day blocks together, in bytes. Cheap, so it runs before anything parses, hashes
+// or diffs a page that came back with a runaway blob of markup in the outlook
+pub fn check_size(forecast: &FetchedForecast, config: &ValidationConfig) -> Result<(), RavenError> {
+    let size = forecast.full.len() + forecast.day_blocks.iter().map(|block| block.len()).sum::<usize>();
+    if size > config.max_bytes {
+        return Err(RavenError::ForecastTooLong {
+            size,
+            max_bytes: config.max_bytes,
+        });
+    }
+    Ok(())
+}
+
+// The last check before satellite messages are spent on `body`, the forecast text being sent as
+// `messages`. `last_sent` is the `content_hash` of the last messages that went out
+pub fn validate_outgoing(
+    body: &str,
+    messages: &[String],
+    last_sent: Option<u64>,
+    config: &ValidationConfig,
+) -> Result<(), RavenError> {
+    let refuse = |refusal| Err(RavenError::SendRefused(refusal));
+    let text = sanitize(body);
+    if text.is_empty() {
+        return refuse(SendRefusal::Empty);
+    }
+
+    // Letters and digits left once every stop phrase is taken out, against those there were
+    let lower = text.to_lowercase();
+    let mut remaining = lower.clone();
+    let mut phrases = Vec::new();
+    for phrase in config.stop_phrases.iter().filter(|phrase| !phrase.trim().is_empty()) {
+        if let Some(without) = without_phrase(&remaining, &phrase.to_lowercase()) {
+            remaining = without;
+            phrases.push(phrase.clone());
+        }
+    }
+    let significant = |text: &str| text.chars().filter(|c| c.is_alphanumeric()).count();
+    if significant(&remaining) * 2 < significant(&lower) {
+        return refuse(SendRefusal::MostlyStopPhrases { phrases });
+    }
+
+    if !text.chars().any(|c| c.is_ascii_digit()) {
+        return refuse(SendRefusal::NoDigits);
+    }
+    if last_sent == Some(content_hash(messages)) {
+        return refuse(SendRefusal::SameAsLastSent);
+    }
+    Ok(())
+}
+
+// What a send is told apart from the last one by, the messages as the inReach gets them. A changed note
+// or trend line makes it a different send
+pub fn content_hash(messages: &[String]) -> u64 {
+    stable_hash(&sanitize(&messages.join(" ")))
+}
+
+// `text` with every occurrence of `phrase` as whole words swapped for a space, None when there's none.
+// "loading" isn't in "unloading"
+fn without_phrase(text: &str, phrase: &str) -> Option<String> {
+    let mut without = String::new();
+    let mut copied = 0;
+    let mut from = 0;
+    while let Some(found) = text[from..].find(phrase) {
+        let start = from + found;
+        let end = start + phrase.len();
+        if text[..start].ends_with(char::is_alphanumeric) || text[end..].starts_with(char::is_alphanumeric) {
+            from = start + text[start..].chars().next().map_or(1, char::len_utf8);
+            continue;
+        }
+        without.push_str(&text[copied..start]);
+        without.push(' ');
+        copied = end;
+        from = end;
+    }
+    if copied == 0 {
+        return None;
+    }
+    without.push_str(&text[copied..]);
+    Some(without)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn refusal(body: &str, last_sent: Option<u64>) -> Option<SendRefusal> {
+        let messages = [format!("1/1 {}", body)];
+        match validate_outgoing(body, &messages, last_sent, &ValidationConfig::default()) {
+            Ok(()) => None,
+            Err(RavenError::SendRefused(refusal)) => Some(refusal),
+            Err(e) => panic!("expected a refusal, got {}", e),
+        }
+    }
+
+    fn sent(body: &str) -> Option<u64> {
+        Some(content_hash(&[format!("1/1 {}", body)]))
+    }
+
+    #[test]
+    fn a_real_forecast_goes_out() {
+        assert!(refusal("Today: in clds, H5F, NW 50-70", sent("Today: in clds, H12F, NW 50-70")).is_none());
+    }
+
+    #[test]
+    fn refuses_an_empty_send() {
+        assert!(matches!(refusal("  \n ", None), Some(SendRefusal::Empty)));
+    }
+
+    #[test]
+    fn refuses_a_send_that_is_mostly_stop_phrases() {
+        match refusal("Loading... Read More 5", None) {
+            Some(SendRefusal::MostlyStopPhrases { phrases }) => assert_eq!(phrases, ["Read More", "Loading"]),
+            refusal => panic!("expected the stop phrases, got {:?}", refusal),
+        }
+    }
+
+    #[test]
+    fn stop_phrases_match_whole_words() {
+        assert!(refusal("Unloading, unloading 5F", None).is_none());
+        assert_eq!(without_phrase("unloading loading", "loading").as_deref(), Some("unloading  "));
+        assert_eq!(without_phrase("unloading", "loading"), None);
+    }
+
+    #[test]
+    fn refuses_a_send_without_numbers() {
+        assert!(matches!(refusal("Today: in the clouds, nw winds", None), Some(SendRefusal::NoDigits)));
+    }
+
+    #[test]
+    fn refuses_what_was_already_sent() {
+        let body = "Today: in clds, H5F, NW 50-70";
+        assert!(matches!(refusal(body, sent(body)), Some(SendRefusal::SameAsLastSent)));
+        // Only whitespace apart is still the same send
+        assert!(matches!(
+            refusal("Today:  in clds,\nH5F, NW 50-70", sent(body)),
+            Some(SendRefusal::SameAsLastSent)
+        ));
+    }
+
+    #[test]
+    fn the_same_forecast_with_another_note_is_a_new_send() {
+        let body = "Today: in clds, H5F, NW 50-70";
+        let with_note = [format!("1/1 {} Back Sunday", body)];
+        assert!(validate_outgoing(body, &with_note, sent(body), &ValidationConfig::default()).is_ok());
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+use chrono::{DateTime, Local, NaiveDate, Timelike, Utc};
+
+use crate::archive::ArchivedRevision;
+use crate::conditions::Observation;
+use crate::digest::Period;
+use crate::error::RavenError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VerifyFormat {
+    Text,
+    Csv,
+}
+
+impl FromStr for VerifyFormat {
+    type Err = RavenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(VerifyFormat::Text),
+            "csv" => Ok(VerifyFormat::Csv),
+            _ => Err(RavenError::InvalidArgument(format!("unknown format {}, expected text or csv", s))),
+        }
+    }
+}
+
+// A day's high, low and strongest wind, predicted or observed
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Extremes {
+    pub high_f: Option<i32>,
+    pub low_f: Option<i32>,
+    pub wind_mph: Option<u32>,
+}
+
+// How much of the day the observations cover
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coverage {
+    pub samples: usize,
+    // Distinct local hours with at least one sample, out of 24
+    pub hours: usize,
+}
+
+pub struct RevisionCheck {
+    pub archived: DateTime<Utc>,
+    pub issued: Option<String>,
+    pub predicted: Extremes,
+    // Predicted minus observed, None where either side is missing
+    pub high_error: Option<i64>,
+    pub low_error: Option<i64>,
+    pub wind_error: Option<i64>,
+}
+
+impl RevisionCheck {
+    // Mean absolute error over whatever could be compared
+    pub fn mean_error(&self) -> Option<f64> {
+        let errors = [self.high_error, self.low_error, self.wind_error]
+            .iter()
+            .flatten()
+            .map(|error| error.abs() as f64)
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            None
+        } else {
+            Some(errors.iter().sum::<f64>() / errors.len() as f64)
+        }
+    }
+}
+
+// "2024-02-10"
+pub fn parse_day(day: &str) -> Result<NaiveDate, RavenError> {
+    NaiveDate::parse_from_str(day.trim(), "%Y-%m-%d")
+        .map_err(|_| RavenError::InvalidArgument(format!("can't parse {}, expected e.g. 2024-02-10", day)))
+}
+
+// The extremes among the samples taken on `day`, local time
+pub fn observed_extremes(observations: &[Observation], day: NaiveDate) -> (Extremes, Coverage) {
+    let on_day = observations
+        .iter()
+        .filter(|observation| observation.observed.with_timezone(&Local).date_naive() == day)
+        .collect::<Vec<_>>();
+    let hours = on_day
+        .iter()
+        .map(|observation| observation.observed.with_timezone(&Local).hour())
+        .collect::<BTreeSet<_>>();
+
+    let extremes = Extremes {
+        high_f: on_day.iter().filter_map(|observation| observation.temperature_f).max(),
+        low_f: on_day.iter().filter_map(|observation| observation.temperature_f).min(),
+        wind_mph: on_day.iter().filter_map(|observation| observation.wind_mph).max(),
+    };
+    let coverage = Coverage {
+        samples: on_day.len(),
+        hours: hours.len(),
+    };
+    (extremes, coverage)
+}
+
+// What a revision predicted for `day`, from every period falling on it, night included. None when it
+// has no period on that day
+pub fn predicted_extremes(revision: &ArchivedRevision, day: NaiveDate) -> Option<Extremes> {
+    let periods = revision
+        .forecast
+        .days
+        .iter()
+        .filter(|period| Period::resolve(&period.label, revision.archived).is_some_and(|period| period.date == day))
+        .collect::<Vec<_>>();
+    if periods.is_empty() {
+        return None;
+    }
+
+    Some(Extremes {
+        high_f: periods.iter().filter_map(|period| period.high_f).max(),
+        low_f: periods.iter().filter_map(|period| period.low_f).min(),
+        wind_mph: periods
+            .iter()
+            .filter_map(|period| period.wind.as_ref().and_then(|wind| wind.max_mph.or(wind.min_mph)))
+            .max(),
+    })
+}
+
+// Every revision archived by the end of `day` that forecast it, oldest first
+pub fn check_revisions(revisions: &[ArchivedRevision], observed: &Extremes, day: NaiveDate) -> Vec<RevisionCheck> {
+    revisions
+        .iter()
+        .filter(|revision| revision.archived.with_timezone(&Local).date_naive() <= day)
+        .filter_map(|revision| {
+            let predicted = predicted_extremes(revision, day)?;
+            Some(RevisionCheck {
+                archived: revision.archived,
+                issued: revision.forecast.issued.clone(),
+                high_error: error(predicted.high_f, observed.high_f),
+                low_error: error(predicted.low_f, observed.low_f),
+                wind_error: error(predicted.wind_mph, observed.wind_mph),
+                predicted,
+            })
+        })
+        .collect()
+}
+
+// The revision with the lowest mean error, the later one on a tie
+pub fn most_accurate(checks: &[RevisionCheck]) -> Option<&RevisionCheck> {
+    checks
+        .iter()
+        .filter_map(|check| check.mean_error().map(|error| (check, error)))
+        .fold(None, |best: Option<(&RevisionCheck, f64)>, (check, error)| match best {
+            Some((_, best_error)) if best_error < error => best,
+            _ => Some((check, error)),
+        })
+        .map(|(check, _)| check)
+}
+
+// Predicted against observed for every revision of `day`, entirely from stored data
+pub fn render_verification(
+    revisions: &[ArchivedRevision],
+    observations: &[Observation],
+    day: NaiveDate,
+    format: VerifyFormat,
+) -> String {
+    let (observed, coverage) = observed_extremes(observations, day);
+    let checks = check_revisions(revisions, &observed, day);
+    match format {
+        VerifyFormat::Text => render_text(&checks, &observed, coverage, day),
+        VerifyFormat::Csv => render_csv(&checks, &observed),
+    }
+}
+
+fn render_text(checks: &[RevisionCheck], observed: &Extremes, coverage: Coverage, day: NaiveDate) -> String {
+    let mut out = format!("Forecast verification for {}\n\n", day.format("%a %b %-d %Y"));
+
+    if coverage.samples == 0 {
+        out.push_str("No summit conditions were sampled that day, there's nothing to verify against.\n");
+    } else {
+        out.push_str(&format!(
+            "Observed: high {}, low {}, wind {} ({} samples over {} of 24 hours)\n",
+            temperature(observed.high_f),
+            temperature(observed.low_f),
+            speed(observed.wind_mph),
+            coverage.samples,
+            coverage.hours
+        ));
+        if coverage.hours < 24 {
+            out.push_str("Some hours have no samples, the true extremes may have been missed.\n");
+        }
+    }
+    out.push('\n');
+
+    if checks.is_empty() {
+        out.push_str("No archived revision forecast this day.\n");
+        return out;
+    }
+
+    out.push_str(&format!(
+        "{:<12} {:<14} {:<14} {:<16} {}\n",
+        "Revision", "High", "Low", "Wind", "Mean error"
+    ));
+    for check in checks {
+        out.push_str(&format!(
+            "{:<12} {:<14} {:<14} {:<16} {}\n",
+            check.archived.with_timezone(&Local).format("%a %H:%M"),
+            with_error(temperature(check.predicted.high_f), check.high_error),
+            with_error(temperature(check.predicted.low_f), check.low_error),
+            with_error(speed(check.predicted.wind_mph), check.wind_error),
+            check
+                .mean_error()
+                .map(|error| format!("{:.1}", error))
+                .unwrap_or_else(|| "-".to_string())
+        ));
+    }
+
+    if let Some(best) = most_accurate(checks) {
+        out.push_str(&format!(
+            "\nMost accurate: the revision archived {} (issued {}), off by {:.1} on average\n",
+            best.archived.with_timezone(&Local).format("%a %b %-d %H:%M"),
+            best.issued.as_deref().unwrap_or("n/a"),
+            best.mean_error().unwrap_or_default()
+        ));
+    }
+    out
+}
+
+fn render_csv(checks: &[RevisionCheck], observed: &Extremes) -> String {
+    let mut out = String::from(
+        "archived,issued,predicted_high_f,observed_high_f,high_error,predicted_low_f,observed_low_f,low_error,\
+predicted_wind_mph,observed_wind_mph,wind_error,mean_error\n",
+    );
+    for check in checks {
+        let fields = [
+            check.archived.to_rfc3339(),
+            csv_quote(check.issued.as_deref().unwrap_or_default()),
+            cell(check.predicted.high_f),
+            cell(observed.high_f),
+            cell(check.high_error),
+            cell(check.predicted.low_f),
+            cell(observed.low_f),
+            cell(check.low_error),
+            cell(check.predicted.wind_mph),
+            cell(observed.wind_mph),
+            cell(check.wind_error),
+            check.mean_error().map(|error| format!("{:.2}", error)).unwrap_or_default(),
+        ];
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn error<T: Into<i64>>(predicted: Option<T>, observed: Option<T>) -> Option<i64> {
+    Some(predicted?.into() - observed?.into())
+}
+
+fn with_error(value: String, error: Option<i64>) -> String {
+    match error {
+        Some(error) => format!("{} ({:+})", value, error),
+        None => value,
+    }
+}
+
+fn temperature(value: Option<i32>) -> String {
+    value.map(|value| format!("{}F", value)).unwrap_or_else(|| "-".to_string())
+}
+
+fn speed(value: Option<u32>) -> String {
+    value.map(|value| format!("{} mph", value)).unwrap_or_else(|| "-".to_string())
+}
+
+// Missing values are empty cells, so spreadsheets see blanks rather than text
+pub fn cell<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+// Issue times have commas ("5:07 AM Saturday, February 10, 2024")
+pub fn csv_quote(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
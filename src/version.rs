@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Debug;
+use std::path::Path;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::archive::content_hash;
+use crate::config::Config;
+use crate::cycle::STATE_PATH;
+use crate::effects::Effects;
+use crate::state::State;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+// Set by build.rs
+pub const GIT_SHA: &str = match option_env!("RAVEN_GIT_SHA") {
+    Some(sha) => sha,
+    None => "unknown",
+};
+
+// e.g. "0.1.0 (3f2a9c81d0e4)"
+pub fn version() -> String {
+    format!("{} ({})", VERSION, GIT_SHA)
+}
+
+// The effective config, file and command line overrides together, hashed whole and per top-level section
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigFingerprint {
+    pub hash: String,
+    pub sections: BTreeMap<String, String>,
+}
+
+impl ConfigFingerprint {
+    pub fn of(config: &Config) -> ConfigFingerprint {
+        fn section(name: &str, value: &impl Debug) -> (String, String) {
+            (name.to_string(), content_hash(&format!("{:?}", value)))
+        }
+        let sections = vec![
+            section("user_agent", &config.user_agent),
+            section("browser_path", &config.browser_path),
+            section("lang", &config.lang),
+            section("polling", &config.polling),
+            section("source", &config.source),
+            section("fallback", &config.fallback),
+            section("validation", &config.validation),
+            section("inreach", &config.inreach),
+            section("sms", &config.sms),
+            section("html", &config.html),
+            section("output", &config.output),
+            section("debug", &config.debug),
+            section("archive", &config.archive),
+            section("change", &config.change),
+            section("notify", &config.notify),
+            section("failures", &config.failures),
+            section("drift", &config.drift),
+            section("delivery", &config.delivery),
+            section("conditions", &config.conditions),
+            section("schedule", &config.schedule),
+            section("twilio", &config.twilio),
+            section("products", &config.products),
+            section("routing", &config.routing),
+            section("retry", &config.retry),
+            section("nws", &config.nws),
+        ]
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+
+        ConfigFingerprint {
+            hash: content_hash(&format!("{:?}", sections)),
+            sections,
+        }
+    }
+
+    // The sections that differ from `previous`, including any only one of them has
+    pub fn changed_since(&self, previous: &ConfigFingerprint) -> Vec<String> {
+        let mut changed = self
+            .sections
+            .iter()
+            .filter(|(name, hash)| previous.sections.get(*name) != Some(hash))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        changed.extend(previous.sections.keys().filter(|name| !self.sections.contains_key(*name)).cloned());
+        changed
+    }
+}
+
+// Logs the build and config at startup, then records them
+pub fn record_run(config: &Config, effects: &Effects) -> Result<(), Box<dyn Error>> {
+    let fingerprint = ConfigFingerprint::of(config);
+    info!("raven {}, config {}", version(), fingerprint.hash);
+    record_config(effects, fingerprint, "since last run")
+}
+
+// When the config changed since the state file last saw it, logs which sections did and resets the
+// state they invalidate. Then records it and the build. `since` is for the log, e.g. "on reload"
+pub fn record_config(effects: &Effects, fingerprint: ConfigFingerprint, since: &str) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(STATE_PATH);
+    let mut state = State::load(effects, path);
+    if let Some(previous) = state.config.as_ref().filter(|previous| previous.hash != fingerprint.hash) {
+        let changed = fingerprint.changed_since(previous);
+        warn!("Configuration changed {}: {}", since, changed.join(", "));
+        reset_invalidated(&mut state, &changed);
+    }
+    if let Some(build) = state.build.as_deref().filter(|build| *build != version()) {
+        info!("Last run was raven {}", build);
+    }
+
+    state.build = Some(version());
+    state.config = Some(fingerprint);
+    state.save(effects, path)
+}
+
+// The baselines and histories built up under the old settings
+fn reset_invalidated(state: &mut State, changed: &[String]) {
+    let changed = |section: &str| changed.iter().any(|name| name == section);
+    if changed("source") || changed("drift") {
+        info!("Resetting the page structure baseline");
+        state.structure = Default::default();
+    }
+    if changed("source") || changed("change") {
+        info!("Resetting the flap history");
+        state.recent_revisions.clear();
+    }
+    if changed("source") {
+        info!("Resetting the learned update times");
+        state.update_times = Default::default();
+    }
+    if changed("schedule") {
+        state.missed_update = None;
+    }
+}
+
+// Everything `raven version --verbose` prints
+pub fn render_version(config: &Config, effects: &Effects, verbose: bool) -> String {
+    let mut out = format!("raven {}\n", version());
+    if !verbose {
+        return out;
+    }
+
+    let fingerprint = ConfigFingerprint::of(config);
+    let state = State::load(effects, Path::new(STATE_PATH));
+    out.push_str(&format!("config    {}\n", fingerprint.hash));
+    for (name, hash) in &fingerprint.sections {
+        out.push_str(&format!("  {:<14} {}\n", name, hash));
+    }
+    out.push_str(&format!(
+        "last run  raven {}, config {}\n",
+        state.build.as_deref().unwrap_or("n/a"),
+        state.config.as_ref().map(|config| config.hash.as_str()).unwrap_or("n/a")
+    ));
+    if let Some(previous) = state.config.as_ref().filter(|previous| previous.hash != fingerprint.hash) {
+        out.push_str(&format!("changed   {}\n", fingerprint.changed_since(previous).join(", ")));
+    }
+    out
+}
@@ -0,0 +1,442 @@
+use std::borrow::Cow;
+use std::error::Error;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use log::error;
+use rand::Rng;
+
+use crate::clock::Clock;
+use crate::config::{Config, PollSchedule, PollingConfig};
+use crate::cycle::{notify_failure, run_cycle, CycleOutcome, STATE_PATH};
+use crate::delivery::DeliveryCheck;
+use crate::effects::Effects;
+use crate::estimate::{fast_polling, UpdateTimes};
+use crate::forecast::Forecast;
+use crate::notify::PendingNotifications;
+use crate::reload::ConfigReloader;
+use crate::source::{BrowserSource, ForecastSource};
+use crate::state::State;
+use crate::trend::{numeric_changes, NumericChange};
+
+// What the watch loop reports after each cycle, in order: one of Changed, Unchanged or
+// FetchFailed, then Sent when something went to the inReach and Delivery when an earlier send was
+// checked, then the same for each extra product
+#[derive(Debug, Clone)]
+pub enum ForecastEvent {
+    // The full or abbreviated forecast was written, first run included
+    Changed {
+        old: Option<Forecast>,
+        new: Forecast,
+        // Every changed number, biggest first
+        diff: Vec<NumericChange>,
+        // Set when the corresponding file was written
+        full: Option<String>,
+        abbreviated: Option<String>,
+        alerts: Vec<String>,
+    },
+    Unchanged,
+    FetchFailed {
+        error: String,
+    },
+    Sent {
+        chunks: Vec<String>,
+    },
+    // A check of an earlier send against the MapShare conversation
+    Delivery {
+        check: DeliveryCheck,
+    },
+    // One of the configured extra products' events
+    Product {
+        name: String,
+        event: Box<ForecastEvent>,
+    },
+}
+
+impl ForecastEvent {
+    // The events a single cycle's outcome translates to
+    pub fn from_outcome(outcome: &CycleOutcome) -> Vec<ForecastEvent> {
+        let mut events = Vec::new();
+        if outcome.full.is_some() || outcome.abbreviated.is_some() {
+            events.push(ForecastEvent::Changed {
+                old: outcome.previous.clone(),
+                new: outcome.forecast.clone(),
+                diff: outcome
+                    .previous
+                    .as_ref()
+                    .map(|previous| numeric_changes(previous, &outcome.forecast))
+                    .unwrap_or_default(),
+                full: outcome.full.clone(),
+                abbreviated: outcome.abbreviated.clone(),
+                alerts: outcome.alerts.clone(),
+            });
+        } else {
+            events.push(ForecastEvent::Unchanged);
+        }
+
+        if !outcome.sent.is_empty() {
+            events.push(ForecastEvent::Sent {
+                chunks: outcome.sent.clone(),
+            });
+        }
+        if let Some(check) = &outcome.delivery {
+            events.push(ForecastEvent::Delivery { check: check.clone() });
+        }
+
+        for product in &outcome.products {
+            let product_events = match &product.result {
+                Ok(outcome) => ForecastEvent::from_outcome(outcome),
+                Err(error) => vec![ForecastEvent::FetchFailed { error: error.clone() }],
+            };
+            events.extend(product_events.into_iter().map(|event| ForecastEvent::Product {
+                name: product.name.clone(),
+                event: Box::new(event),
+            }));
+        }
+        events
+    }
+}
+
+// Stops a running watch loop, it finishes the current cycle, flushes state and returns
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    sender: Sender<()>,
+}
+
+impl ShutdownHandle {
+    pub fn shutdown(&self) {
+        // Already stopped if the loop is gone
+        let _ = self.sender.send(());
+    }
+}
+
+pub struct Shutdown {
+    receiver: Receiver<()>,
+}
+
+impl Shutdown {
+    // Waits `timeout` on `clock`, returning early with true if shutdown was requested
+    fn wait(&self, clock: &dyn Clock, timeout: Duration) -> bool {
+        clock.wait(timeout, &self.receiver)
+    }
+}
+
+pub fn shutdown_channel() -> (ShutdownHandle, Shutdown) {
+    let (sender, receiver) = mpsc::channel();
+    (ShutdownHandle { sender }, Shutdown { receiver })
+}
+
+// Polls `source` until shut down, calling `on_event` after every cycle. Background notifications
+// from one cycle finish before the next starts, and again before returning. With a `reloader` a
+// changed config file is picked up before the next cycle
+pub fn watch<F>(
+    config: &Config,
+    effects: &Effects,
+    source: &mut dyn ForecastSource,
+    send: bool,
+    shutdown: &Shutdown,
+    mut reloader: Option<&mut ConfigReloader>,
+    mut on_event: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(ForecastEvent),
+{
+    let mut config = Cow::Borrowed(config);
+    let mut notifications = PendingNotifications::none();
+
+    loop {
+        // Whatever the last cycle left running in the background finishes before the next fetch
+        if let Err(e) = notifications.wait(effects, Path::new(STATE_PATH)) {
+            error!("Could not record notifications: {}", e);
+        }
+        if let Some(reloaded) = reloader.as_deref_mut().and_then(|reloader| reloader.reload(&config, effects)) {
+            config = Cow::Owned(reloaded);
+        }
+        let config = config.as_ref();
+
+        notifications = match run_cycle(config, effects, source, send) {
+            Ok(outcome) => {
+                ForecastEvent::from_outcome(&outcome).into_iter().for_each(&mut on_event);
+                outcome.notifications
+            }
+            Err(e) => {
+                error!("Could not fetch forecast: {}", e);
+                on_event(ForecastEvent::FetchFailed { error: e.to_string() });
+                notify_failure(config, effects, source, send, e.as_ref())
+            }
+        };
+
+        // Check again for updates after the configured interval, unless asked to stop
+        if shutdown.wait(effects.clock(), poll_delay(&config.polling, effects)) {
+            break;
+        }
+    }
+
+    notifications.wait(effects, Path::new(STATE_PATH))
+}
+
+// Runs the watch loop against the live site on its own thread, events arrive on the receiver.
+// The thread's result is the loop's, available once it has been shut down
+pub fn spawn_watch(
+    config: Config,
+    effects: Effects,
+    send: bool,
+) -> (Receiver<ForecastEvent>, ShutdownHandle, JoinHandle<Result<(), String>>) {
+    let (events, receiver) = mpsc::channel();
+    let (handle, shutdown) = shutdown_channel();
+
+    let thread = thread::spawn(move || {
+        let mut source = BrowserSource::new();
+        let result = watch(&config, &effects, &mut source, send, &shutdown, None, |event| {
+            let _ = events.send(event);
+        });
+        // Closes the browser
+        drop(source);
+        result.map_err(|e| e.to_string())
+    });
+
+    (receiver, handle, thread)
+}
+
+// The fast interval in a fast window or near a learned update time, the usual one otherwise
+fn poll_delay(polling: &PollingConfig, effects: &Effects) -> Duration {
+    let times = match polling.schedule {
+        PollSchedule::Auto => State::load(effects, Path::new(STATE_PATH)).update_times,
+        PollSchedule::Fixed => UpdateTimes::default(),
+    };
+    let interval = if fast_polling(polling, &times, effects.clock().local_now().naive_local()) {
+        polling.fast_interval_seconds
+    } else {
+        polling.interval_seconds
+    };
+    let jitter = rand::thread_rng().gen_range(0, polling.jitter_seconds + 1);
+    Duration::from_secs(interval + jitter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::fetch::FetchedForecast;
+    use crate::format::{Charset, Constraints, Length, Markup};
+    use crate::notify::Notifier;
+    use crate::source::FixtureSource;
+    use crate::storage::MemoryStorage;
+    use chrono::{Local, NaiveDate, NaiveTime, TimeZone, Utc};
+    use std::sync::{Arc, Mutex};
+
+    fn fetched(config: &Config, effects: &Effects, high: i32) -> FetchedForecast {
+        let day_blocks = vec![
+            format!("Today:\nIn the clouds with snow showers. Highs around {}F. NW winds 50 to 70 mph.", high),
+            "Tonight:\nIn the clouds with snow. Lows around -10F. NW winds 60 to 80 mph.".to_string(),
+            "Saturday:\nClearing in the afternoon. Highs around 5F. W winds 40 to 60 mph.".to_string(),
+        ];
+        FetchedForecast {
+            full: format!("Issued: 5:07 AM Friday, January 12, 2024\n\n{}", day_blocks.join("\n\n")),
+            day_blocks,
+            source: config.source.name.clone(),
+            captured: effects.now(),
+            fallback: false,
+        }
+    }
+
+    #[test]
+    fn events_arrive_in_cycle_order() {
+        let config = Config::default();
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2024, 1, 12, 12, 0, 0).unwrap()));
+        let effects = Effects::with_storage(false, Arc::new(MemoryStorage::new())).with_clock(clock);
+        // The same forecast twice, then a new one, then nothing left to fetch
+        let forecasts = vec![fetched(&config, &effects, 5), fetched(&config, &effects, 5), fetched(&config, &effects, 12)];
+        let mut source = FixtureSource::new(forecasts);
+        let (handle, shutdown) = shutdown_channel();
+
+        let mut events = Vec::new();
+        watch(&config, &effects, &mut source, false, &shutdown, None, |event| {
+            if let ForecastEvent::FetchFailed { .. } = event {
+                handle.shutdown();
+            }
+            events.push(event);
+        })
+        .unwrap();
+
+        assert_eq!(events.len(), 4, "{:?}", events);
+        match &events[0] {
+            ForecastEvent::Changed { old, diff, full, .. } => {
+                assert!(old.is_none());
+                assert!(diff.is_empty());
+                assert!(full.is_some());
+            }
+            event => panic!("expected the first run's change, got {:?}", event),
+        }
+        assert!(matches!(events[1], ForecastEvent::Unchanged), "{:?}", events[1]);
+        match &events[2] {
+            ForecastEvent::Changed { old, diff, .. } => {
+                assert!(old.is_some());
+                assert_eq!(diff.len(), 1, "{:?}", diff);
+                assert_eq!((diff[0].old, diff[0].new), (5, 12));
+            }
+            event => panic!("expected the new high, got {:?}", event),
+        }
+        match &events[3] {
+            ForecastEvent::FetchFailed { error } => assert!(error.contains("no more fixture forecasts"), "{}", error),
+            event => panic!("expected the failed fetch, got {:?}", event),
+        }
+    }
+
+    // A phone allowed two notices a day that keeps what it's sent
+    #[derive(Default)]
+    struct Phone {
+        received: Mutex<Vec<String>>,
+    }
+
+    impl Notifier for Phone {
+        fn name(&self) -> String {
+            "phone".to_string()
+        }
+
+        fn channels(&self) -> Vec<String> {
+            vec!["twilio".to_string()]
+        }
+
+        fn max_messages_per_day(&self) -> Option<u32> {
+            Some(3)
+        }
+
+        fn constraints(&self) -> Constraints {
+            Constraints {
+                length: Length::Unlimited,
+                charset: Charset::Unicode,
+                markup: Markup::Plain,
+            }
+        }
+
+        fn notify(&self, messages: &[String]) -> Result<(), Box<dyn Error>> {
+            self.received.lock().unwrap().push(messages.join("\n"));
+            Ok(())
+        }
+
+        fn check(&self) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+    }
+
+    // Whichever update was out at the clock's local time on `day`, nothing once the day is over
+    struct DaySource {
+        day: NaiveDate,
+        updates: Vec<(NaiveTime, FetchedForecast)>,
+    }
+
+    impl ForecastSource for DaySource {
+        fn fetch(&mut self, _config: &Config, effects: &Effects) -> Result<FetchedForecast, Box<dyn Error>> {
+            let now = effects.clock().local_now().naive_local();
+            if now.date() != self.day {
+                return Err("the day is over".into());
+            }
+            let (_, update) = self
+                .updates
+                .iter()
+                .rev()
+                .find(|(time, _)| *time <= now.time())
+                .ok_or("nothing issued yet")?;
+            Ok(FetchedForecast {
+                captured: effects.now(),
+                ..update.clone()
+            })
+        }
+    }
+
+    fn update(high: i32, issued: &str) -> FetchedForecast {
+        let day_blocks = vec![
+            format!("Today:\nIn the clouds with snow showers. Highs around {}F. NW winds 50 to 70 mph.", high),
+            "Tonight:\nIn the clouds with snow. Lows around -10F. NW winds 60 to 80 mph.".to_string(),
+        ];
+        FetchedForecast {
+            full: format!("Issued: {} Friday, January 12, 2024\n\n{}", issued, day_blocks.join("\n\n")),
+            day_blocks,
+            source: "Higher Summits Forecast".to_string(),
+            captured: Utc::now(),
+            fallback: false,
+        }
+    }
+
+    #[test]
+    fn a_simulated_day() {
+        // Forecasts overnight wait for the morning, every half hour
+        let config = Config::parse(
+            r#"
+            [polling]
+            interval_seconds = 1800
+            jitter_seconds = 0
+
+            [routing]
+            [[routing.rules]]
+            events = ["changed"]
+            between = "22:00-06:00"
+            hold = true
+            "#,
+        )
+        .unwrap();
+        let day = NaiveDate::from_ymd_opt(2024, 1, 12).unwrap();
+        let at = |hour, minute| NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+        let start = Local.from_local_datetime(&day.and_time(at(4, 0))).unwrap().with_timezone(&Utc);
+        let clock = Arc::new(ManualClock::new(start));
+        let phone = Arc::new(Phone::default());
+        let effects = Effects::with_storage(false, Arc::new(MemoryStorage::new()))
+            .with_clock(clock.clone())
+            .with_notifiers(vec![phone.clone()]);
+        let mut source = DaySource {
+            day,
+            updates: vec![
+                (at(3, 30), update(2, "3:30 AM")),
+                // A revision during quiet hours, the morning update, then a midday and an afternoon revision
+                (at(4, 40), update(3, "4:40 AM")),
+                (at(6, 15), update(5, "6:15 AM")),
+                (at(12, 10), update(8, "12:10 PM")),
+                (at(16, 10), update(12, "4:10 PM")),
+            ],
+        };
+        let (handle, shutdown) = shutdown_channel();
+
+        let mut changed = 0;
+        let mut unchanged = 0;
+        let mut received_by_six_thirty = None;
+        watch(&config, &effects, &mut source, false, &shutdown, None, |event| {
+            let now = effects.clock().local_now().time();
+            // A cycle's notifications may still be going out when its event arrives, they're in by the next
+            if now == at(5, 30) {
+                assert!(phone.received.lock().unwrap().is_empty(), "sent during quiet hours");
+                assert!(State::load(&effects, Path::new(STATE_PATH)).pending_send.is_some());
+            }
+            if now == at(6, 30) {
+                received_by_six_thirty = Some(phone.received.lock().unwrap().clone());
+            }
+            match event {
+                ForecastEvent::Changed { .. } => changed += 1,
+                ForecastEvent::Unchanged => unchanged += 1,
+                ForecastEvent::FetchFailed { error } => {
+                    assert_eq!(error, "the day is over");
+                    handle.shutdown();
+                }
+                event => panic!("unexpected {:?}", event),
+            }
+        })
+        .unwrap();
+
+        // Polled from 04:00 to 23:30, the failure at midnight ends it
+        assert_eq!((changed, unchanged), (5, 35));
+        assert_eq!(clock.now(), start + chrono::Duration::hours(20));
+        // The revision held overnight went out on the first poll after quiet hours, before the morning update
+        let early = received_by_six_thirty.unwrap();
+        assert!(early.first().is_some_and(|message| message.contains("3F")), "{:?}", early);
+
+        // The afternoon revision was over the day's three notices and the failure went out on the next day's
+        let received = phone.received.lock().unwrap();
+        assert_eq!(received.len(), 4, "{:?}", received);
+        assert!(received[0].contains("3F"), "{}", received[0]);
+        assert!(received[1].contains("5F"), "{}", received[1]);
+        assert!(received[2].contains("8F"), "{}", received[2]);
+        assert!(received[3].contains("the day is over"), "{}", received[3]);
+    }
+}
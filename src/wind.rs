@@ -0,0 +1,280 @@
+use serde::{Deserialize, Serialize};
+
+use crate::locale::Language;
+
+// Words that introduce a change in wind direction, e.g. "W shifting NW" or "becoming northwest"
+const SHIFT_WORDS: &[&str] = &["shifting", "becoming", "veering", "backing", "turning"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    N,
+    NNE,
+    NE,
+    ENE,
+    E,
+    ESE,
+    SE,
+    SSE,
+    S,
+    SSW,
+    SW,
+    WSW,
+    W,
+    WNW,
+    NW,
+    NNW,
+}
+
+const DIRECTIONS: [Direction; 16] = [
+    Direction::N,
+    Direction::NNE,
+    Direction::NE,
+    Direction::ENE,
+    Direction::E,
+    Direction::ESE,
+    Direction::SE,
+    Direction::SSE,
+    Direction::S,
+    Direction::SSW,
+    Direction::SW,
+    Direction::WSW,
+    Direction::W,
+    Direction::WNW,
+    Direction::NW,
+    Direction::NNW,
+];
+
+impl Direction {
+    pub fn abbreviation(self) -> &'static str {
+        match self {
+            Direction::N => "N",
+            Direction::NNE => "NNE",
+            Direction::NE => "NE",
+            Direction::ENE => "ENE",
+            Direction::E => "E",
+            Direction::ESE => "ESE",
+            Direction::SE => "SE",
+            Direction::SSE => "SSE",
+            Direction::S => "S",
+            Direction::SSW => "SSW",
+            Direction::SW => "SW",
+            Direction::WSW => "WSW",
+            Direction::W => "W",
+            Direction::WNW => "WNW",
+            Direction::NW => "NW",
+            Direction::NNW => "NNW",
+        }
+    }
+
+    // Position on the compass rose, N is 0, clockwise
+    pub fn index(self) -> usize {
+        DIRECTIONS.iter().position(|d| *d == self).unwrap()
+    }
+
+    pub fn degrees(self) -> f64 {
+        self.index() as f64 * 22.5
+    }
+
+    // Accepts abbreviations ("NW"), words ("northwest", "north-northwest") and "-erly" forms ("westerly")
+    pub fn parse(word: &str) -> Option<Direction> {
+        // Abbreviations are only trusted in uppercase so words like "s" or "e" in prose don't match
+        if let Some(d) = DIRECTIONS.iter().find(|d| d.abbreviation() == word) {
+            return Some(*d);
+        }
+
+        let lower = word.to_lowercase();
+        let lower = lower.trim_end_matches("erly").replace('-', "");
+
+        // Build the abbreviation from the cardinal words, "northnorthwest" -> "NNW"
+        let mut abbreviation = String::new();
+        let mut rest = lower.as_str();
+        while !rest.is_empty() {
+            let (letter, len) = if rest.starts_with("north") {
+                ('N', 5)
+            } else if rest.starts_with("south") {
+                ('S', 5)
+            } else if rest.starts_with("east") {
+                ('E', 4)
+            } else if rest.starts_with("west") {
+                ('W', 4)
+            } else {
+                return None;
+            };
+            abbreviation.push(letter);
+            rest = &rest[len..];
+        }
+
+        DIRECTIONS.iter().find(|d| d.abbreviation() == abbreviation).copied()
+    }
+
+    // Smallest angle between two directions, 0 to 180 degrees
+    pub fn swing(self, other: Direction) -> f64 {
+        let difference = (self.degrees() - other.degrees()).abs();
+        difference.min(360.0 - difference)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Wind {
+    pub direction: Direction,
+    pub shift_to: Option<Direction>,
+    pub min_mph: Option<u32>,
+    pub max_mph: Option<u32>,
+}
+
+impl Wind {
+    // The direction the wind ends up in, after any forecast shift
+    pub fn final_direction(&self) -> Direction {
+        self.shift_to.unwrap_or(self.direction)
+    }
+
+    // Compact rendering for SMS, e.g. "W→NW 50-70" ("O→NO 50-70" in French)
+    pub fn compact(&self, lang: Language) -> String {
+        let mut compact = lang.direction(self.direction).to_string();
+        if let Some(shift_to) = self.shift_to {
+            compact.push('→');
+            compact.push_str(lang.direction(shift_to));
+        }
+        match (self.min_mph, self.max_mph) {
+            (Some(min), Some(max)) if min != max => compact.push_str(&format!(" {}-{}", min, max)),
+            (Some(speed), _) | (_, Some(speed)) => compact.push_str(&format!(" {}", speed)),
+            _ => {}
+        }
+        compact
+    }
+}
+
+// Parses the first wind forecast out of a day's prose, e.g. "Winds W 50-70 mph shifting NW"
+pub fn parse_wind(text: &str) -> Option<Wind> {
+    let words = text
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '.' || c == ';')
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>();
+
+    // Only trust a direction found near a mention of wind (but not "wind chill"), otherwise "West" could be anything
+    let (direction_index, direction) = words
+        .iter()
+        .enumerate()
+        .filter(|(i, w)| {
+            w.to_lowercase().starts_with("wind")
+                && !words.get(i + 1).is_some_and(|n| n.to_lowercase().starts_with("chill"))
+        })
+        .find_map(|(wind_index, _)| {
+            let window_start = wind_index.saturating_sub(3);
+            words
+                .iter()
+                .enumerate()
+                .skip(window_start)
+                .take(wind_index - window_start + 4)
+                .find_map(|(i, w)| Direction::parse(w).map(|d| (i, d)))
+        })?;
+
+    let rest = &words[direction_index + 1..];
+    let (min_mph, max_mph) = parse_speed(rest);
+    let shift_to = parse_shift(rest);
+
+    Some(Wind {
+        direction,
+        shift_to,
+        min_mph,
+        max_mph,
+    })
+}
+
+// Finds the first "50-70" or "50 to 70" speed range in the words
+fn parse_speed(words: &[&str]) -> (Option<u32>, Option<u32>) {
+    for (i, word) in words.iter().enumerate() {
+        let mut parts = word.splitn(2, '-');
+        let low = match parts.next().and_then(|p| p.parse::<u32>().ok()) {
+            Some(low) => low,
+            None => continue,
+        };
+        if let Some(high) = parts.next() {
+            let high = high.trim_end_matches(|c: char| !c.is_ascii_digit());
+            return (Some(low), high.parse().ok());
+        }
+        if words.get(i + 1) == Some(&"to") {
+            if let Some(high) = words.get(i + 2).and_then(|w| w.parse().ok()) {
+                return (Some(low), Some(high));
+            }
+        }
+        return (Some(low), None);
+    }
+    (None, None)
+}
+
+// Finds a direction following a shift word, allowing a couple of filler words ("shifting to the NW")
+fn parse_shift(words: &[&str]) -> Option<Direction> {
+    words
+        .iter()
+        .enumerate()
+        .filter(|(_, w)| SHIFT_WORDS.contains(&w.to_lowercase().as_str()))
+        .find_map(|(i, _)| words[i + 1..].iter().take(3).find_map(|w| Direction::parse(w)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cardinal_words_and_abbreviations() {
+        assert_eq!(Direction::parse("NW"), Some(Direction::NW));
+        assert_eq!(Direction::parse("northwest"), Some(Direction::NW));
+        assert_eq!(Direction::parse("North-northwest"), Some(Direction::NNW));
+        assert_eq!(Direction::parse("westerly"), Some(Direction::W));
+        assert_eq!(Direction::parse("South"), Some(Direction::S));
+        // Lowercase abbreviations are prose, not directions
+        assert_eq!(Direction::parse("nw"), None);
+        assert_eq!(Direction::parse("summit"), None);
+    }
+
+    #[test]
+    fn parses_a_shifting_wind() {
+        let wind = parse_wind("Winds W 50-70 mph shifting NW").unwrap();
+        assert_eq!(wind.direction, Direction::W);
+        assert_eq!(wind.shift_to, Some(Direction::NW));
+        assert_eq!((wind.min_mph, wind.max_mph), (Some(50), Some(70)));
+        assert_eq!(wind.final_direction(), Direction::NW);
+    }
+
+    #[test]
+    fn parses_a_becoming_wind_in_words() {
+        let wind = parse_wind("Northwest winds 40 to 60 mph becoming west in the afternoon.").unwrap();
+        assert_eq!(wind.direction, Direction::NW);
+        assert_eq!(wind.shift_to, Some(Direction::W));
+        assert_eq!((wind.min_mph, wind.max_mph), (Some(40), Some(60)));
+    }
+
+    #[test]
+    fn parses_a_shift_after_filler_words() {
+        let wind = parse_wind("SW winds 30-45 mph, shifting to the north-northwest overnight").unwrap();
+        assert_eq!(wind.direction, Direction::SW);
+        assert_eq!(wind.shift_to, Some(Direction::NNW));
+    }
+
+    #[test]
+    fn skips_wind_chill() {
+        let wind = parse_wind("Wind chill values to -30F. W winds 60-80 mph.").unwrap();
+        assert_eq!(wind.direction, Direction::W);
+        assert_eq!(wind.shift_to, None);
+        assert_eq!((wind.min_mph, wind.max_mph), (Some(60), Some(80)));
+        assert_eq!(parse_wind("Sunny, highs around 20F. Wind chill near zero."), None);
+    }
+
+    #[test]
+    fn renders_compactly() {
+        let wind = parse_wind("Winds W 50-70 mph shifting NW").unwrap();
+        assert_eq!(wind.compact(Language::En), "W→NW 50-70");
+        assert_eq!(wind.compact(Language::Fr), "O→NO 50-70");
+        let steady = parse_wind("NE winds 20 mph").unwrap();
+        assert_eq!(steady.compact(Language::En), "NE 20");
+    }
+
+    #[test]
+    fn measures_swings_the_short_way_round() {
+        assert_eq!(Direction::N.swing(Direction::S), 180.0);
+        assert_eq!(Direction::NW.swing(Direction::N), 45.0);
+        assert_eq!(Direction::W.swing(Direction::E), 180.0);
+        assert_eq!(Direction::NNW.swing(Direction::NNE), 45.0);
+    }
+}
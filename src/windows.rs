@@ -0,0 +1,182 @@
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::html::escape;
+
+// Where outputs, the archive and state live on Windows when no --data-dir is given. Scheduled tasks
+// start in C:\Windows\System32, so the working directory is no good there
+#[cfg(windows)]
+pub fn default_data_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("raven"))
+}
+
+// Elsewhere everything stays relative to the working directory
+#[cfg(not(windows))]
+pub fn default_data_dir() -> Option<PathBuf> {
+    None
+}
+
+// Chrome, or Edge which every Windows install has, when headless_chrome's own lookup finds neither
+#[cfg(windows)]
+pub fn browser_executable() -> Option<PathBuf> {
+    use std::env;
+
+    if let Ok(path) = headless_chrome::browser::default_executable() {
+        return Some(path);
+    }
+    let roots = ["ProgramFiles", "ProgramFiles(x86)", "LocalAppData"]
+        .iter()
+        .filter_map(|var| env::var_os(var).map(PathBuf::from))
+        .collect::<Vec<_>>();
+    [r"Google\Chrome\Application\chrome.exe", r"Microsoft\Edge\Application\msedge.exe"]
+        .iter()
+        .flat_map(|executable| roots.iter().map(move |root| root.join(executable)))
+        .find(|path| path.is_file())
+}
+
+// headless_chrome's own lookup is left to it
+#[cfg(not(windows))]
+pub fn browser_executable() -> Option<PathBuf> {
+    None
+}
+
+#[cfg(windows)]
+pub use console::{on_console_close, ConsoleCloseGuard};
+
+// ctrlc treats closing the console, logging off and shutting down like Ctrl+C but returns at once,
+// and Windows ends the process as soon as the handler returns. This handler holds Windows off
+// until the watch loop has stopped, within the few seconds it allows
+#[cfg(windows)]
+mod console {
+    use std::io;
+    use std::sync::{Condvar, Mutex, OnceLock};
+    use std::time::Duration;
+
+    use windows_sys::Win32::Foundation::{BOOL, FALSE, TRUE};
+    use windows_sys::Win32::System::Console::{
+        SetConsoleCtrlHandler, CTRL_CLOSE_EVENT, CTRL_LOGOFF_EVENT, CTRL_SHUTDOWN_EVENT,
+    };
+
+    // Windows allows 5 seconds after a close event
+    const CLOSE_GRACE: Duration = Duration::from_millis(4500);
+
+    static ON_CLOSE: OnceLock<Box<dyn Fn() + Send + Sync>> = OnceLock::new();
+    static STOPPED: (Mutex<bool>, Condvar) = (Mutex::new(false), Condvar::new());
+
+    // Marks the watch loop stopped when dropped, letting a pending close go ahead
+    pub struct ConsoleCloseGuard;
+
+    impl Drop for ConsoleCloseGuard {
+        fn drop(&mut self) {
+            let (stopped, changed) = &STOPPED;
+            *stopped.lock().unwrap() = true;
+            changed.notify_all();
+        }
+    }
+
+    // Calls `on_close` when the console is closed (or the user logs off or Windows shuts down),
+    // then waits for the returned guard to be dropped. Ctrl+C and Ctrl+Break are left to ctrlc
+    pub fn on_console_close<F: Fn() + Send + Sync + 'static>(on_close: F) -> io::Result<ConsoleCloseGuard> {
+        if ON_CLOSE.set(Box::new(on_close)).is_err() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "console close handler already set"));
+        }
+        if unsafe { SetConsoleCtrlHandler(Some(handler), TRUE) } == FALSE {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(ConsoleCloseGuard)
+    }
+
+    unsafe extern "system" fn handler(event: u32) -> BOOL {
+        match event {
+            CTRL_CLOSE_EVENT | CTRL_LOGOFF_EVENT | CTRL_SHUTDOWN_EVENT => {
+                if let Some(on_close) = ON_CLOSE.get() {
+                    on_close();
+                }
+                let (stopped, changed) = &STOPPED;
+                let stopped = stopped.lock().unwrap();
+                let _ = changed.wait_timeout_while(stopped, CLOSE_GRACE, |stopped| !*stopped);
+                TRUE
+            }
+            // On to ctrlc's handler
+            _ => FALSE,
+        }
+    }
+}
+
+pub struct TaskOptions<'a> {
+    pub executable: &'a Path,
+    pub config_path: &'a Path,
+    pub data_dir: &'a Path,
+    // DOMAIN\user the task runs as
+    pub user: &'a str,
+    pub send: bool,
+}
+
+// A Task Scheduler task running `raven watch` from boot whether or not anyone is logged on,
+// restarted when it fails. Import with `schtasks /create /tn raven /xml raven.xml`
+pub fn render_task(config: &Config, options: &TaskOptions) -> String {
+    let arguments = format!(
+        "--config \"{}\" --data-dir \"{}\" watch{}",
+        options.config_path.display(),
+        options.data_dir.display(),
+        if options.send { " --send" } else { "" }
+    );
+
+    // Tasks can't carry environment variables, the reply url has to be set for the user
+    let reply_url = match config.inreach.reply_url() {
+        Some(_) => format!(
+            "  <!-- {} is set in this shell, the task needs it too: setx {} \"...\" as {} -->\n",
+            config.inreach.reply_url_env,
+            config.inreach.reply_url_env,
+            escape(options.user)
+        ),
+        None => String::new(),
+    };
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <Task version=\"1.2\" xmlns=\"http://schemas.microsoft.com/windows/2004/02/mit/task\">\n\
+         \x20 <RegistrationInfo>\n\
+         \x20   <Description>raven Mount Washington higher summits forecast watcher</Description>\n\
+         \x20 </RegistrationInfo>\n\
+         {reply_url}\
+         \x20 <Triggers>\n\
+         \x20   <BootTrigger>\n\
+         \x20     <Enabled>true</Enabled>\n\
+         \x20     <Delay>PT1M</Delay>\n\
+         \x20   </BootTrigger>\n\
+         \x20 </Triggers>\n\
+         \x20 <Principals>\n\
+         \x20   <Principal id=\"Author\">\n\
+         \x20     <UserId>{user}</UserId>\n\
+         \x20     <LogonType>S4U</LogonType>\n\
+         \x20     <RunLevel>LeastPrivilege</RunLevel>\n\
+         \x20   </Principal>\n\
+         \x20 </Principals>\n\
+         \x20 <Settings>\n\
+         \x20   <MultipleInstancesPolicy>IgnoreNew</MultipleInstancesPolicy>\n\
+         \x20   <DisallowStartIfOnBatteries>false</DisallowStartIfOnBatteries>\n\
+         \x20   <StopIfGoingOnBatteries>false</StopIfGoingOnBatteries>\n\
+         \x20   <StartWhenAvailable>true</StartWhenAvailable>\n\
+         \x20   <RunOnlyIfNetworkAvailable>false</RunOnlyIfNetworkAvailable>\n\
+         \x20   <ExecutionTimeLimit>PT0S</ExecutionTimeLimit>\n\
+         \x20   <RestartOnFailure>\n\
+         \x20     <Interval>PT1M</Interval>\n\
+         \x20     <Count>999</Count>\n\
+         \x20   </RestartOnFailure>\n\
+         \x20 </Settings>\n\
+         \x20 <Actions Context=\"Author\">\n\
+         \x20   <Exec>\n\
+         \x20     <Command>{command}</Command>\n\
+         \x20     <Arguments>{arguments}</Arguments>\n\
+         \x20     <WorkingDirectory>{working_directory}</WorkingDirectory>\n\
+         \x20   </Exec>\n\
+         \x20 </Actions>\n\
+         </Task>\n",
+        reply_url = reply_url,
+        user = escape(options.user),
+        command = escape(&options.executable.display().to_string()),
+        arguments = escape(&arguments),
+        working_directory = escape(&options.data_dir.display().to_string()),
+    )
+}